@@ -0,0 +1,5 @@
+pub mod options;
+pub mod recorder;
+pub mod replay;
+mod test_recorder;
+mod test_replay;
@@ -0,0 +1,126 @@
+//! # Capture Options
+//!
+//! The `options` module reads the command line of the capture tool and tells which mode to run:
+//! recording the exchanges between workers and a server, printing a capture file, or replaying a
+//! recorded session against a server or a worker.
+
+use std::env;
+use std::process::exit;
+
+/// Address the recorder listens to by default, the workers connect to it instead of the server
+const DEFAULT_LISTEN: &str = "localhost:8788";
+
+/// Address of the server by default
+const DEFAULT_SERVER: &str = "localhost:8787";
+
+/// Capture file written by default
+const DEFAULT_OUTPUT: &str = "session.frakt";
+
+/// What the capture tool does:
+/// * Record: relay the workers connecting to `listen` to the `server`, writing every frame to `output`
+/// * Dump: print the frames of a capture file
+/// * ReplayWorker: play the worker side of a recorded session against a live server
+/// * ReplayServer: play the server side of a recorded session against a live worker connecting to `listen`
+pub enum Mode {
+    Record {
+        listen: String,
+        server: String,
+        output: String,
+    },
+    Dump {
+        capture: String,
+    },
+    ReplayWorker {
+        capture: String,
+        session: u32,
+        server: String,
+    },
+    ReplayServer {
+        capture: String,
+        session: u32,
+        listen: String,
+    },
+}
+
+/// to find the value of a flag
+/// * `args` - the arguments of the program
+/// * `flag` - the flag, with its `=`
+/// * Return: the value, None if the flag is not given
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .find(|arg| arg.starts_with(flag))
+        .map(|arg| arg.trim_start_matches(flag))
+}
+
+///function to get the arguments passed to the capture tool
+/// * Return: `Mode` - what the tool is asked to do
+pub fn parse_args() -> Mode {
+    let args: Vec<String> = env::args().collect();
+
+    if args.len() < 2 || args[1] == "--help" {
+        println!("Usage : ./capture --record [--listen=<host:port>] [--server=<host:port>] [--output=<file>]");
+        println!("Usage : ./capture --dump=<file>");
+        println!("Usage : ./capture --replay=<file> --against=server [--server=<host:port>] [--session=<number>]");
+        println!("Usage : ./capture --replay=<file> --against=worker [--listen=<host:port>] [--session=<number>]");
+        println!("Flag: --record (relay the workers to the server and capture every message)");
+        println!("Flag: --listen=<host:port> (address the workers connect to, localhost:8788 by default)");
+        println!("Flag: --server=<host:port> (address of the server, localhost:8787 by default)");
+        println!("Flag: --output=<file> (capture file written, session.frakt by default)");
+        println!("Flag: --dump=<file> (print the messages of a capture file)");
+        println!("Flag: --replay=<file> (replay a recorded session)");
+        println!("Flag: --against=<server|worker> (play the worker side against a server, or the server side against a worker)");
+        println!("Flag: --session=<number> (session of the capture to replay, 0 by default)");
+        // Terminer le programme
+        exit(0);
+    }
+
+    let listen = flag_value(&args, "--listen=").unwrap_or(DEFAULT_LISTEN);
+    let server = flag_value(&args, "--server=").unwrap_or(DEFAULT_SERVER);
+    let session = match flag_value(&args, "--session=") {
+        Some(session) => match session.parse::<u32>() {
+            Ok(session) => session,
+            Err(_) => {
+                eprintln!("Error while parsing session argument");
+                exit(1);
+            }
+        },
+        None => 0,
+    };
+
+    if args.iter().any(|arg| arg == "--record") {
+        return Mode::Record {
+            listen: listen.to_string(),
+            server: server.to_string(),
+            output: flag_value(&args, "--output=")
+                .unwrap_or(DEFAULT_OUTPUT)
+                .to_string(),
+        };
+    }
+    if let Some(capture) = flag_value(&args, "--dump=") {
+        return Mode::Dump {
+            capture: capture.to_string(),
+        };
+    }
+    if let Some(capture) = flag_value(&args, "--replay=") {
+        let capture = capture.to_string();
+        return match flag_value(&args, "--against=") {
+            Some("server") => Mode::ReplayWorker {
+                capture,
+                session,
+                server: server.to_string(),
+            },
+            Some("worker") => Mode::ReplayServer {
+                capture,
+                session,
+                listen: listen.to_string(),
+            },
+            _ => {
+                eprintln!("Error: --replay needs --against=server or --against=worker");
+                exit(1);
+            }
+        };
+    }
+
+    eprintln!("Error: one of --record, --dump or --replay is needed, see --help");
+    exit(1);
+}
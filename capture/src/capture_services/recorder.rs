@@ -0,0 +1,172 @@
+//! # Capture Recorder
+//!
+//! The `recorder` module sits between the workers and the server: every worker connecting to the
+//! recorder is relayed to the server on a connection of its own, and every frame going through is
+//! written to the capture file with its session, direction and timestamp before being forwarded.
+//!
+//! The frames are relayed as they are, without being deserialized, so the recorder does not take
+//! part in the handshake and both peers negotiate as if they were directly connected. TLS sessions
+//! cannot be captured: the frames are only visible in plain TCP.
+
+use std::{
+    fs::File,
+    io::Write,
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use shared_lib::{
+    capture::capture_file::{decode_header, encode_frame, CaptureWriter, Direction},
+    errors::frakt_error::FraktError,
+    messages_methods::{frame::FrameLimits, messages_methods::read_frame_with_limits},
+};
+
+use super::replay::fragment_name;
+
+/// The capture file shared by the relays of every session
+type SharedCapture = Arc<Mutex<CaptureWriter<File>>>;
+
+/// to relay the workers connecting to `listen` to the server, capturing every frame
+/// * `listen` - the address the workers connect to
+/// * `server` - the address of the server
+/// * `output` - the capture file to write
+/// * Return: an error if the capture file cannot be created or the address cannot be listened to
+pub fn record(listen: &str, server: &str, output: &str) -> Result<(), FraktError> {
+    let capture = CaptureWriter::new(File::create(output)?)?;
+    let listener = TcpListener::bind(listen)?;
+    println!(
+        "Recording the sessions from {} to {} in {}",
+        listen, server, output
+    );
+    relay_sessions(listener, server, capture)
+}
+
+/// to relay the workers accepted by `listener` to the server, capturing every frame, each worker
+/// being a new session
+/// * `listener` - the listener the workers connect to
+/// * `server` - the address of the server
+/// * `capture` - the capture file to write
+/// * Return: `()` once the listener stops accepting workers
+pub fn relay_sessions(
+    listener: TcpListener,
+    server: &str,
+    capture: CaptureWriter<File>,
+) -> Result<(), FraktError> {
+    let capture = Arc::new(Mutex::new(capture));
+    for (session, worker) in listener.incoming().enumerate() {
+        let worker = match worker {
+            Ok(worker) => worker,
+            Err(err) => {
+                eprintln!("Error accepting a worker: {}", err);
+                continue;
+            }
+        };
+        let server = match TcpStream::connect(server) {
+            Ok(server) => server,
+            Err(err) => {
+                eprintln!(
+                    "Session {}: Error connecting to the server: {}",
+                    session, err
+                );
+                continue;
+            }
+        };
+        println!("Session {}: worker connected", session);
+
+        let session = session as u32;
+        match (worker.try_clone(), server.try_clone()) {
+            (Ok(worker_reader), Ok(server_reader)) => {
+                let to_server = capture.clone();
+                thread::spawn(move || {
+                    relay(
+                        session,
+                        Direction::ToServer,
+                        worker_reader,
+                        server,
+                        to_server,
+                    )
+                });
+                let to_worker = capture.clone();
+                thread::spawn(move || {
+                    relay(
+                        session,
+                        Direction::ToWorker,
+                        server_reader,
+                        worker,
+                        to_worker,
+                    )
+                });
+            }
+            _ => eprintln!("Session {}: Error sharing the connections", session),
+        }
+    }
+    Ok(())
+}
+
+/// to forward the frames of one direction of a session, capturing them, until a peer disconnects
+/// * `session` - the number of the session
+/// * `direction` - which way the frames go
+/// * `from` - the connection the frames are read from
+/// * `to` - the connection the frames are forwarded to
+/// * `capture` - the capture file
+fn relay(
+    session: u32,
+    direction: Direction,
+    mut from: TcpStream,
+    mut to: TcpStream,
+    capture: SharedCapture,
+) {
+    // the peers enforce their own limits, the recorder must not refuse what they accept
+    let limits = FrameLimits::unlimited();
+    loop {
+        let (header, data) = match read_frame_with_limits(&mut from, &limits) {
+            Ok(frame) => frame,
+            Err(err) => {
+                if !err.is_disconnection() {
+                    eprintln!("Session {}: Error reading a message: {}", session, err);
+                }
+                break;
+            }
+        };
+
+        match capture.lock() {
+            Ok(mut capture) => {
+                if let Err(err) = capture.record(session, direction, &header, &data) {
+                    eprintln!("Session {}: Error writing the capture: {}", session, err);
+                }
+            }
+            Err(_) => eprintln!("Session {}: Capture file unavailable", session),
+        }
+        let name = match decode_header(&header) {
+            Ok(fragment) => fragment_name(&fragment),
+            Err(_) => String::from("invalid header"),
+        };
+        println!(
+            "Session {}: {} {} ({} bytes of data)",
+            session,
+            arrow(direction),
+            name,
+            data.len()
+        );
+
+        if to.write_all(&encode_frame(&header, &data)).is_err() {
+            break;
+        }
+    }
+
+    // the session ends for both peers as soon as one of them leaves
+    let _ = to.shutdown(Shutdown::Both);
+    let _ = from.shutdown(Shutdown::Both);
+    println!("Session {}: {} closed", session, arrow(direction));
+}
+
+/// to show the direction of a frame
+/// * `direction` - which way the frame went
+/// * Return: `&str` - the peers, in the order of the direction
+pub fn arrow(direction: Direction) -> &'static str {
+    match direction {
+        Direction::ToServer => "worker -> server",
+        Direction::ToWorker => "server -> worker",
+    }
+}
@@ -0,0 +1,318 @@
+//! # Capture Replay
+//!
+//! The `replay` module plays one side of a recorded session against a live peer, frame by frame in
+//! the recorded order, and reports every message of the live peer differing from the recording:
+//!
+//! - against a server, the frames of the worker are sent and the answers of the server awaited.
+//!   The server hands new task ids: they are learnt from its answers and written in place of the
+//!   recorded ones in the results and progress messages sent afterwards, with their checksum
+//!   computed again, so the server recognises its tasks.
+//! - against a worker, the frames of the server are sent to the worker connecting to the tool and
+//!   its messages awaited. The fractals are computed the same way every time, so the pixels of the
+//!   results are compared too.
+//!
+//! The handshake is replayed as recorded: a server requiring authentication sends a new challenge,
+//! the recorded proof is then refused.
+
+use std::{
+    fs::File,
+    io::{BufReader, Write},
+    net::{TcpListener, TcpStream},
+    time::Duration,
+};
+
+use shared_lib::{
+    capture::capture_file::{
+        decode_header, encode_frame, CaptureReader, CapturedMessage, Direction,
+    },
+    complementary_types::u8data::U8Data,
+    errors::frakt_error::FraktError,
+    messages::{
+        fragment_method_cbor::fragment_to_cbor,
+        fragment_method_json::fragment_to_string,
+        handshake::HeaderEncoding,
+        message::{Fragment, FragmentTask},
+    },
+    messages_methods::{
+        frame::FrameLimits, messages_methods::read_frame_with_limits, payload::checksum,
+    },
+};
+
+use super::recorder::arrow;
+
+/// Time to wait for a message of the live peer before reporting it missing
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A recorded task id, and the id the live server gave to the same task
+pub type IdMapping = (Vec<u8>, Vec<u8>);
+
+/// to keep the name of the variant of a `Fragment` only, the content may be large
+/// * `fragment` - the `Fragment`
+/// * Return: `String` - the name of the variant, for example "FragmentTask"
+pub fn fragment_name(fragment: &Fragment) -> String {
+    let name = format!("{:?}", fragment);
+    match name.find('(') {
+        Some(end) => name[..end].to_string(),
+        None => name,
+    }
+}
+
+/// to read the messages of a capture file
+/// * `capture` - the path of the capture file
+/// * Return: the captured messages, or an error if the file cannot be read
+fn read_capture(capture: &str) -> Result<Vec<CapturedMessage>, FraktError> {
+    CaptureReader::new(BufReader::new(File::open(capture)?))?.read_all()
+}
+
+/// to read the messages of one session of a capture file
+/// * `capture` - the path of the capture file
+/// * `session` - the number of the session
+/// * Return: the messages of the session, or an error if the session is not in the capture
+fn read_session(capture: &str, session: u32) -> Result<Vec<CapturedMessage>, FraktError> {
+    let messages: Vec<CapturedMessage> = read_capture(capture)?
+        .into_iter()
+        .filter(|message| message.session == session)
+        .collect();
+    if messages.is_empty() {
+        return Err(FraktError::Validation(format!(
+            "No session {} in {}",
+            session, capture
+        )));
+    }
+    Ok(messages)
+}
+
+/// to print every message of a capture file
+/// * `capture` - the path of the capture file
+/// * Return: an error if the file cannot be read
+pub fn dump(capture: &str) -> Result<(), FraktError> {
+    for message in read_capture(capture)? {
+        let fragment = match message.fragment() {
+            Ok(fragment) => format!("{:?}", fragment),
+            Err(err) => format!("invalid header: {}", err),
+        };
+        println!(
+            "Session {} +{:.3}s {} {} ({} bytes of data)",
+            message.session,
+            message.timestamp.as_secs_f64(),
+            arrow(message.direction),
+            fragment,
+            message.data.len()
+        );
+    }
+    Ok(())
+}
+
+/// to play the worker side of a recorded session against a live server
+/// * `capture` - the path of the capture file
+/// * `session` - the number of the session to replay
+/// * `server` - the address of the server
+/// * Return: the number of messages of the server differing from the recording, or an error if the server cannot be reached
+pub fn replay_against_server(
+    capture: &str,
+    session: u32,
+    server: &str,
+) -> Result<usize, FraktError> {
+    let messages = read_session(capture, session)?;
+    let mut stream = TcpStream::connect(server)?;
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    println!(
+        "Replaying the worker of session {} against {}",
+        session, server
+    );
+
+    let mut ids: Vec<IdMapping> = Vec::new();
+    let mut differences = 0;
+    for message in &messages {
+        match message.direction {
+            Direction::ToServer => {
+                let frame = with_live_ids(message, &ids)?;
+                stream.write_all(&frame)?;
+                println!("-> {}", describe(message));
+            }
+            Direction::ToWorker => {
+                let (header, data) =
+                    match read_frame_with_limits(&mut stream, &FrameLimits::unlimited()) {
+                        Ok(frame) => frame,
+                        Err(err) => {
+                            println!("<- nothing ({}), {} was recorded", err, describe(message));
+                            differences += 1;
+                            if err.is_disconnection() {
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+                let live = CapturedMessage {
+                    header,
+                    data,
+                    ..message.clone()
+                };
+                ids.extend(task_ids(message, &live));
+                differences += report(message, &live, false);
+            }
+        }
+    }
+    Ok(differences)
+}
+
+/// to play the server side of a recorded session against a live worker
+/// * `capture` - the path of the capture file
+/// * `session` - the number of the session to replay
+/// * `listen` - the address the worker connects to
+/// * Return: the number of messages of the worker differing from the recording, or an error if the worker cannot be reached
+pub fn replay_against_worker(
+    capture: &str,
+    session: u32,
+    listen: &str,
+) -> Result<usize, FraktError> {
+    let messages = read_session(capture, session)?;
+    let listener = TcpListener::bind(listen)?;
+    println!(
+        "Replaying the server of session {}, waiting for a worker on {}",
+        session, listen
+    );
+    let (mut stream, _) = listener.accept()?;
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+
+    let mut differences = 0;
+    for message in &messages {
+        match message.direction {
+            Direction::ToWorker => {
+                stream.write_all(&message.frame())?;
+                println!("-> {}", describe(message));
+            }
+            Direction::ToServer => {
+                let (header, data) =
+                    match read_frame_with_limits(&mut stream, &FrameLimits::unlimited()) {
+                        Ok(frame) => frame,
+                        Err(err) => {
+                            println!("<- nothing ({}), {} was recorded", err, describe(message));
+                            differences += 1;
+                            if err.is_disconnection() {
+                                break;
+                            }
+                            continue;
+                        }
+                    };
+                let live = CapturedMessage {
+                    header,
+                    data,
+                    ..message.clone()
+                };
+                differences += report(message, &live, true);
+            }
+        }
+    }
+    Ok(differences)
+}
+
+/// to describe a captured message in one line
+/// * `message` - the message
+/// * Return: `String` - the name of its `Fragment` and the size of its data
+fn describe(message: &CapturedMessage) -> String {
+    let name = match message.fragment() {
+        Ok(fragment) => fragment_name(&fragment),
+        Err(_) => String::from("invalid header"),
+    };
+    format!("{} ({} bytes of data)", name, message.data.len())
+}
+
+/// to print a message of the live peer, with how it differs from the recording
+/// * `recorded` - the message recorded
+/// * `live` - the message of the live peer
+/// * `compare_data` - whether the data must be the same, false when it holds ids given by the server
+/// * Return: `usize` - 1 if the message differs from the recording, 0 otherwise
+fn report(recorded: &CapturedMessage, live: &CapturedMessage, compare_data: bool) -> usize {
+    let recorded_name = describe(recorded);
+    let live_name = describe(live);
+    if recorded_name != live_name {
+        println!("<- {}, {} was recorded", live_name, recorded_name);
+        1
+    } else if compare_data && recorded.data != live.data {
+        println!("<- {}, the data differs from the recording", live_name);
+        1
+    } else {
+        println!("<- {}", live_name);
+        0
+    }
+}
+
+/// to pair the ids of the tasks recorded with the ids of the same tasks given by the live server
+/// * `recorded` - the `FragmentTask` or `FragmentBatch` recorded
+/// * `live` - the answer of the live server
+/// * Return: the recorded ids with their live counterpart, empty if the messages hold no task
+fn task_ids(recorded: &CapturedMessage, live: &CapturedMessage) -> Vec<IdMapping> {
+    let tasks = |message: &CapturedMessage| -> Vec<FragmentTask> {
+        match decode_header(&message.header) {
+            Ok(Fragment::FragmentTask(task)) => vec![task],
+            Ok(Fragment::FragmentBatch(batch)) => batch.tasks,
+            _ => Vec::new(),
+        }
+    };
+    tasks(recorded)
+        .iter()
+        .zip(tasks(live).iter())
+        .filter_map(|(recorded_task, live_task)| {
+            Some((
+                id_bytes(&recorded_task.id, &recorded.data)?.to_vec(),
+                id_bytes(&live_task.id, &live.data)?.to_vec(),
+            ))
+        })
+        .collect()
+}
+
+/// to get the bytes of an id in the data of a message
+/// * `id` - where the id is in the data
+/// * `data` - the data of the message
+/// * Return: the bytes of the id, None if it is outside of the data
+fn id_bytes<'a>(id: &U8Data, data: &'a [u8]) -> Option<&'a [u8]> {
+    let start = id.offset as usize;
+    data.get(start..start + id.count as usize)
+}
+
+/// to build the frame of a recorded worker message, with the id given by the live server to its task
+/// * `message` - the message recorded
+/// * `ids` - the recorded ids with their live counterpart
+/// * Return: the frame to send, or an error if the header cannot be encoded again
+pub fn with_live_ids(message: &CapturedMessage, ids: &[IdMapping]) -> Result<Vec<u8>, FraktError> {
+    let mut fragment = match message.fragment() {
+        Ok(fragment) => fragment,
+        Err(_) => return Ok(message.frame()),
+    };
+    let id = match &fragment {
+        Fragment::FragmentResult(result) => result.id,
+        Fragment::FragmentResultChunk(chunk) => chunk.id,
+        Fragment::FragmentProgress(progress) => progress.id,
+        _ => return Ok(message.frame()),
+    };
+    let live_id = match id_bytes(&id, &message.data).and_then(|recorded| {
+        ids.iter()
+            .find(|(old, live)| old == recorded && live.len() == recorded.len())
+    }) {
+        Some((_, live_id)) => live_id,
+        None => return Ok(message.frame()),
+    };
+
+    let mut data = message.data.clone();
+    let start = id.offset as usize;
+    data[start..start + live_id.len()].copy_from_slice(live_id);
+    match &mut fragment {
+        Fragment::FragmentResult(result) if result.checksum.is_some() => {
+            result.checksum = Some(checksum(&data));
+        }
+        Fragment::FragmentResultChunk(chunk) if chunk.checksum.is_some() => {
+            chunk.checksum = Some(checksum(&data));
+        }
+        _ => {}
+    }
+
+    let header = match message.header_encoding() {
+        HeaderEncoding::Json => fragment_to_string(&fragment)
+            .map(String::into_bytes)
+            .map_err(|err| FraktError::Serialization(format!("JSON header: {}", err)))?,
+        HeaderEncoding::Cbor => fragment_to_cbor(&fragment)
+            .map_err(|err| FraktError::Serialization(format!("CBOR header: {}", err)))?,
+    };
+    Ok(encode_frame(&header, &data))
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+use std::{
+    fs::{self, File},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    thread,
+};
+
+#[cfg(test)]
+use mock_lib::{
+    mock_server::{mandelbrot_task, MockReport, MockServer, ServerScript},
+    mock_worker::{MockWorker, Pixels},
+};
+#[cfg(test)]
+use shared_lib::{
+    capture::capture_file::{CaptureReader, CaptureWriter, CapturedMessage, Direction},
+    messages::{handshake::Capabilities, message::FragmentTask},
+};
+
+#[cfg(test)]
+use super::{recorder::relay_sessions, replay::fragment_name};
+
+/// a capture file of the temporary directory, for one test
+#[cfg(test)]
+pub fn capture_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("frakt_test_{}_{}.frakt", name, std::process::id()))
+}
+
+/// a worker computing every task of a mock server, through the recorder
+#[cfg(test)]
+pub fn record_session(
+    tasks: Vec<FragmentTask>,
+    capabilities: Capabilities,
+    path: &Path,
+) -> MockReport {
+    let server = MockServer::start(ServerScript::new(tasks)).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let recorder = listener.local_addr().unwrap();
+    let capture = CaptureWriter::new(File::create(path).unwrap()).unwrap();
+    let server_address = server.address().to_string();
+    thread::spawn(move || relay_sessions(listener, &server_address, capture));
+
+    let mut worker = MockWorker::connect(recorder).unwrap();
+    worker.handshake(capabilities).unwrap();
+    worker.run(&Pixels::Computed, 1).unwrap();
+    server.join().unwrap()
+}
+
+#[cfg(test)]
+pub fn read_messages(path: &Path) -> Vec<CapturedMessage> {
+    CaptureReader::new(File::open(path).unwrap())
+        .unwrap()
+        .read_all()
+        .unwrap()
+}
+
+#[test]
+fn test_recorder_relays_and_captures_every_frame() {
+    let path = capture_path("recorder");
+    let report = record_session(
+        vec![mandelbrot_task(8, 8), mandelbrot_task(6, 4)],
+        Capabilities::supported(),
+        &path,
+    );
+    assert_eq!(report.results.len(), 2);
+    assert!(report
+        .results
+        .iter()
+        .all(|result| result.verify(0.0).is_ok()));
+
+    let messages = read_messages(&path);
+    fs::remove_file(&path).unwrap();
+    let exchange: Vec<(Direction, String)> = messages
+        .iter()
+        .map(|message| {
+            (
+                message.direction,
+                fragment_name(&message.fragment().unwrap()),
+            )
+        })
+        .collect();
+    let expected = [
+        (Direction::ToServer, "FragmentHello"),
+        (Direction::ToWorker, "FragmentWelcome"),
+        (Direction::ToServer, "FragmentRequest"),
+        (Direction::ToWorker, "FragmentTask"),
+        (Direction::ToServer, "FragmentResult"),
+        (Direction::ToWorker, "FragmentTask"),
+        (Direction::ToServer, "FragmentResult"),
+        (Direction::ToWorker, "FragmentNoWork"),
+    ];
+    assert_eq!(
+        exchange,
+        expected
+            .iter()
+            .map(|(direction, name)| (*direction, name.to_string()))
+            .collect::<Vec<_>>()
+    );
+    assert!(messages.iter().all(|message| message.session == 0));
+    assert!(messages
+        .windows(2)
+        .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    // the result carries the id given with its task
+    assert_eq!(messages[4].data[..16], messages[3].data[..16]);
+}
@@ -0,0 +1,125 @@
+#[cfg(test)]
+use std::{fs, io::Cursor};
+
+#[cfg(test)]
+use mock_lib::mock_server::{mandelbrot_task, MockServer, ServerScript};
+#[cfg(test)]
+use shared_lib::{
+    capture::capture_file::{decode_header, CapturedMessage, Direction},
+    messages::{
+        handshake::{Capabilities, HeaderEncoding},
+        message::Fragment,
+    },
+    messages_methods::{
+        frame::FrameLimits, messages_methods::read_frame_with_limits, payload::verify_checksum,
+    },
+};
+
+#[cfg(test)]
+use super::replay::{replay_against_server, with_live_ids};
+#[cfg(test)]
+use super::test_recorder::{capture_path, read_messages, record_session};
+
+/// the first result sent by the worker in a recorded session
+#[cfg(test)]
+fn first_result(messages: &[CapturedMessage]) -> CapturedMessage {
+    messages
+        .iter()
+        .find(|message| {
+            message.direction == Direction::ToServer
+                && matches!(message.fragment(), Ok(Fragment::FragmentResult(_)))
+        })
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn test_matching_replay_reports_no_difference() {
+    let path = capture_path("replay_matching");
+    let tasks = vec![mandelbrot_task(8, 8), mandelbrot_task(6, 4)];
+    record_session(tasks.clone(), Capabilities::supported(), &path);
+
+    let server = MockServer::start(ServerScript::new(tasks)).unwrap();
+    let differences =
+        replay_against_server(path.to_str().unwrap(), 0, &server.address().to_string());
+    fs::remove_file(&path).unwrap();
+    assert_eq!(differences.unwrap(), 0);
+
+    let report = server.join().unwrap();
+    assert_eq!(report.results.len(), 2);
+    assert!(report.errors.is_empty());
+}
+
+#[test]
+fn test_different_answers_are_reported() {
+    let path = capture_path("replay_different");
+    record_session(
+        vec![mandelbrot_task(8, 8), mandelbrot_task(6, 4)],
+        Capabilities::supported(),
+        &path,
+    );
+
+    // the live server has a single task: it answers the first result with a FragmentNoWork
+    let server = MockServer::start(ServerScript::new(vec![mandelbrot_task(8, 8)])).unwrap();
+    let differences =
+        replay_against_server(path.to_str().unwrap(), 0, &server.address().to_string());
+    fs::remove_file(&path).unwrap();
+    assert!(differences.unwrap() > 0);
+    assert_eq!(server.join().unwrap().results.len(), 1);
+}
+
+#[test]
+fn test_live_ids_replace_the_recorded_ones() {
+    for header_encoding in [HeaderEncoding::Json, HeaderEncoding::Cbor] {
+        let path = capture_path(&format!("replay_ids_{:?}", header_encoding));
+        let capabilities = Capabilities {
+            header_encodings: vec![header_encoding],
+            ..Capabilities::supported()
+        };
+        record_session(vec![mandelbrot_task(8, 8)], capabilities, &path);
+        let recorded = first_result(&read_messages(&path));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(recorded.header_encoding(), header_encoding);
+
+        let recorded_id = recorded.data[..16].to_vec();
+        let live_id = vec![0xab; 16];
+        let frame = with_live_ids(&recorded, &[(recorded_id, live_id.clone())]).unwrap();
+
+        let (header, data) =
+            read_frame_with_limits(&mut Cursor::new(frame), &FrameLimits::unlimited()).unwrap();
+        assert_eq!(data[..16], live_id[..]);
+        assert_eq!(data[16..], recorded.data[16..]);
+        let (recorded_checksum, live_checksum) = match (
+            recorded.fragment().unwrap(),
+            decode_header(&header).unwrap(),
+        ) {
+            (Fragment::FragmentResult(recorded), Fragment::FragmentResult(live)) => {
+                (recorded.checksum, live.checksum)
+            }
+            other => panic!("expected two results, got {:?}", other),
+        };
+        assert!(live_checksum.is_some());
+        assert_ne!(live_checksum, recorded_checksum);
+        assert!(verify_checksum(live_checksum, &data).is_ok());
+        assert!(verify_checksum(recorded_checksum, &data).is_err());
+    }
+}
+
+#[test]
+fn test_unknown_ids_are_sent_as_recorded() {
+    let path = capture_path("replay_unknown_ids");
+    record_session(
+        vec![mandelbrot_task(8, 8)],
+        Capabilities::supported(),
+        &path,
+    );
+    let recorded = first_result(&read_messages(&path));
+    fs::remove_file(&path).unwrap();
+
+    let other = (vec![0xcd; 16], vec![0xab; 16]);
+    assert_eq!(
+        with_live_ids(&recorded, &[other]).unwrap(),
+        recorded.frame()
+    );
+    assert_eq!(with_live_ids(&recorded, &[]).unwrap(), recorded.frame());
+}
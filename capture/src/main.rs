@@ -0,0 +1,65 @@
+//! # Fractal Protocol Capture
+//!
+//! The capture tool records the messages exchanged between workers and a server, and replays a
+//! recorded session to reproduce a bug without reading the logs of both sides.
+//!
+//! ## Usage
+//!
+//! - `./capture --record [--listen=<host:port>] [--server=<host:port>] [--output=<file>]`: Relay the workers connecting to the tool (localhost:8788 by default) to the server (localhost:8787 by default), and write every message to the capture file (session.frakt by default).
+//! - `./capture --dump=<file>`: Print every message of a capture file, with its session, time and direction.
+//! - `./capture --replay=<file> --against=server [--server=<host:port>] [--session=<number>]`: Play the worker side of a recorded session against a server.
+//! - `./capture --replay=<file> --against=worker [--listen=<host:port>] [--session=<number>]`: Play the server side of a recorded session against a worker connecting to the tool.
+//!
+//! Every message is recorded with the number of its session (the connections in the order they
+//! were accepted), its direction, its timestamp, its `Fragment` header and its data: see
+//! `shared_lib::capture::capture_file` for the format. A replay reports every message of the live
+//! peer differing from the recording, and exits with an error code if there is any.
+//!
+//! ## Example
+//!
+//! ```shell
+//! ./server --port=8787
+//! ./capture --record --listen=localhost:8788 --output=bug.frakt
+//! ./worker --port=8788
+//! ./capture --dump=bug.frakt
+//! ./capture --replay=bug.frakt --against=server --session=0
+//! ```
+
+use std::process::exit;
+
+mod capture_services;
+use capture_services::options::{parse_args, Mode};
+use capture_services::{recorder, replay};
+
+fn main() {
+    let result = match parse_args() {
+        Mode::Record {
+            listen,
+            server,
+            output,
+        } => recorder::record(&listen, &server, &output).map(|_| 0),
+        Mode::Dump { capture } => replay::dump(&capture).map(|_| 0),
+        Mode::ReplayWorker {
+            capture,
+            session,
+            server,
+        } => replay::replay_against_server(&capture, session, &server),
+        Mode::ReplayServer {
+            capture,
+            session,
+            listen,
+        } => replay::replay_against_worker(&capture, session, &listen),
+    };
+
+    match result {
+        Ok(0) => {}
+        Ok(differences) => {
+            println!("{} message(s) differ from the recording", differences);
+            exit(2);
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! # Mock Peers
+//!
+//! In-process peers speaking the protocol of `shared_lib`, to test a custom worker or server
+//! without running the real binaries:
+//!
+//! - `mock_server` hands a scripted list of tasks to the workers connecting to it and reports the
+//!   results they sent, which can be checked against the pixels computed by `shared_lib`.
+//! - `mock_worker` connects to a server, computes the tasks received or answers them with canned
+//!   pixels, and gives access to every step of the exchange.
+//!
+//! ```rust
+//! use mock_lib::mock_server::{MockServer, ServerScript};
+//! use mock_lib::mock_worker::{MockWorker, Pixels};
+//! use shared_lib::messages::handshake::Capabilities;
+//!
+//! let tasks = vec![mock_lib::mock_server::mandelbrot_task(8, 8)];
+//! let server = MockServer::start(ServerScript::new(tasks)).unwrap();
+//!
+//! let mut worker = MockWorker::connect(server.address()).unwrap();
+//! worker.handshake(Capabilities::supported()).unwrap();
+//! worker.run(&Pixels::Computed, 1).unwrap();
+//!
+//! let report = server.join().unwrap();
+//! assert!(report.results[0].verify(0.0).is_ok());
+//! ```
+
+pub mod mock_server;
+pub mod mock_worker;
+mod test_mock;
@@ -0,0 +1,472 @@
+//! # Mock Server
+//!
+//! The `mock_server` module runs a server in a thread of the test, on a free port of the loopback
+//! interface. It hands the tasks of a `ServerScript` to the workers connecting to it and keeps every
+//! result they send, so a test can assert on them once the server is joined.
+//!
+//! The mock follows the protocol as the real server does:
+//!
+//! - a session opening with a `FragmentHello` is welcomed with the capabilities negotiated against
+//!   the script, other sessions are legacy sessions (JSON headers, uncompressed pixels);
+//! - a `FragmentRequest` is answered with a `FragmentTask`, or a `FragmentBatch` of up to
+//!   `maximal_work_load` tasks when batches are negotiated, or a `FragmentNoWork` once every task
+//!   is handed;
+//! - every result, or the last band of a result sent in `FragmentResultChunk`, is answered with the
+//!   next task or a `FragmentNoWork`;
+//! - a result which cannot be decoded, does not match its checksum or belongs to an unknown task is
+//!   answered with a `FragmentError`, any other message closes the session with
+//!   `ErrorCode::UnexpectedFragment`.
+//!
+//! The tasks handed stay valid across sessions, so workers opening a connection for every result
+//! are served too. The mock does not require authentication and serves the sessions one at a time.
+//! It stops once every task has a result, or once the timeout of the script is reached.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use shared_lib::{
+    complementary_types::{
+        pixelintensity::PixelIntensity, point::Point, range::Range, resolution::Resolution,
+        u8data::U8Data,
+    },
+    errors::frakt_error::FraktError,
+    fractal_implementation::fractal::FractalDescriptor,
+    fractal_types::mandelbrot::Mandelbrot,
+    messages::{
+        handshake::{check_protocol_version, Capabilities, PROTOCOL_VERSION},
+        message::{
+            ErrorCode, Fragment, FragmentBatch, FragmentError, FragmentHello, FragmentNoWork,
+            FragmentResultChunk, FragmentTask, FragmentWelcome,
+        },
+    },
+    messages_methods::{
+        frame::FrameLimits,
+        messages_methods::{read_message_with_limits, send_message, send_message_with},
+        payload::{decode_pixels, verify_checksum},
+    },
+};
+
+/// Seconds a worker is asked to wait when every task is handed
+pub const NO_WORK_RETRY_AFTER: u32 = 1;
+
+/// Size of the ids of the tasks handed by the mock, the size used by the real server
+const TASK_ID_SIZE: usize = 16;
+
+/// Time to wait between two checks for a worker connecting
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+
+/// What the mock server does:
+/// * tasks: the tasks handed to the workers, in this order
+/// * capabilities: the capabilities of the server, negotiated with the workers sending a `FragmentHello`
+/// * timeout: the time after which the server stops, even if some tasks have no result
+#[derive(Debug, Clone)]
+pub struct ServerScript {
+    pub tasks: Vec<FragmentTask>,
+    pub capabilities: Capabilities,
+    pub timeout: Duration,
+}
+
+impl ServerScript {
+    /// to script a server handing tasks with every capability of this library
+    /// * `tasks` - the tasks to hand, their id is replaced by the one given by the server
+    /// * Return: `ServerScript` - the script, stopping the server after 10 seconds
+    pub fn new(tasks: Vec<FragmentTask>) -> ServerScript {
+        ServerScript {
+            tasks,
+            capabilities: Capabilities::supported(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// A result received by the mock server:
+/// * task: the task of the script, as handed to the worker
+/// * id: the id given to the task
+/// * pixels: the pixels of the task, reassembled when they were sent band by band
+/// * bands: the number of messages the pixels were sent in, 1 for a `FragmentResult`
+#[derive(Debug, Clone)]
+pub struct ReceivedResult {
+    pub task: FragmentTask,
+    pub id: Vec<u8>,
+    pub pixels: Vec<PixelIntensity>,
+    pub bands: usize,
+}
+
+impl ReceivedResult {
+    /// to compare the pixels received with the pixels computed by this library for the same task
+    /// * `tolerance` - the largest difference accepted on `zn` and `count`
+    /// * Return: `Result<(), String>` - the first pixel differing, if any
+    pub fn verify(&self, tolerance: f32) -> Result<(), String> {
+        let expected = FractalDescriptor::get_datas(&self.task);
+        if expected.len() != self.pixels.len() {
+            return Err(format!(
+                "{} pixels received, {} expected",
+                self.pixels.len(),
+                expected.len()
+            ));
+        }
+        let close = |expected: f32, received: f32| {
+            expected == received
+                || (expected.is_nan() && received.is_nan())
+                || (expected - received).abs() <= tolerance
+        };
+        for (index, (expected, received)) in expected.iter().zip(&self.pixels).enumerate() {
+            if !close(expected.zn, received.zn) || !close(expected.count, received.count) {
+                return Err(format!(
+                    "pixel {}: ({}, {}) received, ({}, {}) expected",
+                    index, received.zn, received.count, expected.zn, expected.count
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What the mock server saw, once joined:
+/// * hellos: the `FragmentHello` of the sessions opening with a handshake
+/// * results: the results received, in the order they were completed
+/// * errors: the `FragmentError` raised by the messages of the workers, sent back to them when the message expects an answer
+/// * progress: the number of `FragmentProgress` received
+/// * sessions: the number of sessions served
+/// * unanswered: the number of tasks of the script without any result when the server stopped
+#[derive(Debug, Clone, Default)]
+pub struct MockReport {
+    pub hellos: Vec<FragmentHello>,
+    pub results: Vec<ReceivedResult>,
+    pub errors: Vec<FragmentError>,
+    pub progress: usize,
+    pub sessions: usize,
+    pub unanswered: usize,
+}
+
+/// A server running in a thread of the test
+pub struct MockServer {
+    address: SocketAddr,
+    handle: JoinHandle<Result<MockReport, FraktError>>,
+}
+
+impl MockServer {
+    /// to start a server on a free port of the loopback interface
+    /// * `script` - the tasks to hand and the capabilities of the server
+    /// * Return: the running server, or an error if no port can be listened to
+    pub fn start(script: ServerScript) -> Result<MockServer, FraktError> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+        let handle = thread::spawn(move || serve(listener, script));
+        Ok(MockServer { address, handle })
+    }
+
+    /// to get the address the workers connect to
+    /// * Return: `SocketAddr` - the address of the server
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// to wait for the server to stop, once every task has a result or the timeout is reached
+    /// * Return: what the server saw, or an error if it could not serve the workers
+    pub fn join(self) -> Result<MockReport, FraktError> {
+        self.handle.join().unwrap_or_else(|_| {
+            Err(FraktError::Validation(String::from(
+                "The mock server panicked",
+            )))
+        })
+    }
+}
+
+/// to build a small Mandelbrot task, handy to script a server
+/// * `nx` - the width of the task, in pixels
+/// * `ny` - the height of the task, in pixels
+/// * Return: `FragmentTask` - the task covering the whole Mandelbrot set
+pub fn mandelbrot_task(nx: u16, ny: u16) -> FragmentTask {
+    FragmentTask::new(
+        U8Data::new(0, TASK_ID_SIZE as u32),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(nx, ny),
+        Range::new(Point::new(-2.0, -1.5), Point::new(1.0, 1.5)),
+    )
+}
+
+/// A task handed to a worker, waiting for its pixels
+struct HandedTask {
+    task: FragmentTask,
+    pixels: Vec<PixelIntensity>,
+    bands: usize,
+}
+
+/// The tasks of the script, from handed to answered
+struct Dispatch {
+    waiting: VecDeque<FragmentTask>,
+    handed: HashMap<Vec<u8>, HandedTask>,
+    next_id: u128,
+}
+
+impl Dispatch {
+    /// to know whether every task has a result
+    fn is_done(&self) -> bool {
+        self.waiting.is_empty() && self.handed.is_empty()
+    }
+
+    /// to hand tasks to a worker, with deterministic ids
+    /// * `count` - the number of tasks the worker accepts
+    /// * Return: a `FragmentTask`, or a `FragmentBatch` of several tasks, with the ids in the data; a `FragmentNoWork` if every task is handed
+    fn hand(&mut self, count: usize) -> (Fragment, Vec<u8>) {
+        let mut tasks = Vec::new();
+        let mut ids = Vec::new();
+        while tasks.len() < count {
+            let task = match self.waiting.pop_front() {
+                Some(task) => task,
+                None => break,
+            };
+            let id = self.next_id.to_be_bytes().to_vec();
+            self.next_id += 1;
+            let task = FragmentTask {
+                id: U8Data::new(ids.len() as u32, TASK_ID_SIZE as u32),
+                ..task
+            };
+            self.handed.insert(
+                id.clone(),
+                HandedTask {
+                    task,
+                    pixels: Vec::new(),
+                    bands: 0,
+                },
+            );
+            ids.extend(id);
+            tasks.push(task);
+        }
+        match tasks.len() {
+            0 => (
+                Fragment::FragmentNoWork(FragmentNoWork::new(NO_WORK_RETRY_AFTER)),
+                Vec::new(),
+            ),
+            1 => (Fragment::FragmentTask(tasks[0]), ids),
+            _ => (Fragment::FragmentBatch(FragmentBatch::new(tasks)), ids),
+        }
+    }
+}
+
+/// to serve the workers one session at a time, until every task has a result or the timeout
+/// * `listener` - the socket the workers connect to
+/// * `script` - the tasks to hand and the capabilities of the server
+/// * Return: what the server saw, or an error if the socket failed
+fn serve(listener: TcpListener, script: ServerScript) -> Result<MockReport, FraktError> {
+    let deadline = Instant::now() + script.timeout;
+    let mut dispatch = Dispatch {
+        waiting: script.tasks.iter().copied().collect(),
+        handed: HashMap::new(),
+        next_id: 0,
+    };
+    let mut report = MockReport::default();
+
+    // the deadline is checked between two connections: accepting must not block
+    listener.set_nonblocking(true)?;
+    while !dispatch.is_done() && Instant::now() < deadline {
+        let mut stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+            Err(err) => return Err(FraktError::Transport(err)),
+        };
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(deadline.saturating_duration_since(Instant::now())))?;
+        report.sessions += 1;
+
+        if let Err(err) = serve_session(&mut stream, &script, &mut dispatch, &mut report) {
+            if !err.is_disconnection() && !err.is_timeout() {
+                return Err(err);
+            }
+        }
+    }
+
+    report.unanswered = dispatch.waiting.len() + dispatch.handed.len();
+    Ok(report)
+}
+
+/// to serve one worker until it disconnects, breaks the protocol or every task has a result
+/// * `stream` - the connection of the worker
+/// * `script` - the capabilities of the server
+/// * `dispatch` - the tasks of the script
+/// * `report` - what the server saw
+/// * Return: an error if the connection failed
+fn serve_session(
+    stream: &mut TcpStream,
+    script: &ServerScript,
+    dispatch: &mut Dispatch,
+    report: &mut MockReport,
+) -> Result<(), FraktError> {
+    let mut capabilities = Capabilities::legacy();
+    let mut first_message = true;
+    while !dispatch.is_done() {
+        let limits = FrameLimits::new(capabilities.max_message_size);
+        let (fragment, data) =
+            read_message_with_limits(stream, capabilities.header_encoding(), &limits)?;
+
+        let answer = match (fragment, first_message) {
+            (Fragment::FragmentHello(hello), true) => {
+                let negotiated = check_protocol_version(hello.protocol_version)
+                    .and_then(|_| script.capabilities.negotiate(&hello.capabilities));
+                report.hellos.push(hello);
+                // the handshake is always in JSON
+                match negotiated {
+                    Ok(negotiated) => {
+                        let welcome = FragmentWelcome::accept(PROTOCOL_VERSION, negotiated.clone());
+                        send_message(stream, Fragment::FragmentWelcome(welcome), &[])?;
+                        capabilities = negotiated;
+                    }
+                    Err(reason) => {
+                        let welcome = FragmentWelcome::refuse(
+                            PROTOCOL_VERSION,
+                            reason,
+                            script.capabilities.clone(),
+                        );
+                        return send_message(stream, Fragment::FragmentWelcome(welcome), &[]);
+                    }
+                }
+                None
+            }
+            (Fragment::FragmentRequest(request), _) => {
+                let count = if capabilities.batch {
+                    request.maximal_work_load.max(1) as usize
+                } else {
+                    1
+                };
+                Some(dispatch.hand(count))
+            }
+            (Fragment::FragmentResult(result), _) => receive_band(
+                FragmentResultChunk::whole(result),
+                &data,
+                &capabilities,
+                dispatch,
+                report,
+            ),
+            (Fragment::FragmentResultChunk(chunk), _) => {
+                receive_band(chunk, &data, &capabilities, dispatch, report)
+            }
+            (Fragment::FragmentProgress(_), _) => {
+                report.progress += 1;
+                None
+            }
+            (fragment, _) => {
+                let error = FragmentError::new(
+                    ErrorCode::UnexpectedFragment,
+                    format!("{:?} is not expected from a worker", fragment),
+                );
+                report.errors.push(error.clone());
+                let encoding = capabilities.header_encoding();
+                return send_message_with(stream, Fragment::FragmentError(error), &[], encoding);
+            }
+        };
+        first_message = false;
+
+        if let Some((fragment, data)) = answer {
+            send_message_with(stream, fragment, &data, capabilities.header_encoding())?;
+        }
+    }
+    Ok(())
+}
+
+/// to receive the pixels of a task, the whole result or one of its bands
+/// * `chunk` - the band received, a `FragmentResult` being its only band
+/// * `data` - the data of the message: the id of the task, then its pixels
+/// * `capabilities` - the capabilities negotiated for the session
+/// * `dispatch` - the tasks of the script
+/// * `report` - what the server saw
+/// * Return: the answer to send, None for a band which is not the last one
+fn receive_band(
+    chunk: FragmentResultChunk,
+    data: &[u8],
+    capabilities: &Capabilities,
+    dispatch: &mut Dispatch,
+    report: &mut MockReport,
+) -> Option<(Fragment, Vec<u8>)> {
+    let reject = |report: &mut MockReport, code: ErrorCode, message: String| {
+        let error = FragmentError::new(code, message);
+        report.errors.push(error.clone());
+        chunk
+            .last
+            .then(|| (Fragment::FragmentError(error), Vec::new()))
+    };
+
+    let start = chunk.id.offset as usize;
+    let id = match data.get(start..start + chunk.id.count as usize) {
+        Some(id) => id.to_vec(),
+        None => {
+            return reject(
+                report,
+                ErrorCode::InvalidResult,
+                String::from("The id is outside of the data"),
+            )
+        }
+    };
+    if let Err(err) = verify_checksum(chunk.checksum, data) {
+        return reject(report, ErrorCode::ChecksumMismatch, err.to_string());
+    }
+    let handed = match dispatch.handed.get_mut(&id) {
+        Some(handed) => handed,
+        None => {
+            return reject(
+                report,
+                ErrorCode::UnknownTask,
+                format!("No task with the id {:?}", id),
+            )
+        }
+    };
+
+    let pixels = data
+        .get(chunk.pixels.offset as usize..)
+        .ok_or_else(|| FraktError::Validation(String::from("The pixels are outside of the data")))
+        .and_then(|payload| {
+            decode_pixels(payload, capabilities.payload_encoding(), chunk.pixels.count)
+        });
+    let pixels = match pixels {
+        Ok(pixels) if chunk.first_pixel as usize == handed.pixels.len() => pixels,
+        Ok(_) => {
+            return reject(
+                report,
+                ErrorCode::InvalidResult,
+                format!(
+                    "Band starting at pixel {}, {} pixels received before",
+                    chunk.first_pixel,
+                    handed.pixels.len()
+                ),
+            )
+        }
+        Err(err) => return reject(report, ErrorCode::InvalidResult, err.to_string()),
+    };
+    handed.pixels.extend(pixels);
+    handed.bands += 1;
+
+    if !chunk.last {
+        return None;
+    }
+    let handed = dispatch.handed.remove(&id)?;
+    let total = handed.task.resolution.nx as usize * handed.task.resolution.ny as usize;
+    if handed.pixels.len() != total {
+        // the task is handed again, its result is incomplete
+        dispatch.waiting.push_back(handed.task);
+        return reject(
+            report,
+            ErrorCode::InvalidResult,
+            format!(
+                "{} pixels received, {} expected",
+                handed.pixels.len(),
+                total
+            ),
+        );
+    }
+    report.results.push(ReceivedResult {
+        task: handed.task,
+        id,
+        pixels: handed.pixels,
+        bands: handed.bands,
+    });
+    Some(dispatch.hand(1))
+}
@@ -0,0 +1,279 @@
+//! # Mock Worker
+//!
+//! The `mock_worker` module connects to a server as a worker would, one step at a time: the test
+//! chooses whether to open a handshake, how many tasks to ask for, and which pixels to answer them
+//! with, computed by `shared_lib` or canned. `MockWorker::run` chains the steps until the server
+//! has no more task to hand.
+//!
+//! A worker which does not open a handshake is a legacy worker: it sends JSON headers and
+//! uncompressed pixels, and keeps its session open to send its results.
+
+use std::{
+    collections::VecDeque,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+use shared_lib::{
+    complementary_types::{pixelintensity::PixelIntensity, u8data::U8Data},
+    errors::frakt_error::FraktError,
+    fractal_implementation::fractal::FractalDescriptor,
+    messages::{
+        handshake::{Capabilities, PROTOCOL_VERSION},
+        message::{
+            Fragment, FragmentHello, FragmentProgress, FragmentRequest, FragmentResult,
+            FragmentResultChunk, FragmentTask,
+        },
+    },
+    messages_methods::{
+        frame::FrameLimits,
+        messages_methods::{read_message, read_message_with_limits, send_message_with},
+        payload::{checksum, encode_pixels},
+    },
+};
+
+/// Name announced by the mock worker
+pub const MOCK_WORKER_NAME: &str = "mock_worker";
+
+/// Time to wait for an answer of the server
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The pixels the mock worker answers a task with:
+/// * Computed: the pixels computed by `shared_lib`
+/// * Canned: the same pixel everywhere, without computing anything
+#[derive(Debug, Clone)]
+pub enum Pixels {
+    Computed,
+    Canned(PixelIntensity),
+}
+
+impl Pixels {
+    /// to get the pixels of a task
+    /// * `task` - the task to answer
+    /// * Return: `Vec<PixelIntensity>` - one pixel for every point of the resolution of the task
+    pub fn for_task(&self, task: &FragmentTask) -> Vec<PixelIntensity> {
+        match self {
+            Pixels::Computed => FractalDescriptor::get_datas(task),
+            Pixels::Canned(pixel) => {
+                vec![pixel.clone(); task.resolution.nx as usize * task.resolution.ny as usize]
+            }
+        }
+    }
+}
+
+/// What the server answered to a request or a result:
+/// * Tasks: the tasks handed, each one with its id at the start of its data
+/// * NoWork: no task for now, the server asks to wait this number of seconds
+#[derive(Debug, Clone)]
+pub enum Assignment {
+    Tasks(Vec<(FragmentTask, Vec<u8>)>),
+    NoWork(u32),
+}
+
+/// A worker connected to a server
+pub struct MockWorker {
+    stream: TcpStream,
+    capabilities: Capabilities,
+}
+
+impl MockWorker {
+    /// to connect to a server, as a legacy worker until `handshake` is called
+    /// * `address` - the address of the server
+    /// * Return: the worker, or an error if the server cannot be reached
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<MockWorker, FraktError> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+        Ok(MockWorker {
+            stream,
+            capabilities: Capabilities::legacy(),
+        })
+    }
+
+    /// to get the capabilities used for the session
+    /// * Return: `&Capabilities` - the negotiated capabilities, the legacy ones before a handshake
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// to open the session with a handshake and keep the capabilities negotiated by the server
+    /// * `capabilities` - the capabilities to announce
+    /// * Return: the negotiated capabilities, or `FraktError::Refused` if the server refused the worker
+    pub fn handshake(&mut self, capabilities: Capabilities) -> Result<Capabilities, FraktError> {
+        let hello = FragmentHello::new(
+            PROTOCOL_VERSION,
+            String::from(MOCK_WORKER_NAME),
+            capabilities,
+        );
+        self.send(Fragment::FragmentHello(hello), &[])?;
+        let welcome = match read_message(&mut self.stream)? {
+            (Fragment::FragmentWelcome(welcome), _) => welcome,
+            (fragment, _) => return Err(FraktError::unexpected("FragmentWelcome", &fragment)),
+        };
+        if !welcome.accepted {
+            return Err(FraktError::Refused(welcome.reason.unwrap_or_default()));
+        }
+        self.capabilities = welcome.capabilities;
+        Ok(self.capabilities.clone())
+    }
+
+    /// to send any message, with the header encoding of the session
+    /// * `fragment` - the `Fragment` to send
+    /// * `data` - the data following the `Fragment`
+    /// * Return: an error if the message cannot be sent
+    pub fn send(&mut self, fragment: Fragment, data: &[u8]) -> Result<(), FraktError> {
+        let encoding = match fragment {
+            // the handshake is always in JSON
+            Fragment::FragmentHello(_) => Capabilities::legacy().header_encoding(),
+            _ => self.capabilities.header_encoding(),
+        };
+        send_message_with(&mut self.stream, fragment, data, encoding)
+    }
+
+    /// to read any message, with the header encoding of the session
+    /// * Return: the `Fragment` and its data, or an error if nothing valid was received in time
+    pub fn receive(&mut self) -> Result<(Fragment, Vec<u8>), FraktError> {
+        let limits = FrameLimits::new(self.capabilities.max_message_size);
+        read_message_with_limits(
+            &mut self.stream,
+            self.capabilities.header_encoding(),
+            &limits,
+        )
+    }
+
+    /// to ask the server for work
+    /// * `maximal_work_load` - the number of tasks the worker accepts at once
+    /// * Return: the answer of the server, `FraktError::Rejected` if it sent a `FragmentError`
+    pub fn request(&mut self, maximal_work_load: u32) -> Result<Assignment, FraktError> {
+        let request = FragmentRequest::new(String::from(MOCK_WORKER_NAME), maximal_work_load);
+        self.send(Fragment::FragmentRequest(request), &[])?;
+        self.assignment()
+    }
+
+    /// to tell the server how far the computation of a task is
+    /// * `id` - the id of the task
+    /// * `percentage` - the share of the pixels computed
+    /// * Return: an error if the message cannot be sent
+    pub fn send_progress(&mut self, id: &[u8], percentage: u8) -> Result<(), FraktError> {
+        let progress = FragmentProgress::new(U8Data::new(0, id.len() as u32), percentage);
+        self.send(Fragment::FragmentProgress(progress), id)
+    }
+
+    /// to send the pixels of a task in a single `FragmentResult`
+    /// * `task` - the task, as returned in an `Assignment`
+    /// * `id` - the id of the task
+    /// * `pixels` - the pixels of the task
+    /// * Return: the answer of the server, `FraktError::Rejected` if it sent a `FragmentError`
+    pub fn send_result(
+        &mut self,
+        task: &FragmentTask,
+        id: &[u8],
+        pixels: &[PixelIntensity],
+    ) -> Result<Assignment, FraktError> {
+        let data = self.result_data(id, pixels)?;
+        let mut result = FragmentResult::create(task);
+        result.checksum = self.checksum(&data);
+        self.send(Fragment::FragmentResult(result), &data)?;
+        self.assignment()
+    }
+
+    /// to send the pixels of a task band by band with `FragmentResultChunk`, whether the server
+    /// accepts it or not
+    /// * `task` - the task, as returned in an `Assignment`
+    /// * `id` - the id of the task
+    /// * `pixels` - the pixels of the task
+    /// * `rows` - the number of rows of a band
+    /// * Return: the answer of the server to the last band, `FraktError::Rejected` if it sent a `FragmentError`
+    pub fn send_result_in_bands(
+        &mut self,
+        task: &FragmentTask,
+        id: &[u8],
+        pixels: &[PixelIntensity],
+        rows: u16,
+    ) -> Result<Assignment, FraktError> {
+        let band = task.resolution.nx as usize * rows.max(1) as usize;
+        for (index, band_pixels) in pixels.chunks(band).enumerate() {
+            let data = self.result_data(id, band_pixels)?;
+            let mut chunk =
+                FragmentResultChunk::create(task, (index * band) as u32, band_pixels.len() as u32);
+            chunk.checksum = self.checksum(&data);
+            self.send(Fragment::FragmentResultChunk(chunk), &data)?;
+        }
+        self.assignment()
+    }
+
+    /// to compute the tasks handed by the server and send their results, until it has no more
+    /// * `pixels` - the pixels to answer the tasks with
+    /// * `maximal_work_load` - the number of tasks asked for at once
+    /// * Return: the tasks answered, or an error if the server rejected a message or left
+    pub fn run(
+        &mut self,
+        pixels: &Pixels,
+        maximal_work_load: u32,
+    ) -> Result<Vec<FragmentTask>, FraktError> {
+        let mut pending = VecDeque::new();
+        if let Assignment::Tasks(tasks) = self.request(maximal_work_load)? {
+            pending.extend(tasks);
+        }
+        let mut answered = Vec::new();
+        while let Some((task, id)) = pending.pop_front() {
+            if let Assignment::Tasks(tasks) =
+                self.send_result(&task, &id, &pixels.for_task(&task))?
+            {
+                pending.extend(tasks);
+            }
+            answered.push(task);
+        }
+        Ok(answered)
+    }
+
+    /// to read the answer of the server to a request or a result
+    /// * Return: the tasks handed or the delay before asking again, `FraktError::Rejected` if the server sent a `FragmentError`
+    fn assignment(&mut self) -> Result<Assignment, FraktError> {
+        let (fragment, data) = self.receive()?;
+        let tasks = match fragment {
+            Fragment::FragmentTask(task) => vec![task],
+            Fragment::FragmentBatch(batch) => batch.tasks,
+            Fragment::FragmentNoWork(no_work) => {
+                return Ok(Assignment::NoWork(no_work.retry_after))
+            }
+            Fragment::FragmentError(error) => return Err(FraktError::from(error)),
+            fragment => return Err(FraktError::unexpected("FragmentTask", &fragment)),
+        };
+
+        // every task gets its own id at the start of its data, as a single `FragmentTask` has
+        let tasks = tasks
+            .into_iter()
+            .map(|task| {
+                let start = task.id.offset as usize;
+                let id = data
+                    .get(start..start + task.id.count as usize)
+                    .ok_or_else(|| {
+                        FraktError::Validation(String::from("A task id is outside of the data"))
+                    })?;
+                let task = FragmentTask {
+                    id: U8Data::new(0, task.id.count),
+                    ..task
+                };
+                Ok((task, id.to_vec()))
+            })
+            .collect::<Result<Vec<_>, FraktError>>()?;
+        Ok(Assignment::Tasks(tasks))
+    }
+
+    /// to build the data of a result: the id of the task, then its pixels as negotiated
+    /// * `id` - the id of the task
+    /// * `pixels` - the pixels to send
+    /// * Return: the data, or an error if the pixels cannot be encoded
+    fn result_data(&self, id: &[u8], pixels: &[PixelIntensity]) -> Result<Vec<u8>, FraktError> {
+        let mut data = id.to_vec();
+        data.extend(encode_pixels(pixels, self.capabilities.payload_encoding())?);
+        Ok(data)
+    }
+
+    /// to compute the checksum of the data of a result, when the server checks it
+    /// * `data` - the data of the result
+    /// * Return: `Option<u32>` - the CRC32 of the data, None if checksums were not negotiated
+    fn checksum(&self, data: &[u8]) -> Option<u32> {
+        self.capabilities.checksum.then(|| checksum(data))
+    }
+}
@@ -0,0 +1,137 @@
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+use crate::mock_server::{mandelbrot_task, MockServer, ServerScript};
+#[cfg(test)]
+use crate::mock_worker::{Assignment, MockWorker, Pixels};
+#[cfg(test)]
+use shared_lib::complementary_types::pixelintensity::PixelIntensity;
+#[cfg(test)]
+use shared_lib::errors::frakt_error::FraktError;
+#[cfg(test)]
+use shared_lib::messages::handshake::{Capabilities, PayloadEncoding};
+#[cfg(test)]
+use shared_lib::messages::message::{ErrorCode, Fragment, FragmentWelcome};
+
+#[test]
+fn test_computed_results_are_verified() {
+    let tasks = vec![
+        mandelbrot_task(8, 8),
+        mandelbrot_task(6, 4),
+        mandelbrot_task(5, 5),
+    ];
+    let server = MockServer::start(ServerScript::new(tasks)).unwrap();
+
+    let mut worker = MockWorker::connect(server.address()).unwrap();
+    let capabilities = Capabilities {
+        payload_encodings: vec![PayloadEncoding::Deflate],
+        ..Capabilities::supported()
+    };
+    let negotiated = worker.handshake(capabilities).unwrap();
+    assert!(negotiated.batch && negotiated.checksum);
+    assert_eq!(worker.run(&Pixels::Computed, 2).unwrap().len(), 3);
+
+    let report = server.join().unwrap();
+    assert_eq!(report.hellos.len(), 1);
+    assert_eq!(report.sessions, 1);
+    assert_eq!(report.unanswered, 0);
+    assert!(report.errors.is_empty());
+    assert_eq!(report.results.len(), 3);
+    for result in &report.results {
+        assert_eq!(result.bands, 1);
+        assert!(result.verify(0.0).is_ok());
+    }
+}
+
+#[test]
+fn test_canned_pixels_fail_verification() {
+    let server = MockServer::start(ServerScript::new(vec![mandelbrot_task(4, 4)])).unwrap();
+
+    let mut worker = MockWorker::connect(server.address()).unwrap();
+    worker.handshake(Capabilities::supported()).unwrap();
+    let canned = Pixels::Canned(PixelIntensity::new(0.5, 0.5));
+    assert_eq!(worker.run(&canned, 1).unwrap().len(), 1);
+
+    let report = server.join().unwrap();
+    assert_eq!(report.results.len(), 1);
+    assert!(report.results[0].verify(0.01).is_err());
+}
+
+#[test]
+fn test_legacy_worker_is_served() {
+    let server = MockServer::start(ServerScript::new(vec![mandelbrot_task(4, 3)])).unwrap();
+
+    let mut worker = MockWorker::connect(server.address()).unwrap();
+    assert_eq!(worker.run(&Pixels::Computed, 4).unwrap().len(), 1);
+
+    let report = server.join().unwrap();
+    assert!(report.hellos.is_empty());
+    assert_eq!(report.results.len(), 1);
+    assert!(report.results[0].verify(0.0).is_ok());
+}
+
+#[test]
+fn test_bands_are_reassembled() {
+    let server = MockServer::start(ServerScript::new(vec![mandelbrot_task(8, 7)])).unwrap();
+
+    let mut worker = MockWorker::connect(server.address()).unwrap();
+    worker.handshake(Capabilities::supported()).unwrap();
+    let (task, id) = match worker.request(1).unwrap() {
+        Assignment::Tasks(tasks) => tasks[0].clone(),
+        Assignment::NoWork(_) => panic!("a task was expected"),
+    };
+    worker.send_progress(&id, 50).unwrap();
+    let pixels = Pixels::Computed.for_task(&task);
+    let answer = worker.send_result_in_bands(&task, &id, &pixels, 3).unwrap();
+    assert!(matches!(answer, Assignment::NoWork(_)));
+
+    let report = server.join().unwrap();
+    assert_eq!(report.progress, 1);
+    assert_eq!(report.results[0].bands, 3);
+    assert!(report.results[0].verify(0.0).is_ok());
+}
+
+#[test]
+fn test_protocol_errors_are_reported() {
+    // the task is never answered: the server stops at its timeout
+    let script = ServerScript {
+        timeout: Duration::from_secs(1),
+        ..ServerScript::new(vec![mandelbrot_task(2, 2)])
+    };
+    let server = MockServer::start(script).unwrap();
+
+    let mut worker = MockWorker::connect(server.address()).unwrap();
+    worker.handshake(Capabilities::supported()).unwrap();
+    let (task, _) = match worker.request(1).unwrap() {
+        Assignment::Tasks(tasks) => tasks[0].clone(),
+        Assignment::NoWork(_) => panic!("a task was expected"),
+    };
+
+    // a result for a task never handed is refused, the session stays open
+    let pixels = Pixels::Computed.for_task(&task);
+    let err = worker.send_result(&task, &[0xFF; 16], &pixels).err();
+    assert!(matches!(
+        err,
+        Some(FraktError::Rejected {
+            code: ErrorCode::UnknownTask,
+            ..
+        })
+    ));
+
+    // a message a server sends closes the session
+    let welcome = FragmentWelcome::accept(1, Capabilities::supported());
+    worker
+        .send(Fragment::FragmentWelcome(welcome), &[])
+        .unwrap();
+    assert!(matches!(
+        worker.receive(),
+        Ok((Fragment::FragmentError(_), _))
+    ));
+
+    let report = server.join().unwrap();
+    assert_eq!(report.unanswered, 1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.errors[0].code, ErrorCode::UnknownTask);
+    assert_eq!(report.errors[1].code, ErrorCode::UnexpectedFragment);
+}
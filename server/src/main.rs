@@ -10,15 +10,31 @@
 //!
 //! - `--help`: Displays usage information.
 //! - `--fractal=<fractal_name>`: Specifies the type of fractal to calculate (default is Julia).
+//! - `--job=<job.toml|job.json>`: Renders the image described by this job file (fractal and its parameters, viewport, resolution, iterations, palette, priority and output path) instead of `--fractal`; an invalid job stops the server before any task is dispatched. Repeat the flag to render several jobs together.
 //! - `--host=<host>`: Specifies the host to bind the server to (default is localhost).
 //! - `--port=<port>`: Specifies the port to bind the server to (default is 8787).
+//! - `--idle-timeout=<seconds>`: Closes a client session after this many seconds without message (default is 60).
+//! - `--tls-cert=<cert.pem> --tls-key=<key.pem>`: Accepts TLS connections only, with this certificate chain and private key.
+//! - `--client-ca=<ca.pem>`: With TLS, requires workers to present a certificate signed by this CA.
+//! - `--secret-file=<path>`: Requires workers to authenticate with the key stored in this file; unauthenticated requests and results are rejected and logged.
+//! - `--max-frame-size=<bytes>`: Largest message accepted from a worker (default is 16 MiB), announced during the handshake.
+//! - `--frame-timeout=<seconds>`: Time allowed to receive a whole message once its first bytes arrived (default is 30).
+//! - `--lease=<seconds>`: Time a worker sending its progress may stay silent while computing a task before the task is given to another worker (default is 30).
+//! - `--silent-lease=<seconds>`: Same for the workers not sending their progress, only renewed by the bands of a streamed result (default is 300).
+//! - `--unix-socket=<path>`: Also accepts the workers of the same machine on this Unix domain socket, they connect with the address `unix:<path>`.
+//! - `--ws-port=<port>`: Also accepts workers speaking WebSocket on this port, such as browsers, with the same messages carried in binary WebSocket messages.
 //!
 //! Example:
 //!
 //! ```sh
 //! ./server --fractal=Mandelbrot
+//! ./server --job=jobs/julia.toml
+//! ./server --job=jobs/julia.toml --job=jobs/mandelbrot.json
 //! ./server --host=127.0.0.1 --port=8787
 //! ./server 127.0.0.1
+//! ./server --tls-cert=certs/server.pem --tls-key=certs/server.key
+//! ./server --ws-port=8789
+//! ./server --unix-socket=/tmp/frakt.sock
 //! ```
 //!
 //! ## Fractal Types
@@ -37,36 +53,59 @@
 //!
 //! The server spawns a dedicated thread to handle fractal calculations and client interactions. It listens for incoming client connections and delegates tasks to client threads. Once all tasks are completed, the server generates a full image of the fractal.
 //!
+//! The server thread only hands the tasks and follows them, it never waits: the client threads check and decode the pixels of the results, the painter thread paints them in the images of the jobs, and the saver thread writes the images (see `server_services::painter`). When the queue is empty, the workers are told there is no work until a random fractal is added 5sec later.
+//!
+//! The server thread holds a queue of jobs, each with its own tasks, image and priority. The tasks are handed from the job which received the fewest workers for its priority, so a job of priority 3 is computed about three times faster than a job of priority 1. Each job saves its image and leaves the queue as soon as all its pixels are painted; once the queue is empty, a random fractal is added.
+//!
 //! ## Client Thread
 //!
 //! Each client connection is processed in a separate thread. Clients can request tasks from the server, perform the calculations, and send back results. The client thread communicates with the server thread using message passing.
 //!
+//! Workers accepting it may send a large result in bands of rows (`FragmentResultChunk`): each band is painted into the image as it arrives, and only the last one is answered.
+//!
+//! A connection is a session: workers can keep it open and exchange many tasks and results over it. Legacy workers opening one connection per result are still supported, their session ends when they drop the connection.
+//!
+//! A session starts with a handshake: the worker sends a `FragmentHello` with its protocol version and capabilities, and the server answers with a `FragmentWelcome` accepting it with the negotiated capabilities or refusing it with the reason. Workers without handshake are served with the legacy capabilities, and only receive tasks for fractals they support.
+//!
 
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{BTreeMap, HashMap, HashSet},
     net::TcpListener,
     process::exit,
-    sync::mpsc::{self, Sender},
-    thread,
-    time::Duration,
+    sync::{mpsc, Arc},
 };
 
 mod server_services;
-use shared_lib::{
-    messages::message::Fragment, messages_methods::messages_methods::send_message_to_client,
-};
+use shared_lib::messages::message::{ErrorCode, Fragment};
 
+use crate::server_services::painter::{spawn_painter, PainterMessage};
 use crate::server_services::server::{
-    create_params_for_iterated_sin_z, create_params_for_julia, create_params_for_mandelbrot,
-    create_params_for_newton_raphson_z_3, create_params_for_newton_raphson_z_4,
-    create_params_for_nova_newton_raphson_z_3, create_params_for_nova_newton_raphson_z_4,
-    format_data_to_pixel_intensity_vector, generate_unique_id, parse_args, put_color_in_image,
-    read_message_from_client, reset_state, FractalCalculState,
+    accept_result, add_due_job, add_job, assign_tasks, default_job, finish_job, no_work,
+    parse_args, parse_job_args, parse_lease_args, parse_limits_args, parse_secret_args,
+    parse_tls_args, parse_websocket_args, reclaim_expired_tasks, record_progress, refill_queue,
+    reject, requeue_task, FractalCalculState,
+};
+use crate::server_services::session::{
+    accept_clients, Payload, ServerEvent, ServerMessage, SessionSettings,
+};
+#[cfg(unix)]
+use crate::server_services::{
+    server::parse_unix_socket_args,
+    session::{accept_unix_clients, bind_unix_socket},
 };
 
 fn main() {
-    let (host, port, mut fractal_to_calcul) = parse_args();
+    let (host, port, fractal_to_calcul, idle_timeout) = parse_args();
+    //les fichiers de job sont valides avant d ouvrir le serveur, une erreur arrete le serveur
+    let job_arguments = parse_job_args();
+    let lease = parse_lease_args();
+    let settings = SessionSettings {
+        tls: parse_tls_args(),
+        secret: parse_secret_args().map(Arc::new),
+        idle_timeout,
+        limits: parse_limits_args(),
+        websocket: false,
+    };
     let adress = format!("{}:{}", host, port);
     let listener = match TcpListener::bind(&adress) {
         Ok(listener) => listener,
@@ -76,62 +115,133 @@ fn main() {
         }
     };
 
-    println!("Server listening on {}", adress);
+    match settings.tls {
+        Some(_) => println!("Server listening on {} (TLS)", adress),
+        None => println!("Server listening on {}", adress),
+    }
+    // les workers WebSocket (navigateurs...) se connectent sur un second port
+    let websocket_listener = parse_websocket_args().map(|websocket_port| {
+        let websocket_adress = format!("{}:{}", host, websocket_port);
+        match TcpListener::bind(&websocket_adress) {
+            Ok(listener) => {
+                println!("Server listening on {} (WebSocket)", websocket_adress);
+                listener
+            }
+            Err(err) => {
+                eprintln!("Error binding to address: {}", err);
+                exit(1);
+            }
+        }
+    });
+    // les workers de la même machine peuvent passer par une socket Unix plutôt que par TCP
+    #[cfg(unix)]
+    let unix_listener = parse_unix_socket_args().map(|path| match bind_unix_socket(&path) {
+        Ok(listener) => {
+            println!("Server listening on unix:{}", path);
+            listener
+        }
+        Err(err) => {
+            eprintln!("Error binding to Unix socket: {}", err);
+            exit(1);
+        }
+    });
+    if settings.secret.is_some() {
+        println!("Workers must authenticate with the shared key");
+    }
 
-    let (tx, rx) = mpsc::channel();
+    let (tx, rx) = mpsc::channel::<ServerEvent>();
+    //les images des jobs sont peintes et sauvegardees par d autres threads, le server thread ne fait que distribuer les taches
+    let painter = spawn_painter(tx.clone());
 
     println!("create server thread");
+    let events = tx.clone();
     std::thread::spawn(move || {
         println!("Server Thread: I am created");
 
-        let image_width = 1200 as u32;
-        let image_height = 1200 as u32;
-
-        let mut image_buffer = image::ImageBuffer::new(image_width, image_height);
-
-        let params = match fractal_to_calcul.as_str() {
-            "Julia" => create_params_for_julia(),
-            "Mandelbrot" => create_params_for_mandelbrot(),
-            "IteratedSinZ" => create_params_for_iterated_sin_z(),
-            "NewtonRaphsonZ3" => create_params_for_newton_raphson_z_3(),
-            "NewtonRaphsonZ4" => create_params_for_newton_raphson_z_4(),
-            "NovaNewtonRaphsonZ3" => create_params_for_nova_newton_raphson_z_3(),
-            "NovaNewtonRaphsonZ4" => create_params_for_nova_newton_raphson_z_4(),
-            _ => create_params_for_julia(),
-        };
-
         let tasks_state = HashMap::new();
         let calcul_state = HashMap::new();
         let mut fractal_calcul_state = FractalCalculState {
-            params,
+            jobs: BTreeMap::new(),
+            next_job: 1,
+            task_jobs: HashMap::new(),
             tasks_state,
             calcul_state,
+            last_seen: HashMap::new(),
+            silent: HashSet::new(),
+            sessions: HashMap::new(),
+            owners: HashMap::new(),
+            received: HashMap::new(),
+            painter,
+            idle_since: None,
         };
 
-        for received in rx {
-            let (tx, fragment, datas): (Sender<(Fragment, Vec<u8>)>, Fragment, Vec<u8>) = received;
+        //chaque job a ses taches (une par tuile de son image) et son image, sans job le serveur calcule la fractale de --fractal
+        if job_arguments.is_empty() {
+            add_job(&mut fractal_calcul_state, default_job(&fractal_to_calcul));
+        }
+        for job in job_arguments {
+            add_job(&mut fractal_calcul_state, job);
+        }
+
+        for event in rx {
+            let (tx, fragment, payload, capabilities, session): ServerMessage = match event {
+                ServerEvent::Client(received) => *received,
+                ServerEvent::JobPainted(job_number) => {
+                    //Si tous les pixels de l'image du job ont ete peints, retirer le job de la file, le saver thread sauvegarde l image
+                    if finish_job(
+                        &mut fractal_calcul_state,
+                        job_number,
+                        "The image of this job is complete",
+                    )
+                    .is_some()
+                    {
+                        println!(
+                            "Server Thread: Job {} complete, {} job(s) left",
+                            job_number,
+                            fractal_calcul_state.jobs.len()
+                        );
+                    }
+                    refill_queue(&mut fractal_calcul_state, &events);
+                    continue;
+                }
+                ServerEvent::NewJobDue(idle_since) => {
+                    //la file est vide depuis NEW_JOB_DELAY: un job aleatoire y est ajoute, sans reverifier le temps ecoule
+                    add_due_job(&mut fractal_calcul_state, idle_since);
+                    continue;
+                }
+            };
             println!("Server Thread: Received fragment and datas from client thread");
 
+            //les taches dont le worker ne donne plus de nouvelles sont redistribuees
+            let reclaimed = reclaim_expired_tasks(&mut fractal_calcul_state, lease);
+            if reclaimed > 0 {
+                println!(
+                    "Server Thread: {} task(s) given back after their lease expired",
+                    reclaimed
+                );
+            }
+            //si plus aucun job, le serveur en genere un automatiquement au bout de 5sec, sans bloquer les autres workers
+            refill_queue(&mut fractal_calcul_state, &events);
+
             match fragment {
-                Fragment::FragmentRequest(_) => {
-                    //recuperer une tache et l envoyer avec le tx.send(task)
-                    //si pas de tache, le serveur en genere automatiquement au bout de 5sec
-                    if fractal_calcul_state.params.len() == 0 {
-                        println!("Server Thread: No more task, waiting 5sec before generating a new fractal");
-                        thread::sleep(Duration::from_secs(5));
-                        fractal_to_calcul = reset_state(&mut fractal_calcul_state);
-                    }
-                    let id = generate_unique_id();
-                    let task = match fractal_calcul_state.params.pop() {
-                        Some(task) => task,
+                Fragment::FragmentRequest(request) => {
+                    //recuperer autant de taches que le client peut en calculer et les envoyer avec le tx.send
+                    //les taches sont enregistrees dans le state avec leur id
+                    let answer = match assign_tasks(
+                        &mut fractal_calcul_state,
+                        &capabilities,
+                        request.maximal_work_load,
+                        &tx,
+                        session,
+                    ) {
+                        Some(answer) => answer,
                         None => {
-                            println!("Server Thread: No more task");
-                            return;
+                            println!("Server Thread: No task this client can compute");
+                            let _ = tx.send(no_work());
+                            continue;
                         }
                     };
-                    //enregistrer la tache dans le state avec son id
-                    fractal_calcul_state.tasks_state.insert(id.clone(), task);
-                    match tx.send((Fragment::FragmentTask(task), id.clone())) {
+                    match tx.send(answer) {
                         Ok(_) => println!("Server Thread: send fragment task to client thread"),
                         Err(_) => {
                             println!("Server Thread: Error sending fragment task to client thread")
@@ -139,132 +249,150 @@ fn main() {
                     };
                 }
 
-                Fragment::FragmentResult(result) => {
-                    //recuperer le resultat et creer l image en cherchant la tache grace a l'id
-                    let id_size = result.id.count as usize;
-                    let (id, data_to_be_transformed) = datas.split_at(id_size);
-                    let _id = id.to_vec();
-                    let data_to_be_transformed = data_to_be_transformed.to_vec();
-
-                    let pixel_intensities =
-                        format_data_to_pixel_intensity_vector(&data_to_be_transformed);
-                    fractal_calcul_state
-                        .calcul_state
-                        .insert(_id.clone(), pixel_intensities.clone());
-                    println!("Server Thread: processed result on server thread");
-                    let task_calculated = match fractal_calcul_state.tasks_state.get(&_id) {
-                        Some(task) => task,
-                        None => {
-                            println!("Server Thread: No task found");
-                            return;
+                Fragment::FragmentResultChunk(result) => {
+                    //recuperer le resultat, deja decode par le client thread, en cherchant la tache grace a l'id
+                    //seule la derniere bande d un resultat recoit une reponse
+                    let (id, pixels) = match payload {
+                        Payload::Pixels(id, pixels) => (id, pixels),
+                        Payload::Raw(_) => {
+                            println!("Server Thread: Result received without its pixels");
+                            if result.last {
+                                let _ = tx.send(reject(
+                                    ErrorCode::Internal,
+                                    String::from("The pixels of the result were not decoded"),
+                                ));
+                            }
+                            continue;
                         }
                     };
 
-                    //on construit l image globale au fur et a mesure que les resultats sont recupérés
-                    put_color_in_image(&task_calculated, &pixel_intensities, &mut image_buffer);
-
-                    //Si l'image est complete, la sauvegarder et vider le state
-                    if fractal_calcul_state.calcul_state.len() == 16 {
-                        let file_path = format!("images/server/full{fractal_to_calcul}.png");
-                        println!("Server Thread: create Full Image, path: {}", file_path);
-
-                        // Créez le répertoire s'il n'existe pas
-                        if let Some(parent_dir) = std::path::Path::new(&file_path).parent() {
-                            if !parent_dir.exists() {
-                                if let Err(err) = fs::create_dir_all(parent_dir) {
-                                    eprintln!("Error creating directory: {}", err);
+                    //un resultat pour une tache inconnue (bail expire), deja recue ou confiee a un autre worker est rejete sans toucher a l image ni a la tache
+                    //des donnees corrompues ou illisibles: la tache est remise dans la liste pour etre recalculee et le worker recoit un refus
+                    //(a la derniere bande, les suivantes seront refusees comme tache inconnue), sa session reste ouverte
+                    let (task_calculated, job_number, pixel_intensities) =
+                        match accept_result(&mut fractal_calcul_state, &id, session, pixels) {
+                            Ok(accepted) => accepted,
+                            Err((code, reason)) => {
+                                println!("Server Thread: Result rejected: {}", reason);
+                                if result.last {
+                                    let _ = tx.send(reject(code, reason));
                                 }
-                            }
-                        }
-
-                        match image_buffer.save(&file_path) {
-                            Ok(_) => {
-                                println!("Server Thread: Image saved");
-                            }
-                            Err(err) => {
-                                eprintln!("Error saving image: {}", err);
+                                continue;
                             }
                         };
-                        //on reset le state
-                        fractal_calcul_state.calcul_state.clear();
-                        fractal_calcul_state.tasks_state.clear();
+                    //les bandes doivent arriver dans l ordre et couvrir toute la tache
+                    let received = fractal_calcul_state.received.get(&id).copied().unwrap_or(0);
+                    let total =
+                        task_calculated.resolution.nx as u32 * task_calculated.resolution.ny as u32;
+                    let band_end = received + pixel_intensities.len() as u32;
+                    if result.first_pixel != received
+                        || band_end > total
+                        || (result.last && band_end != total)
+                    {
+                        println!(
+                            "Server Thread: Band of pixels {}..{} does not follow the {} pixels received",
+                            result.first_pixel, band_end, received
+                        );
+                        requeue_task(&mut fractal_calcul_state, &id);
+                        if result.last {
+                            let _ = tx.send(reject(
+                                ErrorCode::InvalidResult,
+                                String::from(
+                                    "The bands of the result do not cover the task in order",
+                                ),
+                            ));
+                        }
+                        continue;
                     }
 
-                    //recuperer une tache et l envoyer avec le tx.send(task)
-                    //si pas de tache, le serveur en genere automatiquement au bout de 5sec
-                    if fractal_calcul_state.params.len() == 0 {
-                        println!("Server Thread: No more task, waiting 5sec before generating a new fractal");
-                        thread::sleep(Duration::from_secs(5));
-                        fractal_to_calcul = reset_state(&mut fractal_calcul_state);
+                    //le painter thread construit l image du job au fur et a mesure que les bandes sont recuperees, a la place de la tuile dans l image
+                    let paint = PainterMessage::Paint {
+                        job: job_number,
+                        task: task_calculated,
+                        first_pixel: result.first_pixel,
+                        pixels: pixel_intensities,
+                    };
+                    if fractal_calcul_state.painter.send(paint).is_err() {
+                        println!("Server Thread: Error sending the pixels to the painter thread");
                     }
-                    let task = match fractal_calcul_state.params.pop() {
-                        Some(task) => task,
+                    if !result.last {
+                        //une bande recue montre aussi que le worker est toujours en vie
+                        fractal_calcul_state.received.insert(id.clone(), band_end);
+                        record_progress(&mut fractal_calcul_state, &id);
+                        continue;
+                    }
+
+                    //les pixels sont envoyes au painter thread, il suffit de retenir que la tache est terminee
+                    fractal_calcul_state.received.remove(&id);
+                    fractal_calcul_state
+                        .calcul_state
+                        .insert(id.clone(), Vec::new());
+                    fractal_calcul_state.last_seen.remove(&id);
+                    fractal_calcul_state.silent.remove(&id);
+                    fractal_calcul_state.sessions.remove(&id);
+                    fractal_calcul_state.owners.remove(&id);
+                    println!("Server Thread: processed result on server thread");
+
+                    //une tache remplace celle dont le resultat vient d etre recu
+                    let answer = match assign_tasks(
+                        &mut fractal_calcul_state,
+                        &capabilities,
+                        1,
+                        &tx,
+                        session,
+                    ) {
+                        Some(answer) => answer,
                         None => {
-                            println!("Server Thread: No more task");
-                            return;
+                            println!("Server Thread: No task this client can compute");
+                            let _ = tx.send(no_work());
+                            continue;
                         }
                     };
-                    let new_id = generate_unique_id();
-                    let _ = tx.send((Fragment::FragmentTask(task), new_id.clone()));
+                    let _ = tx.send(answer);
                     println!("Server Thread: send fragment task to client thread");
+                }
 
-                    //enregistrer la tache dans le state avec son id
-
-                    fractal_calcul_state
-                        .tasks_state
-                        .insert(new_id.clone(), task);
+                Fragment::FragmentProgress(progress) => {
+                    //le worker est toujours en vie: on met a jour la date de derniere nouvelle de la tache
+                    let id_start = progress.id.offset as usize;
+                    let id_end = id_start + progress.id.count as usize;
+                    let id = match &payload {
+                        Payload::Raw(datas) => datas.get(id_start..id_end).unwrap_or_default(),
+                        Payload::Pixels(..) => &[],
+                    };
+                    match record_progress(&mut fractal_calcul_state, id) {
+                        Some(since) => println!(
+                            "Server Thread: task {}% done, previous news {:?} ago",
+                            progress.percentage, since
+                        ),
+                        None => println!("Server Thread: Progress received for an unknown task"),
+                    }
                 }
                 _ => {
                     println!("Unknown request received");
+                    let _ = tx.send(reject(
+                        ErrorCode::UnexpectedFragment,
+                        String::from("Only FragmentRequest, FragmentResult and FragmentResultChunk are handled"),
+                    ));
                 }
             }
         }
     });
 
     // accepter les connexions des clients
-    for stream in listener.incoming() {
-        match stream {
-            Ok(mut stream) => {
-                println!("New client connected");
-                // traiter chaque client dans un thread séparé
-                let tx = tx.clone();
-                std::thread::spawn(move || {
-                    let (fragment, data) = match read_message_from_client(&mut stream) {
-                        Ok((fragment, data)) => (fragment, data),
-                        Err(e) => {
-                            println!("Error reading message from client: {}", e);
-                            return;
-                        }
-                    };
-                    let (tx_from_client, rx) = mpsc::channel::<(Fragment, Vec<u8>)>();
-                    match tx.send((tx_from_client, fragment, data)) {
-                        Ok(_) => println!("Client Thread: send fragment and data to server thread"),
-                        Err(_) => println!(
-                            "Client Thread: Error sending fragment and data to server thread"
-                        ),
-                    };
-
-                    match rx.recv() {
-                        Ok(received) => {
-                            let (task, id) = received;
-                            println!("Client Thread: received fragment and id to server thread");
-                            match send_message_to_client(&mut stream, task, id) {
-                                Ok(_) => println!("Client Thread: send task to client for calcul"),
-                                Err(_) => println!("Client Thread: Error sending task to client"),
-                            };
-                        }
-                        Err(_) => {
-                            println!("Client Thread: No more task");
-                            return;
-                        }
-                    };
-
-                    println!("New client disconnected");
-                });
-            }
-            Err(e) => {
-                println!("Error accepting connection: {}", e);
-            }
-        }
+    if let Some(websocket_listener) = websocket_listener {
+        let tx = tx.clone();
+        let settings = SessionSettings {
+            websocket: true,
+            ..settings.clone()
+        };
+        std::thread::spawn(move || accept_clients(websocket_listener, tx, settings));
+    }
+    #[cfg(unix)]
+    if let Some(unix_listener) = unix_listener {
+        let tx = tx.clone();
+        let settings = settings.clone();
+        std::thread::spawn(move || accept_unix_clients(unix_listener, tx, settings));
     }
+    accept_clients(listener, tx, settings);
 }
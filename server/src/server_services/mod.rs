@@ -1 +1,6 @@
+pub mod painter;
 pub mod server;
+pub mod session;
+mod test_painter;
+mod test_server;
+mod test_session;
@@ -0,0 +1,151 @@
+//! # Painter
+//!
+//! The `painter` module paints and saves the images of the jobs away from the server thread, so
+//! the dispatch of the tasks never waits for the image of a job.
+//!
+//! The painter thread owns the image of every job of the queue. The server thread checks each band
+//! of pixels received and hands it to the painter thread, which paints it at the place of its tile.
+//! Once every pixel of an image is painted, the painter thread gives the image to the saver thread
+//! and tells the server thread the job is complete.
+//!
+//! The saver thread encodes and writes the images, one after the other: a large image or a slow
+//! disk delays the next images, never the tasks.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use image::{ImageBuffer, Rgb};
+use shared_lib::{
+    complementary_types::pixelintensity::PixelIntensity,
+    fractal_implementation::assembler::ImageAssembler, messages::message::FragmentTask,
+};
+
+use super::session::ServerEvent;
+
+/// Message sent by the server thread to the painter thread:
+/// * Start: a job was added to the queue, with its image to paint and the path of the image file
+/// * Paint: a band of pixels of a task of a job, checked by the server thread, `first_pixel` being the index in the task of its first pixel
+#[derive(Debug)]
+pub enum PainterMessage {
+    Start {
+        job: u32,
+        assembler: ImageAssembler,
+        output: String,
+    },
+    Paint {
+        job: u32,
+        task: FragmentTask,
+        first_pixel: u32,
+        pixels: Vec<PixelIntensity>,
+    },
+}
+
+/// Image complete sent to the saver thread:
+/// * the number of the job
+/// * the path of the image file
+/// * the image
+pub type SaverMessage = (u32, String, ImageBuffer<Rgb<u8>, Vec<u8>>);
+
+/// to start the painter thread and its saver thread
+/// * `server` - the channel to the server thread, told when the image of a job is complete
+/// * Return: `Sender<PainterMessage>` - the channel to the painter thread
+pub fn spawn_painter(server: Sender<ServerEvent>) -> Sender<PainterMessage> {
+    let (tx, rx) = mpsc::channel::<PainterMessage>();
+    let saver = spawn_saver();
+
+    println!("create painter thread");
+    thread::spawn(move || {
+        println!("Painter Thread: I am created");
+        let mut images: HashMap<u32, (ImageAssembler, String)> = HashMap::new();
+
+        for message in rx {
+            match message {
+                PainterMessage::Start {
+                    job,
+                    assembler,
+                    output,
+                } => {
+                    images.insert(job, (assembler, output));
+                }
+                PainterMessage::Paint {
+                    job,
+                    task,
+                    first_pixel,
+                    pixels,
+                } => {
+                    let (assembler, _) = match images.get_mut(&job) {
+                        Some(image) => image,
+                        None => {
+                            println!(
+                                "Painter Thread: Pixels received for job {} not in the queue",
+                                job
+                            );
+                            continue;
+                        }
+                    };
+                    //on construit l image du job au fur et a mesure que les bandes sont recuperees, a la place de la tuile dans l image
+                    if let Err(err) = assembler.paint(&task, first_pixel, &pixels) {
+                        println!("Painter Thread: Result out of the image: {}", err);
+                        continue;
+                    }
+                    if !assembler.is_complete() {
+                        continue;
+                    }
+
+                    //Si tous les pixels de l'image ont ete peints, la sauvegarder et prevenir le server thread
+                    if let Some((assembler, output)) = images.remove(&job) {
+                        let _ = saver.send((job, output, assembler.into_image()));
+                    }
+                    if server.send(ServerEvent::JobPainted(job)).is_err() {
+                        println!(
+                            "Painter Thread: Error sending the end of job {} to server thread",
+                            job
+                        );
+                    }
+                }
+            }
+        }
+    });
+    tx
+}
+
+/// to start the saver thread, writing the images in the order they are complete
+/// * Return: `Sender<SaverMessage>` - the channel to the saver thread
+pub fn spawn_saver() -> Sender<SaverMessage> {
+    let (tx, rx) = mpsc::channel::<SaverMessage>();
+
+    println!("create saver thread");
+    thread::spawn(move || {
+        println!("Saver Thread: I am created");
+        for (job, file_path, image) in rx {
+            println!(
+                "Saver Thread: create Full Image of job {}, path: {}",
+                job, file_path
+            );
+
+            // Créez le répertoire s'il n'existe pas
+            if let Some(parent_dir) = Path::new(&file_path).parent() {
+                if !parent_dir.exists() {
+                    if let Err(err) = fs::create_dir_all(parent_dir) {
+                        eprintln!("Error creating directory: {}", err);
+                    }
+                }
+            }
+
+            match image.save(&file_path) {
+                Ok(_) => {
+                    println!("Saver Thread: Image saved");
+                }
+                Err(err) => {
+                    eprintln!("Error saving image: {}", err);
+                }
+            };
+        }
+    });
+    tx
+}
@@ -8,23 +8,26 @@
 //!
 //! ## Examples
 //!
+//! The server is a binary crate, so the examples below are not run as doctests.
+//!
 //! Generating a unique ID:
 //!
-//! ```rust
-//! use your_module_name::generate_unique_id;
+//! ```ignore
+//! use crate::server_services::server::generate_unique_id;
 //!
 //! let unique_id = generate_unique_id();
 //! ```
 //!
-//! Reading a message from a client:http://88.166.25.87/
+//! Reading a message from a client:
 //!
-//! ```rust
-//! use std::io;
+//! ```ignore
 //! use std::net::TcpStream;
-//! use your_module_name::read_message_from_client;
+//! use shared_lib::messages::handshake::HeaderEncoding;
+//! use shared_lib::messages_methods::frame::FrameLimits;
+//! use crate::server_services::server::read_message_from_client;
 //!
 //! let mut stream = TcpStream::connect("127.0.0.1:8080").expect("Connection failed");
-//! let (fragment, datas) = read_message_from_client(&mut stream).expect("Failed to read message from client");
+//! let (fragment, datas) = read_message_from_client(&mut stream, HeaderEncoding::Json, &FrameLimits::default()).expect("Failed to read message from client");
 //!
 //! println!("Fragment: {:?}", fragment);
 //! println!("Datas: {:?}", datas);
@@ -32,22 +35,27 @@
 //!
 //! Formatting data to a vector of `PixelIntensity`:
 //!
-//! ```rust
-//! use your_module_name::format_data_to_pixel_intensity_vector;
-//! use shared_lib::complementary_types::pixelintensity::PixelIntensity;
+//! ```ignore
+//! use shared_lib::messages::handshake::PayloadEncoding;
+//! use crate::server_services::server::format_data_to_pixel_intensity_vector;
 //!
-//! let datas = vec![/*... raw data ...*/];
-//! let pixel_intensities = format_data_to_pixel_intensity_vector(&datas);
+//! let datas = vec![0u8; 300 * 300 * 8];
+//! let pixel_intensities = format_data_to_pixel_intensity_vector(&datas, PayloadEncoding::F32BigEndian, 300 * 300)
+//!     .expect("Invalid pixels");
 //!
 //! for intensity in pixel_intensities {
 //!     println!("Pixel Intensity: {:?}", intensity);
 //! }
 //! ```
 
-use std::collections::HashMap;
-use std::net::TcpStream;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::io::Read;
 use std::process::exit;
-use std::{env, io};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use complex_math::Complex;
 use rand::seq::SliceRandom;
@@ -58,21 +66,70 @@ use shared_lib::complementary_types::point::Point;
 use shared_lib::complementary_types::range::Range;
 use shared_lib::complementary_types::resolution::Resolution;
 use shared_lib::complementary_types::u8data::U8Data;
+use shared_lib::errors::frakt_error::FraktError;
+use shared_lib::fractal_implementation::assembler::ImageAssembler;
 use shared_lib::fractal_implementation::fractal::FractalDescriptor;
-use shared_lib::fractal_implementation::fractal_calcul::color;
-use shared_lib::messages::message::{Fragment, FragmentTask};
-use shared_lib::messages_methods::messages_methods::read_message;
+use shared_lib::fractal_implementation::palette::Palette;
+use shared_lib::fractal_implementation::tiling::TilePlan;
+use shared_lib::fractal_types::iterated_sin_z::IteratedSinZ;
+use shared_lib::fractal_types::julia_descriptor::JuliaDescriptor;
+use shared_lib::fractal_types::mandelbrot::Mandelbrot;
+use shared_lib::fractal_types::newton_raphson_z_3::NewtonRaphsonZ3;
+use shared_lib::fractal_types::newton_raphson_z_4::NewtonRaphsonZ4;
+use shared_lib::fractal_types::nova_newton_raphson_z_3::NovaNewtonRaphsonZ3;
+use shared_lib::fractal_types::nova_newton_raphson_z_4::NovaNewtonRaphsonZ4;
+use shared_lib::job::job_file::{Job, DEFAULT_TILE_SIZE};
+use shared_lib::messages::authentication::load_secret;
+use shared_lib::messages::handshake::{Capabilities, HeaderEncoding, PayloadEncoding};
+use shared_lib::messages::message::{
+    ErrorCode, Fragment, FragmentBatch, FragmentCancel, FragmentError, FragmentNoWork, FragmentTask,
+};
+use shared_lib::messages_methods::frame::{FrameLimits, ReadTimeout};
+use shared_lib::messages_methods::messages_methods::read_message_with_deadline;
+use shared_lib::messages_methods::payload::decode_payload;
+use shared_lib::transport::tls::{server_config, ServerConfig};
+
+use super::painter::PainterMessage;
+use super::session::ServerEvent;
+
+/// A job of the queue:
+/// * job: the image to render, with its priority
+/// * params: FragmentTask of the job still waiting for a worker, one per tile of the image
+/// * dispatched: number of tasks of the job handed to workers, to share the workers between the jobs according to their priority
+#[derive(Debug, Clone)]
+pub struct RenderJob {
+    pub job: Job,
+    pub params: Vec<FragmentTask>,
+    pub dispatched: u64,
+}
 
 /// Structure to store:
-/// * params: FragmentTask needed to be computed for the full fractal
+/// * jobs: BTreeMap of the jobs being rendered with their number, in the order they were added
+/// * next_job: number of the next job added
+/// * task_jobs: HashMap of the number of the job of each FragmentTask sent to a client, with the unique id of the FragmentTask
 /// * tasks_state: HashMap of FragmentTask sent to client for computation with their unique id
-/// * calcul_state: HashMap of PixelIntensity (data computed) with the unique id of the FragmentTask corresponding
+/// * calcul_state: HashMap of PixelIntensity (data computed) with the unique id of the FragmentTask corresponding; the pixels of a result streamed in bands are painted as they arrive and not kept
+/// * last_seen: HashMap of the last time the worker computing a task sent news of it, with the unique id of the FragmentTask; the lease of the task expires when the worker stays silent for too long
+/// * silent: HashSet of the unique id of the FragmentTask computed by a worker not sending `FragmentProgress`, held to the longer silent lease
+/// * sessions: HashMap of the number of the session holding the lease of a task, with the unique id of the FragmentTask; only this session may send its result
+/// * owners: HashMap of the channel to the session of the worker computing a task, with the unique id of the FragmentTask, for the workers accepting a `FragmentCancel`
+/// * received: HashMap of the number of pixels already received for a task streamed in bands, with the unique id of the FragmentTask
+/// * painter: the channel to the painter thread, which paints and saves the image of each job
+/// * idle_since: the time the queue became empty, a random job is added `NEW_JOB_DELAY` later; None while the queue holds a job
 #[derive(Debug, Clone)]
 pub struct FractalCalculState {
-    //16 squares
-    pub params: Vec<FragmentTask>,
+    pub jobs: BTreeMap<u32, RenderJob>,
+    pub next_job: u32,
+    pub task_jobs: HashMap<Vec<u8>, u32>,
     pub tasks_state: HashMap<Vec<u8>, FragmentTask>,
     pub calcul_state: HashMap<Vec<u8>, Vec<PixelIntensity>>,
+    pub last_seen: HashMap<Vec<u8>, Instant>,
+    pub silent: HashSet<Vec<u8>>,
+    pub sessions: HashMap<Vec<u8>, u64>,
+    pub owners: HashMap<Vec<u8>, Sender<(Fragment, Vec<u8>)>>,
+    pub received: HashMap<Vec<u8>, u32>,
+    pub painter: Sender<PainterMessage>,
+    pub idle_since: Option<Instant>,
 }
 
 /// to generate a unique id as a vector of 16 bytes
@@ -86,11 +143,368 @@ pub fn generate_unique_id() -> Vec<u8> {
     Vec::from(result)
 }
 
+/// Seconds a worker is asked to wait when there is no task for it
+pub const NO_WORK_RETRY_AFTER: u32 = 5;
+
+/// to build the answer to a worker the server has no task for
+/// * Return: (`Fragment`, `Vec<u8>`) - a `FragmentNoWork` asking to retry after `NO_WORK_RETRY_AFTER` seconds, without data
+pub fn no_work() -> (Fragment, Vec<u8>) {
+    (
+        Fragment::FragmentNoWork(FragmentNoWork::new(NO_WORK_RETRY_AFTER)),
+        Vec::new(),
+    )
+}
+
+/// to build the answer rejecting a message of a worker
+/// * `code` - why the message is rejected
+/// * `message` - details for the worker logs
+/// * Return: (`Fragment`, `Vec<u8>`) - a `FragmentError`, without data
+pub fn reject(code: ErrorCode, message: String) -> (Fragment, Vec<u8>) {
+    (
+        Fragment::FragmentError(FragmentError::new(code, message)),
+        Vec::new(),
+    )
+}
+
+/// to record the progress sent by the worker computing a task
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `id` - the unique id of the task
+/// * Return: `Option<Duration>` - the time since the previous news of the task, None if the task is not waiting for a result
+pub fn record_progress(
+    fractal_calcul_state: &mut FractalCalculState,
+    id: &[u8],
+) -> Option<Duration> {
+    if !fractal_calcul_state.tasks_state.contains_key(id)
+        || fractal_calcul_state.calcul_state.contains_key(id)
+    {
+        return None;
+    }
+    let now = Instant::now();
+    let previous = fractal_calcul_state.last_seen.insert(id.to_vec(), now);
+    Some(previous.map_or(Duration::ZERO, |previous| now.duration_since(previous)))
+}
+
+/// Time a worker may stay silent while computing a task before the task is given to another worker:
+/// * progress: for the workers sending `FragmentProgress`, renewed by each of them
+/// * silent: for the workers not sending `FragmentProgress`, only renewed by the bands of a streamed result, long enough to compute a whole task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lease {
+    pub progress: Duration,
+    pub silent: Duration,
+}
+
+/// to give back the tasks whose lease expired: their worker sent no news for longer than the lease,
+/// so they are put back in the `params` of their job to be handed to another worker. Their ids are forgotten, a late
+/// result for one of them is then rejected as an unknown task.
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `lease` - the time a worker may stay silent while computing a task, depending on whether it sends `FragmentProgress`
+/// * Return: `usize` - the number of tasks given back
+pub fn reclaim_expired_tasks(fractal_calcul_state: &mut FractalCalculState, lease: Lease) -> usize {
+    let now = Instant::now();
+    let silent = &fractal_calcul_state.silent;
+    let expired: Vec<Vec<u8>> = fractal_calcul_state
+        .last_seen
+        .iter()
+        .filter(|(id, last_seen)| {
+            let lease = if silent.contains(*id) {
+                lease.silent
+            } else {
+                lease.progress
+            };
+            now.duration_since(**last_seen) > lease
+        })
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    for id in &expired {
+        requeue_task(fractal_calcul_state, id);
+    }
+    expired.len()
+}
+
+/// to give back a task whose result is unusable or whose lease expired, so another worker computes it again
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `id` - the unique id of the task
+/// * Return: `bool` - false if no task is waiting for this id
+pub fn requeue_task(fractal_calcul_state: &mut FractalCalculState, id: &[u8]) -> bool {
+    fractal_calcul_state.received.remove(id);
+    fractal_calcul_state.last_seen.remove(id);
+    fractal_calcul_state.silent.remove(id);
+    fractal_calcul_state.sessions.remove(id);
+    fractal_calcul_state.owners.remove(id);
+    let task = fractal_calcul_state.tasks_state.remove(id);
+    let job = fractal_calcul_state.task_jobs.remove(id);
+    match (task, job) {
+        (Some(task), Some(job)) => match fractal_calcul_state.jobs.get_mut(&job) {
+            Some(render_job) => {
+                render_job.params.push(task);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// to find the task a result was computed for, a result for a task already received, for a task
+/// unknown (its lease expired, or its job is finished), or for a task handed to another session, is
+/// rejected without touching the image nor the task
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `id` - the unique id of the task, sent with the result
+/// * `session` - the number of the session sending the result
+/// * Return: `Result<(FragmentTask, u32), (ErrorCode, String)>` - the task and the number of its job, or the error to send to the worker
+pub fn find_result_task(
+    fractal_calcul_state: &FractalCalculState,
+    id: &[u8],
+    session: u64,
+) -> Result<(FragmentTask, u32), (ErrorCode, String)> {
+    if fractal_calcul_state.calcul_state.contains_key(id) {
+        return Err((
+            ErrorCode::UnknownTask,
+            String::from("This result was already received"),
+        ));
+    }
+    match (
+        fractal_calcul_state.tasks_state.get(id),
+        fractal_calcul_state.task_jobs.get(id),
+    ) {
+        (Some(task), Some(job_number))
+            if fractal_calcul_state.sessions.get(id) == Some(&session) =>
+        {
+            Ok((*task, *job_number))
+        }
+        (Some(_), Some(_)) => Err((
+            ErrorCode::UnknownTask,
+            String::from("This task was handed to another worker"),
+        )),
+        _ => Err((
+            ErrorCode::UnknownTask,
+            String::from("No task is waiting for this result, its lease may have expired"),
+        )),
+    }
+}
+
+/// to check a result before its pixels are painted: the task must be in flight and handed to the
+/// session sending the result, and a result whose pixels are unusable gives back its task
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `id` - the unique id of the task, sent with the result
+/// * `session` - the number of the session sending the result
+/// * `pixels` - the pixels of the result, or the code and the reason why they are unusable
+/// * Return: `Result<(FragmentTask, u32, Vec<PixelIntensity>), (ErrorCode, String)>` - the task, the number of its job and the pixels, or the error to send to the worker
+pub fn accept_result(
+    fractal_calcul_state: &mut FractalCalculState,
+    id: &[u8],
+    session: u64,
+    pixels: Result<Vec<PixelIntensity>, (ErrorCode, String)>,
+) -> Result<(FragmentTask, u32, Vec<PixelIntensity>), (ErrorCode, String)> {
+    let (task, job_number) = find_result_task(fractal_calcul_state, id, session)?;
+    match pixels {
+        Ok(pixels) => Ok((task, job_number, pixels)),
+        Err(error) => {
+            requeue_task(fractal_calcul_state, id);
+            Err(error)
+        }
+    }
+}
+
+/// Maximal number of tasks handed in a single `FragmentBatch`, whatever the client asks for, so one
+/// worker cannot take the whole queue and the batch stays far below the frame limits
+pub const MAX_BATCH_TASKS: u32 = 16;
+
+/// to hand tasks to a client, each of them registered with a new unique id and a fresh lease, the silent lease if the client does not send `FragmentProgress`
+/// * `fractal_calcul_state` - the state of the fractal being computed
+/// * `capabilities` - the capabilities of the client
+/// * `maximal_work_load` - the number of tasks the client asked for, only honoured if it supports batches, up to `MAX_BATCH_TASKS`
+/// * `owner` - the channel to the session of the client, kept to cancel the tasks if it accepts a `FragmentCancel`
+/// * `session` - the number of the session of the client, the only one whose results are accepted for the tasks
+/// * Return: `Option<(Fragment, Vec<u8>)>` - a `FragmentTask`, or a `FragmentBatch` when several tasks are handed, with the ids in the data; None if there is no task for the client
+pub fn assign_tasks(
+    fractal_calcul_state: &mut FractalCalculState,
+    capabilities: &Capabilities,
+    maximal_work_load: u32,
+    owner: &Sender<(Fragment, Vec<u8>)>,
+    session: u64,
+) -> Option<(Fragment, Vec<u8>)> {
+    let count = if capabilities.batch {
+        maximal_work_load.clamp(1, MAX_BATCH_TASKS) as usize
+    } else {
+        1
+    };
+    let mut tasks = Vec::new();
+    let mut ids = Vec::new();
+    while tasks.len() < count {
+        let (job, task) = match take_task_for(fractal_calcul_state, capabilities) {
+            Some(task) => task,
+            None => break,
+        };
+        let id = generate_unique_id();
+        let task = FragmentTask {
+            id: U8Data::new(ids.len() as u32, id.len() as u32),
+            ..task
+        };
+        fractal_calcul_state.tasks_state.insert(id.clone(), task);
+        fractal_calcul_state.task_jobs.insert(id.clone(), job);
+        fractal_calcul_state
+            .last_seen
+            .insert(id.clone(), Instant::now());
+        if !capabilities.progress {
+            fractal_calcul_state.silent.insert(id.clone());
+        }
+        fractal_calcul_state.sessions.insert(id.clone(), session);
+        if capabilities.cancel {
+            fractal_calcul_state
+                .owners
+                .insert(id.clone(), owner.clone());
+        }
+        ids.extend(id);
+        tasks.push(task);
+    }
+
+    match tasks.len() {
+        0 => None,
+        1 => Some((Fragment::FragmentTask(tasks[0]), ids)),
+        _ => Some((Fragment::FragmentBatch(FragmentBatch::new(tasks)), ids)),
+    }
+}
+
+/// to add a job at the end of the queue, its image cut into tasks
+/// * `fractal_calcul_state` - the state holding the queue
+/// * `job` - the job to render, already validated
+/// * Return: `u32` - the number of the job in the queue
+pub fn add_job(fractal_calcul_state: &mut FractalCalculState, job: Job) -> u32 {
+    let plan = job.plan();
+    let number = fractal_calcul_state.next_job;
+    fractal_calcul_state.next_job += 1;
+    println!(
+        "Job {}: {} {}x{} pixels, {} iterations, priority {}, saved in {}",
+        number,
+        job.fractal,
+        job.resolution.nx,
+        job.resolution.ny,
+        job.max_iteration,
+        job.priority,
+        job.output
+    );
+    // un job qui arrive part avec la part des autres jobs, pour ne pas leur prendre tous les workers
+    let dispatched = fractal_calcul_state
+        .jobs
+        .values()
+        .map(|render_job| {
+            render_job.dispatched * job.priority as u64 / render_job.job.priority as u64
+        })
+        .min()
+        .unwrap_or(0);
+    //l image du job est peinte par le painter thread
+    let start = PainterMessage::Start {
+        job: number,
+        assembler: ImageAssembler::for_plan(&plan, job.palette),
+        output: job.output.clone(),
+    };
+    if fractal_calcul_state.painter.send(start).is_err() {
+        println!(
+            "Job {}: Error sending the image to the painter thread",
+            number
+        );
+    }
+    fractal_calcul_state.jobs.insert(
+        number,
+        RenderJob {
+            params: create_params(&plan),
+            job,
+            dispatched,
+        },
+    );
+    number
+}
+
+/// to take a job out of the queue once its image is painted: its tasks still computed by workers
+/// are cancelled, the workers accepting it receive a `FragmentCancel`, the others will have their
+/// late results rejected
+/// * `fractal_calcul_state` - the state holding the queue
+/// * `job` - the number of the job
+/// * `reason` - why the tasks are cancelled, sent to the workers
+/// * Return: `Option<RenderJob>` - the job, None if the job is not in the queue
+pub fn finish_job(
+    fractal_calcul_state: &mut FractalCalculState,
+    job: u32,
+    reason: &str,
+) -> Option<RenderJob> {
+    let render_job = fractal_calcul_state.jobs.remove(&job)?;
+    let ids: Vec<Vec<u8>> = fractal_calcul_state
+        .task_jobs
+        .iter()
+        .filter(|(_, task_job)| **task_job == job)
+        .map(|(id, _)| id.clone())
+        .collect();
+
+    let mut cancelled = 0;
+    for id in &ids {
+        let outstanding = !fractal_calcul_state.calcul_state.contains_key(id);
+        if let (true, Some(owner)) = (outstanding, fractal_calcul_state.owners.get(id)) {
+            let cancel =
+                FragmentCancel::new(vec![U8Data::new(0, id.len() as u32)], reason.to_string());
+            let _ = owner.send((Fragment::FragmentCancel(cancel), id.clone()));
+            cancelled += 1;
+        }
+        fractal_calcul_state.task_jobs.remove(id);
+        fractal_calcul_state.tasks_state.remove(id);
+        fractal_calcul_state.calcul_state.remove(id);
+        fractal_calcul_state.last_seen.remove(id);
+        fractal_calcul_state.silent.remove(id);
+        fractal_calcul_state.sessions.remove(id);
+        fractal_calcul_state.owners.remove(id);
+        fractal_calcul_state.received.remove(id);
+    }
+    if cancelled > 0 {
+        println!("Job {}: {} task(s) cancelled", job, cancelled);
+    }
+    Some(render_job)
+}
+
+/// to take the next task a client is able to compute: the workers are shared between the jobs
+/// according to their priority, the task is taken from the job with the fewest tasks handed for its
+/// priority, the oldest job first in case of a tie
+/// * `fractal_calcul_state` - the state holding the tasks to be computed
+/// * `capabilities` - the capabilities of the client
+/// * Return: `Option<(u32, FragmentTask)>` - the number of the job and the task, or None if no remaining task can be computed by the client
+pub fn take_task_for(
+    fractal_calcul_state: &mut FractalCalculState,
+    capabilities: &Capabilities,
+) -> Option<(u32, FragmentTask)> {
+    let (number, render_job) = fractal_calcul_state
+        .jobs
+        .iter_mut()
+        .filter(|(_, render_job)| {
+            render_job
+                .params
+                .iter()
+                .any(|task| capabilities.supports_fractal(&task.fractal))
+        })
+        .min_by(|(_, a), (_, b)| {
+            // a.dispatched / a.priority compare a b.dispatched / b.priority, sans division
+            let a_share = a.dispatched as u128 * b.job.priority as u128;
+            let b_share = b.dispatched as u128 * a.job.priority as u128;
+            a_share.cmp(&b_share)
+        })?;
+    let position = render_job
+        .params
+        .iter()
+        .rposition(|task| capabilities.supports_fractal(&task.fractal))?;
+    render_job.dispatched += 1;
+    Some((*number, render_job.params.remove(position)))
+}
+
 /// to read a message from a client
-/// * `stream` - a mutable reference to a TcpStream
-/// * Return: Result<(Fragment, `Vec<u8>`), io::Error> - a result containing a tuple of Fragment and a vector of bytes  or an io::Error
-pub fn read_message_from_client(stream: &mut TcpStream) -> Result<(Fragment, Vec<u8>), io::Error> {
-    let (fragment, datas) = match read_message(stream) {
+/// * `stream` - a mutable reference to the stream of the client, plain TCP or TLS
+/// * `encoding` - the header encoding negotiated for the session (JSON before and without handshake)
+/// * `limits` - the limits of the frames accepted from the client, each read of a frame being bounded by the time left before its `frame_timeout`
+/// * Return: Result<(Fragment, `Vec<u8>`), FraktError> - a result containing a tuple of Fragment and a vector of bytes  or a FraktError, `UnexpectedFragment` if the client sent a fragment only the server sends
+pub fn read_message_from_client<R: Read + ReadTimeout>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+    limits: &FrameLimits,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    let (fragment, datas) = match read_message_with_deadline(stream, encoding, limits) {
         Ok((Fragment::FragmentRequest(request), datas)) => {
             println!("Client Thread: Request received");
             ((Fragment::FragmentRequest(request)), datas)
@@ -99,11 +513,27 @@ pub fn read_message_from_client(stream: &mut TcpStream) -> Result<(Fragment, Vec
             println!("Client Thread: Result received");
             ((Fragment::FragmentResult(result)), datas)
         }
-        Ok(_) => {
+        Ok((Fragment::FragmentHello(hello), datas)) => {
+            println!("Client Thread: Hello received");
+            ((Fragment::FragmentHello(hello)), datas)
+        }
+        Ok((Fragment::FragmentAuthentication(authentication), datas)) => {
+            println!("Client Thread: Authentication received");
+            ((Fragment::FragmentAuthentication(authentication)), datas)
+        }
+        Ok((Fragment::FragmentProgress(progress), datas)) => {
+            println!("Client Thread: Progress received");
+            ((Fragment::FragmentProgress(progress)), datas)
+        }
+        Ok((Fragment::FragmentResultChunk(chunk), datas)) => {
+            println!("Client Thread: Result chunk received");
+            ((Fragment::FragmentResultChunk(chunk)), datas)
+        }
+        Ok((fragment, _)) => {
             println!("Unknown request received");
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Unknown request received",
+            return Err(FraktError::unexpected(
+                "FragmentRequest, FragmentResult, FragmentResultChunk, FragmentProgress, FragmentHello or FragmentAuthentication",
+                &fragment,
             ));
         }
         Err(err) => {
@@ -115,15 +545,20 @@ pub fn read_message_from_client(stream: &mut TcpStream) -> Result<(Fragment, Vec
 }
 
 /// to format data to a vector of PixelIntensity
-/// * `datas` - a reference to a vector of bytes (u8)
-/// * Return: `Vec<PixelIntensity>` - a vector of PixelIntensity instances
-pub fn format_data_to_pixel_intensity_vector(datas: &Vec<u8>) -> Vec<PixelIntensity> {
+/// * `datas` - a reference to a vector of bytes (u8), the pixels as encoded by the client
+/// * `encoding` - the payload encoding negotiated with the client, compressed pixels are decompressed transparently
+/// * `pixel_count` - the number of pixels expected
+/// * Return: `Result<Vec<PixelIntensity>, FraktError>` - a vector of PixelIntensity instances, or a FraktError if the pixels cannot be decoded
+pub fn format_data_to_pixel_intensity_vector(
+    datas: &[u8],
+    encoding: PayloadEncoding,
+    pixel_count: u32,
+) -> Result<Vec<PixelIntensity>, FraktError> {
+    let pixel_size = std::mem::size_of::<PixelIntensity>();
+    let datas = decode_payload(datas, encoding, pixel_count as usize * pixel_size)?;
     let mut pixel_intensities = Vec::new();
 
-    for chunk in datas.chunks_exact(std::mem::size_of::<PixelIntensity>()) {
-        // Assurez-vous que le chunk a la taille correcte
-        assert_eq!(chunk.len(), std::mem::size_of::<PixelIntensity>());
-
+    for chunk in datas.chunks_exact(pixel_size) {
         // Convertissez chaque groupe d'octets en f32
         let zn_bytes: [u8; 4] = [chunk[0], chunk[1], chunk[2], chunk[3]];
         let count_bytes: [u8; 4] = [chunk[4], chunk[5], chunk[6], chunk[7]];
@@ -134,344 +569,71 @@ pub fn format_data_to_pixel_intensity_vector(datas: &Vec<u8>) -> Vec<PixelIntens
         let pixel_intensity = PixelIntensity::new(zn, count);
         pixel_intensities.push(pixel_intensity);
     }
-    pixel_intensities
-}
-
-///function to create the params for the julia fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for Julia fractal
-pub fn create_params_for_julia() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal: shared_lib::fractal_implementation::fractal::FractalDescriptor::Julia(
-                shared_lib::fractal_types::julia_descriptor::JuliaDescriptor {
-                    c: Complex {
-                        re: 0.285,
-                        im: 0.013,
-                    },
-                    divergence_threshold_square: 4.0,
-                },
-            ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
-    }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the mandelbrot fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for Mandelbrot fractal
-pub fn create_params_for_mandelbrot() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal: shared_lib::fractal_implementation::fractal::FractalDescriptor::Mandelbrot(
-                shared_lib::fractal_types::mandelbrot::Mandelbrot {},
-            ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
-    }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the iterated sin z fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for IteratedSinZ fractal
-pub fn create_params_for_iterated_sin_z() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal: shared_lib::fractal_implementation::fractal::FractalDescriptor::IteratedSinZ(
-                shared_lib::fractal_types::iterated_sin_z::IteratedSinZ {
-                    c: Complex { re: 1.0, im: 0.3 },
-                },
-            ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
-    }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the newton raphson z 3 fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for NewtonRaphsonZ3 fractal
-pub fn create_params_for_newton_raphson_z_3() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal:
-                shared_lib::fractal_implementation::fractal::FractalDescriptor::NewtonRaphsonZ3(
-                    shared_lib::fractal_types::newton_raphson_z_3::NewtonRaphsonZ3 {},
-                ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
-    }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the newton raphson z 4 fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for NewtonRaphsonZ4 fractal
-pub fn create_params_for_newton_raphson_z_4() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal:
-                shared_lib::fractal_implementation::fractal::FractalDescriptor::NewtonRaphsonZ4(
-                    shared_lib::fractal_types::newton_raphson_z_4::NewtonRaphsonZ4 {},
-                ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
-    }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the nova newton raphson z 3 fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for NovaNewtonRaphsonZ3 fractal
-pub fn create_params_for_nova_newton_raphson_z_3() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal:
-                shared_lib::fractal_implementation::fractal::FractalDescriptor::NovaNewtonRaphsonZ3(
-                    shared_lib::fractal_types::nova_newton_raphson_z_3::NovaNewtonRaphsonZ3 {},
-                ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
+    Ok(pixel_intensities)
+}
+
+///function to get the fractal to compute from its name, with its default parameters
+/// * `fractal_name` - the name of the fractal, as given with --fractal; an unknown name gives Julia
+/// * Return: `FractalDescriptor` - the fractal with its default parameters
+pub fn default_fractal(fractal_name: &str) -> FractalDescriptor {
+    match fractal_name {
+        "Mandelbrot" => FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        "IteratedSinZ" => FractalDescriptor::IteratedSinZ(IteratedSinZ {
+            c: Complex { re: 1.0, im: 0.3 },
+        }),
+        "NewtonRaphsonZ3" => FractalDescriptor::NewtonRaphsonZ3(NewtonRaphsonZ3 {}),
+        "NewtonRaphsonZ4" => FractalDescriptor::NewtonRaphsonZ4(NewtonRaphsonZ4 {}),
+        "NovaNewtonRaphsonZ3" => FractalDescriptor::NovaNewtonRaphsonZ3(NovaNewtonRaphsonZ3 {}),
+        "NovaNewtonRaphsonZ4" => FractalDescriptor::NovaNewtonRaphsonZ4(NovaNewtonRaphsonZ4 {}),
+        _ => FractalDescriptor::Julia(JuliaDescriptor {
+            c: Complex {
+                re: 0.285,
+                im: 0.013,
             },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
+            divergence_threshold_square: 4.0,
+        }),
     }
-    println!("Params created");
-
-    params
-}
-
-///function to create the params for the nova newton raphson z 4 fractal
-/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask for NovaNewtonRaphsonZ4 fractal
-pub fn create_params_for_nova_newton_raphson_z_4() -> Vec<FragmentTask> {
-    let mut params = Vec::new();
-
-    let step_size_x = (1.2 - (-1.2)) / 4.0;
-    let step_size_y = (1.2 - (-1.2)) / 4.0;
-    let mut min_x = -1.2;
-    let mut min_y = -1.2;
-    let mut max_x = -0.6;
-    let mut max_y = -0.6;
-
-    for _i in 0..16 {
-        params.push(FragmentTask {
-            id: U8Data::new(0, 16),
-            fractal:
-                shared_lib::fractal_implementation::fractal::FractalDescriptor::NovaNewtonRaphsonZ4(
-                    shared_lib::fractal_types::nova_newton_raphson_z_4::NovaNewtonRaphsonZ4 {},
-                ),
-            max_iteration: 64,
-            resolution: Resolution { nx: 300, ny: 300 },
-            range: Range {
-                min: Point { x: min_x, y: min_y },
-                max: Point { x: max_x, y: max_y },
-            },
-        });
-
-        min_x = max_x;
-        if min_x < 1.2 {
-            max_x = max_x + step_size_x;
-        } else {
-            min_x = -1.2;
-            max_x = -0.6;
-            min_y = max_y;
-            max_y = max_y + step_size_y;
-        }
+}
+
+///function to get the job computed by default for a fractal: 1200x1200 pixels over [-1.2, 1.2]², in tiles of 300x300 pixels computed with 64 iterations, saved in images/server
+/// * `fractal_name` - the name of the fractal, as given with --fractal
+/// * Return: `Job` - the job of the image
+pub fn default_job(fractal_name: &str) -> Job {
+    let fractal = default_fractal(fractal_name);
+    Job {
+        fractal,
+        viewport: Range {
+            min: Point { x: -1.2, y: -1.2 },
+            max: Point { x: 1.2, y: 1.2 },
+        },
+        resolution: Resolution { nx: 1200, ny: 1200 },
+        tile_size: DEFAULT_TILE_SIZE,
+        max_iteration: 64,
+        palette: Palette::Classic,
+        priority: 1,
+        output: format!("images/server/full{}.png", fractal),
     }
-    println!("Params created");
-
-    params
-}
-
-///function to color the pixel of the image_buffer
-/// * `task` - a reference to a FragmentTask to get the coordinates of the pixel to color
-/// * `pixel_intensity_vec` - a reference to a vector of PixelIntensity to get the zn value or count value to color the pixel accordingly
-/// * `image_buffer` - a mutable reference to the image buffer to be colored
-pub fn put_color_in_image(
-    task: &FragmentTask,
-    pixel_intensity_vec: &Vec<PixelIntensity>,
-    image_buffer: &mut image::ImageBuffer<image::Rgb<u8>, Vec<u8>>,
-) {
-    let mut x = ((task.range.min.x + 1.2) / 2.4 * 1200.0) as u32;
-    let mut y = ((task.range.min.y + 1.2) / 2.4 * 1200.0) as u32;
-
-    let y_end = y + 300;
-    let x_end = x + 300;
-
-    let mut count = 0;
-    while y < y_end && count < pixel_intensity_vec.len() {
-        while x < x_end && count < pixel_intensity_vec.len() {
-            let color = match task.fractal {
-                FractalDescriptor::Julia(_) => color(pixel_intensity_vec[count].zn as f64),
-                FractalDescriptor::Mandelbrot(_) => color(pixel_intensity_vec[count].zn as f64),
-                _ => color(pixel_intensity_vec[count].count as f64),
-            };
-            image_buffer.put_pixel(x, y, image::Rgb(color));
+}
 
-            x += 1;
-            count += 1;
+///function to create the params of an image, one FragmentTask per tile
+/// * `plan` - the image to compute and the size of its tiles
+/// * Return: `Vec<FragmentTask>` - a vector of FragmentTask covering the image, empty if the plan is invalid
+pub fn create_params(plan: &TilePlan) -> Vec<FragmentTask> {
+    match plan.tasks() {
+        Ok(params) => {
+            println!("Params created");
+            params
+        }
+        Err(err) => {
+            eprintln!("Error creating params: {}", err);
+            Vec::new()
         }
-        x = ((task.range.min.x + 1.2) / 2.4 * 1200.0) as u32;
-        y += 1;
     }
 }
 
 ///function to get the arguments passed to the server
-/// * Return: (`String`,`String`,`String`) - the ip, the port and the fractal name to be calculated
-pub fn parse_args() -> (String, String, String) {
+/// * Return: (`String`,`String`,`String`,`Duration`) - the ip, the port, the fractal name to be calculated and the idle timeout of client sessions
+pub fn parse_args() -> (String, String, String, Duration) {
     let args: Vec<String> = env::args().collect();
 
     // Vous pouvez également récupérer des arguments spécifiques en utilisant des indices
@@ -490,6 +652,11 @@ pub fn parse_args() -> (String, String, String) {
         .find(|arg| arg.starts_with("--port="))
         .map(|arg| arg.trim_start_matches("--port="));
 
+    let idle_timeout_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--idle-timeout="))
+        .map(|arg| arg.trim_start_matches("--idle-timeout="));
+
     let fractal = match fractal_argument {
         Some(fractal) => {
             println!("Fractal argument: {}", fractal);
@@ -514,13 +681,43 @@ pub fn parse_args() -> (String, String, String) {
         None => "8787".to_string(),
     };
 
+    let idle_timeout = match idle_timeout_argument {
+        Some(idle_timeout) => {
+            println!("Idle timeout argument: {}", idle_timeout);
+            match idle_timeout.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                _ => {
+                    eprintln!("Error while parsing idle timeout argument");
+                    exit(1);
+                }
+            }
+        }
+        None => Duration::from_secs(60),
+    };
+
     if args.len() == 2 {
         if args[1] == "--help" {
             println!("Usage : ./server 0.0.0.0");
             println!("Usage : ./server <flag>");
             println!("Flag: --fractal=<fractal_name>");
+            println!("Flag: --job=<job.toml|job.json> (render the image described by this job file instead of --fractal, may be repeated)");
             println!("fractal_name: Julia, Mandelbrot, IteratedSinZ, NewtonRaphsonZ3, NewtonRaphsonZ4, NovaNewtonRaphsonZ3, NovaNewtonRaphson");
             println!("Flag: --ip=<ip_adress>");
+            println!("Flag: --port=<port>");
+            println!("Flag: --idle-timeout=<seconds>");
+            println!(
+                "Flag: --tls-cert=<cert.pem> --tls-key=<key.pem> (accept TLS connections only)"
+            );
+            println!("Flag: --client-ca=<ca.pem> (with TLS, require a worker certificate signed by this CA)");
+            println!("Flag: --secret-file=<path> (require workers to authenticate with the key of this file)");
+            println!("Flag: --max-frame-size=<bytes> (largest message accepted from a worker)");
+            println!("Flag: --frame-timeout=<seconds> (time allowed to receive a whole message once started)");
+            println!("Flag: --lease=<seconds> (time a worker may stay silent before its task is given to another one)");
+            println!(
+                "Flag: --silent-lease=<seconds> (same for the workers not sending their progress)"
+            );
+            println!("Flag: --ws-port=<port> (also accept workers speaking WebSocket, such as browsers, on this port)");
+            println!("Flag: --unix-socket=<path> (also accept the workers of this machine on this Unix domain socket)");
             // Terminer le programme
             exit(0);
         }
@@ -530,10 +727,129 @@ pub fn parse_args() -> (String, String, String) {
     } else {
     }
 
-    (host.to_string(), port.to_string(), fractal.to_string())
+    (
+        host.to_string(),
+        port.to_string(),
+        fractal.to_string(),
+        idle_timeout,
+    )
+}
+
+///function to get the TLS arguments passed to the server
+/// * Return: `Option<Arc<ServerConfig>>` - the TLS configuration built from the certificate and key given, None to talk plain TCP
+pub fn parse_tls_args() -> Option<Arc<ServerConfig>> {
+    let args: Vec<String> = env::args().collect();
+
+    let find_argument = |flag: &str| {
+        args.iter()
+            .find(|arg| arg.starts_with(flag))
+            .map(|arg| arg.trim_start_matches(flag).to_string())
+    };
+    let cert_argument = find_argument("--tls-cert=");
+    let key_argument = find_argument("--tls-key=");
+    let client_ca_argument = find_argument("--client-ca=");
+
+    let (cert, key) = match (cert_argument, key_argument) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => {
+            if client_ca_argument.is_some() {
+                eprintln!("Error: --client-ca needs --tls-cert and --tls-key");
+                exit(1);
+            }
+            return None;
+        }
+        _ => {
+            eprintln!("Error: --tls-cert and --tls-key must be given together");
+            exit(1);
+        }
+    };
+
+    println!("TLS certificate argument: {}", cert);
+    if let Some(client_ca) = &client_ca_argument {
+        println!("TLS client CA argument: {}", client_ca);
+    }
+    match server_config(&cert, &key, client_ca_argument.as_deref()) {
+        Ok(config) => Some(config),
+        Err(err) => {
+            eprintln!("Error while loading TLS configuration: {}", err);
+            exit(1);
+        }
+    }
+}
+
+///function to get the shared key passed to the server
+/// * Return: `Option<Vec<u8>>` - the key workers must prove they know, None to accept any worker
+pub fn parse_secret_args() -> Option<Vec<u8>> {
+    let args: Vec<String> = env::args().collect();
+
+    let secret_file_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--secret-file="))
+        .map(|arg| arg.trim_start_matches("--secret-file="))?;
+
+    println!("Secret file argument: {}", secret_file_argument);
+    match load_secret(secret_file_argument) {
+        Ok(secret) => Some(secret),
+        Err(err) => {
+            eprintln!("Error while loading secret: {}", err);
+            exit(1);
+        }
+    }
 }
 
-pub fn reset_state(fractal_calcul_state: &mut FractalCalculState) -> String {
+///function to get the limits of the messages accepted by the server
+/// * Return: `FrameLimits` - the limits given with --max-frame-size and --frame-timeout, or the default ones
+pub fn parse_limits_args() -> FrameLimits {
+    let args: Vec<String> = env::args().collect();
+
+    let max_frame_size_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--max-frame-size="))
+        .map(|arg| arg.trim_start_matches("--max-frame-size="));
+
+    let frame_timeout_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--frame-timeout="))
+        .map(|arg| arg.trim_start_matches("--frame-timeout="));
+
+    let max_frame_size = match max_frame_size_argument {
+        Some(max_frame_size) => {
+            println!("Max frame size argument: {}", max_frame_size);
+            match max_frame_size.parse::<u32>() {
+                Ok(size) if size > 0 => size,
+                _ => {
+                    eprintln!("Error while parsing max frame size argument");
+                    exit(1);
+                }
+            }
+        }
+        None => FrameLimits::default().max_frame_size,
+    };
+
+    let frame_timeout = match frame_timeout_argument {
+        Some(frame_timeout) => {
+            println!("Frame timeout argument: {}", frame_timeout);
+            match frame_timeout.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                _ => {
+                    eprintln!("Error while parsing frame timeout argument");
+                    exit(1);
+                }
+            }
+        }
+        None => Duration::from_secs(30),
+    };
+
+    FrameLimits {
+        frame_timeout: Some(frame_timeout),
+        ..FrameLimits::new(max_frame_size)
+    }
+}
+
+///function to add a job computing a random fractal once the queue is empty
+/// * `fractal_calcul_state` - the state receiving the new job
+/// * Return: `u32` - the number of the new job
+pub fn reset_state(fractal_calcul_state: &mut FractalCalculState) -> u32 {
     //used to generate a random fractal for task
     let random_fractal_name = vec![
         "Julia",
@@ -545,56 +861,174 @@ pub fn reset_state(fractal_calcul_state: &mut FractalCalculState) -> String {
         "NovaNewtonRaphsonZ4",
     ];
 
-    let new_fractal = random_fractal_name.choose(&mut rand::thread_rng());
+    let new_fractal = random_fractal_name
+        .choose(&mut rand::thread_rng())
+        .unwrap_or(&"Julia");
 
-    return match new_fractal {
-        Some(&"Julia") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_julia());
-            "Julia".to_string()
-        }
-        Some(&"Mandelbrot") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_mandelbrot());
-            "Mandelbrot".to_string()
-        }
-        Some(&"IteratedSinZ") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_iterated_sin_z());
-            "IteratedSinZ".to_string()
-        }
-        Some(&"NewtonRaphsonZ3") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_newton_raphson_z_3());
-            "NewtonRaphsonZ3".to_string()
-        }
-        Some(&"NewtonRaphsonZ4") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_newton_raphson_z_4());
-            "NewtonRaphsonZ4".to_string()
+    add_job(fractal_calcul_state, default_job(new_fractal))
+}
+
+/// Time the queue stays empty before a random job is added
+pub const NEW_JOB_DELAY: Duration = Duration::from_secs(5);
+
+///function to add a random job once the queue has been empty for `NEW_JOB_DELAY`, without waiting:
+///a timer thread sends `ServerEvent::NewJobDue` to the server thread when the delay is over (see
+///`add_due_job`), the workers asking for a task meanwhile are told to come back later
+/// * `fractal_calcul_state` - the state holding the queue
+/// * `events` - the channel to the server thread, for the timer
+/// * Return: `Option<u32>` - the number of the job added, None if the queue holds a job or has not been empty long enough
+pub fn refill_queue(
+    fractal_calcul_state: &mut FractalCalculState,
+    events: &Sender<ServerEvent>,
+) -> Option<u32> {
+    if !fractal_calcul_state.jobs.is_empty() {
+        fractal_calcul_state.idle_since = None;
+        return None;
+    }
+    let idle_since = match fractal_calcul_state.idle_since {
+        Some(idle_since) => idle_since,
+        None => {
+            println!(
+                "Server Thread: No more job, a new fractal will be generated in {:?}",
+                NEW_JOB_DELAY
+            );
+            let idle_since = *fractal_calcul_state.idle_since.insert(Instant::now());
+            let timer = events.clone();
+            thread::spawn(move || {
+                thread::sleep(NEW_JOB_DELAY);
+                let _ = timer.send(ServerEvent::NewJobDue(idle_since));
+            });
+            idle_since
         }
-        Some(&"NovaNewtonRaphsonZ3") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_nova_newton_raphson_z_3());
-            "NovaNewtonRaphsonZ3".to_string()
+    };
+    if idle_since.elapsed() < NEW_JOB_DELAY {
+        return None;
+    }
+    fractal_calcul_state.idle_since = None;
+    Some(reset_state(fractal_calcul_state))
+}
+
+///function to add the random job a timer thread is due for, without checking the time again: the
+///timer may wake up a little before `NEW_JOB_DELAY` has elapsed for the server thread
+/// * `fractal_calcul_state` - the state holding the queue
+/// * `idle_since` - the time the queue became empty, sent by the timer with `ServerEvent::NewJobDue`
+/// * Return: `Option<u32>` - the number of the job added, None if the queue was refilled since the timer started
+pub fn add_due_job(
+    fractal_calcul_state: &mut FractalCalculState,
+    idle_since: Instant,
+) -> Option<u32> {
+    if !fractal_calcul_state.jobs.is_empty() || fractal_calcul_state.idle_since != Some(idle_since)
+    {
+        return None;
+    }
+    fractal_calcul_state.idle_since = None;
+    Some(reset_state(fractal_calcul_state))
+}
+
+///function to get the port of the WebSocket listener
+/// * Return: `Option<u16>` - the port given with --ws-port, None to accept TCP workers only
+pub fn parse_websocket_args() -> Option<u16> {
+    let args: Vec<String> = env::args().collect();
+
+    let websocket_port_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--ws-port="))
+        .map(|arg| arg.trim_start_matches("--ws-port="))?;
+
+    println!("WebSocket port argument: {}", websocket_port_argument);
+    match websocket_port_argument.parse::<u16>() {
+        Ok(port) => Some(port),
+        Err(_) => {
+            eprintln!("Error while parsing WebSocket port argument");
+            exit(1);
         }
-        Some(&"NovaNewtonRaphsonZ4") => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_nova_newton_raphson_z_4());
-            "NovaNewtonRaphsonZ4".to_string()
+    }
+}
+
+///function to get the path of the Unix domain socket of the server
+/// * Return: `Option<String>` - the path given with --unix-socket, None to accept TCP workers only
+#[cfg(unix)]
+pub fn parse_unix_socket_args() -> Option<String> {
+    let args: Vec<String> = env::args().collect();
+
+    let unix_socket_argument = args
+        .iter()
+        .find(|arg| arg.starts_with("--unix-socket="))
+        .map(|arg| arg.trim_start_matches("--unix-socket="))?;
+
+    println!("Unix socket argument: {}", unix_socket_argument);
+    if unix_socket_argument.is_empty() {
+        eprintln!("Error while parsing Unix socket argument");
+        exit(1);
+    }
+    Some(unix_socket_argument.to_string())
+}
+
+///function to get the lease of the tasks
+/// * Return: `Lease` - the time given with --lease, 30 seconds by default, for the workers sending `FragmentProgress`, and the time given with --silent-lease, 300 seconds by default, for the others
+pub fn parse_lease_args() -> Lease {
+    Lease {
+        progress: parse_seconds_arg("--lease=", "lease", 30),
+        silent: parse_seconds_arg("--silent-lease=", "silent lease", 300),
+    }
+}
+
+///function to get a positive number of seconds given with a flag
+/// * `flag` - the flag, with its `=`
+/// * `name` - the name of the argument, for the logs
+/// * `default` - the number of seconds if the flag is not given
+/// * Return: `Duration` - the time given with the flag
+fn parse_seconds_arg(flag: &str, name: &str, default: u64) -> Duration {
+    let args: Vec<String> = env::args().collect();
+
+    let argument = args
+        .iter()
+        .find(|arg| arg.starts_with(flag))
+        .map(|arg| arg.trim_start_matches(flag));
+
+    match argument {
+        Some(seconds) => {
+            println!("{} argument: {}", name, seconds);
+            match seconds.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                _ => {
+                    eprintln!("Error while parsing {} argument", name);
+                    exit(1);
+                }
+            }
         }
-        _ => {
-            fractal_calcul_state
-                .params
-                .extend(create_params_for_julia());
-            "Julia".to_string()
+        None => Duration::from_secs(default),
+    }
+}
+
+///function to get the job files passed to the server, the jobs are validated before any task is dispatched
+/// * Return: `Vec<Job>` - the jobs read from the files given with --job, in the order of the arguments; empty to compute the fractal given with --fractal
+pub fn parse_job_args() -> Vec<Job> {
+    let args: Vec<String> = env::args().collect();
+
+    let mut jobs: Vec<Job> = Vec::new();
+    for job_argument in args
+        .iter()
+        .filter(|arg| arg.starts_with("--job="))
+        .map(|arg| arg.trim_start_matches("--job="))
+    {
+        println!("Job argument: {}", job_argument);
+        let job = match Job::load(job_argument) {
+            Ok(job) => job,
+            Err(err) => {
+                eprintln!("Error while loading job: {}", err);
+                exit(1);
+            }
+        };
+        //deux jobs ecrivant la meme image s ecraseraient
+        if jobs.iter().any(|other| other.output == job.output) {
+            eprintln!(
+                "Error while loading job: {} writes {} like a previous job",
+                job_argument, job.output
+            );
+            exit(1);
         }
-    };
+        jobs.push(job);
+    }
+    jobs
 }
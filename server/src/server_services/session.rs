@@ -0,0 +1,608 @@
+//! # Client Session
+//!
+//! The `session` module handles one client connection on its own thread. A connection is a
+//! session: the thread keeps reading messages, forwards each of them to the server thread and
+//! sends the answer back, until the client disconnects or stays idle for too long.
+//!
+//! Legacy workers open a new connection for every result and drop the previous one, so their
+//! sessions simply end after the first exchange. Workers that keep the connection open exchange
+//! as many `FragmentTask`/`FragmentResult` rounds as they want over the same socket.
+//!
+//! The first message of a session may be a `FragmentHello`: the worker and the server then agree
+//! on the capabilities used for the session, or the worker is refused with a `FragmentWelcome`
+//! explaining why. Sessions starting directly with a request or a result are legacy sessions.
+//!
+//! When the server is started with a certificate, the TLS handshake is done on the session thread
+//! before any message is read, so a slow or failing client does not hold the other connections.
+//!
+//! Workers on the same machine as the server may connect to its Unix domain socket instead of its
+//! TCP port: no port is exposed and the loopback is skipped. The Unix socket is not encrypted, the
+//! TLS settings only apply to the TCP port, while the authentication applies to both.
+//!
+//! Clients connecting to the WebSocket port, such as browsers, first go through the WebSocket
+//! handshake, over TLS if the server has a certificate. Their frames are then carried in binary
+//! WebSocket messages and their sessions are handled exactly like the TCP ones.
+//!
+//! When the server is started with a shared key, the worker must prove it knows the key during the
+//! handshake (see `shared_lib::messages::authentication`). Legacy sessions and workers failing the
+//! challenge are rejected before any request or result reaches the server thread, and the attempt
+//! is logged with the address of the client.
+//!
+//! While it computes a task, a worker may send `FragmentProgress` messages: they are forwarded to
+//! the server thread, which does not answer them, and they keep the session from being idle.
+//!
+//! A worker accepting it may stream a large result in bands of rows with `FragmentResultChunk`
+//! messages instead of a single `FragmentResult`: each band is forwarded to the server thread as
+//! soon as it is read, and only the last one is answered.
+//!
+//! The pixels of a result are checked against their checksum and decoded on the session thread,
+//! so the server thread only records them: a large or compressed result does not hold the
+//! dispatch of the tasks to the other workers.
+//!
+//! Every message expecting an answer is answered by the server thread, with a task, a
+//! `FragmentNoWork` or a `FragmentError`. Should the answer not come within the idle timeout, the
+//! session is closed rather than leaving the worker waiting forever.
+//!
+//! The server thread may also push a `FragmentCancel` to a worker accepting it, through the same
+//! channel as its answers. The session forwards it while waiting for an answer, or after the next
+//! message of the worker: the periodic `FragmentProgress` of a busy worker bound the delay.
+//!
+//! A client sending a message the session does not expect receives a `FragmentError` explaining
+//! why before the connection is closed.
+
+#[cfg(unix)]
+use std::{
+    fs, io,
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+};
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use shared_lib::{
+    complementary_types::{pixelintensity::PixelIntensity, u8data::U8Data},
+    errors::frakt_error::FraktError,
+    messages::{
+        authentication::{generate_nonce, verify_proof},
+        handshake::{
+            check_protocol_version, Capabilities, HeaderEncoding, PayloadEncoding, PROTOCOL_VERSION,
+        },
+        message::{
+            ErrorCode, Fragment, FragmentChallenge, FragmentError, FragmentHello,
+            FragmentResultChunk, FragmentWelcome,
+        },
+    },
+    messages_methods::{
+        frame::FrameLimits,
+        messages_methods::{send_message_to_client, send_message_with},
+        payload::verify_checksum,
+    },
+    transport::{
+        stream::Stream,
+        tls::{self, ServerConfig},
+        websocket,
+    },
+};
+
+use super::server::{format_data_to_pixel_intensity_vector, read_message_from_client};
+
+/// Message sent by a client thread to the server thread:
+/// * the channel used by the server thread to answer
+/// * the `Fragment` received from the client, a `FragmentResult` is sent as a single `FragmentResultChunk`
+/// * the data received with the `Fragment`, the pixels of a result already decoded
+/// * the capabilities of the client
+/// * the number of the session of the client, telling apart the sessions sending results
+pub type ServerMessage = (
+    Sender<(Fragment, Vec<u8>)>,
+    Fragment,
+    Payload,
+    Capabilities,
+    u64,
+);
+
+/// Number of the next session opened
+static NEXT_SESSION: AtomicU64 = AtomicU64::new(1);
+
+/// Data of a message sent to the server thread:
+/// * Raw: the data as received, for a request or a progress
+/// * Pixels: the unique id of the task of a result with its pixels, or with the code and the reason of the rejection if the pixels are corrupted or cannot be decoded
+#[derive(Debug)]
+pub enum Payload {
+    Raw(Vec<u8>),
+    Pixels(Vec<u8>, Result<Vec<PixelIntensity>, (ErrorCode, String)>),
+}
+
+/// Message received by the server thread:
+/// * Client: a message of a client thread
+/// * JobPainted: every pixel of the image of a job was painted by the painter thread, the job may leave the queue
+/// * NewJobDue: the queue, empty since this time, has been empty for `NEW_JOB_DELAY`, a random job may be added
+#[derive(Debug)]
+pub enum ServerEvent {
+    Client(Box<ServerMessage>),
+    JobPainted(u32),
+    NewJobDue(Instant),
+}
+
+/// Settings shared by every session:
+/// * tls: the TLS configuration of the server, None to talk plain TCP
+/// * secret: the key workers must prove they know, None to accept any worker
+/// * idle_timeout: the maximum time to wait for a message from the client
+/// * limits: the limits of the frames accepted from the clients
+/// * websocket: whether the clients open a WebSocket before sending their frames
+#[derive(Clone)]
+pub struct SessionSettings {
+    pub tls: Option<Arc<ServerConfig>>,
+    pub secret: Option<Arc<Vec<u8>>>,
+    pub idle_timeout: Duration,
+    pub limits: FrameLimits,
+    pub websocket: bool,
+}
+
+/// to accept the connections of the clients, each one handled in its own thread
+/// * `listener` - the socket the clients connect to
+/// * `tx` - the channel to the server thread
+/// * `settings` - the settings of the server for the sessions
+pub fn accept_clients(listener: TcpListener, tx: Sender<ServerEvent>, settings: SessionSettings) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                println!("New client connected");
+                // traiter chaque client dans un thread séparé, tant que sa session est ouverte
+                let tx = tx.clone();
+                let settings = settings.clone();
+                std::thread::spawn(move || handle_client(stream, tx, settings));
+            }
+            Err(e) => {
+                println!("Error accepting connection: {}", e);
+            }
+        }
+    }
+}
+
+/// to listen on a Unix domain socket, replacing the socket left by a previous server
+/// * `path` - the path of the socket
+/// * Return: the socket the local clients connect to, or an `io::Error` if the path is taken by another file
+#[cfg(unix)]
+pub fn bind_unix_socket(path: &str) -> io::Result<UnixListener> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        if metadata.file_type().is_socket() {
+            fs::remove_file(path)?;
+        }
+    }
+    UnixListener::bind(path)
+}
+
+/// to accept the connections of the clients on the same machine, each one handled in its own thread
+/// * `listener` - the Unix domain socket the clients connect to
+/// * `tx` - the channel to the server thread
+/// * `settings` - the settings of the server for the sessions
+#[cfg(unix)]
+pub fn accept_unix_clients(
+    listener: UnixListener,
+    tx: Sender<ServerEvent>,
+    settings: SessionSettings,
+) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                println!("New local client connected");
+                let tx = tx.clone();
+                let settings = settings.clone();
+                std::thread::spawn(move || handle_unix_client(stream, tx, settings));
+            }
+            Err(e) => {
+                println!("Error accepting connection: {}", e);
+            }
+        }
+    }
+}
+
+/// to handle a client connection until it is closed or idle
+/// * `tcp` - the TcpStream of the client
+/// * `tx` - the channel to the server thread
+/// * `settings` - the settings of the server for the sessions
+pub fn handle_client(tcp: TcpStream, tx: Sender<ServerEvent>, settings: SessionSettings) {
+    if let Err(err) = tcp.set_read_timeout(Some(settings.idle_timeout)) {
+        println!("Client Thread: Error setting idle timeout: {}", err);
+        return;
+    }
+    let peer = match tcp.peer_addr() {
+        Ok(address) => address.to_string(),
+        Err(_) => String::from("unknown address"),
+    };
+
+    let stream = match &settings.tls {
+        Some(config) => match tls::accept(tcp, config) {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Client Thread: TLS handshake failed: {}", err);
+                return;
+            }
+        },
+        None => Stream::Tcp(tcp),
+    };
+    handle_session(stream, &peer, tx, settings);
+}
+
+/// to handle a client connection on the Unix domain socket until it is closed or idle
+/// * `unix` - the UnixStream of the client
+/// * `tx` - the channel to the server thread
+/// * `settings` - the settings of the server for the sessions
+#[cfg(unix)]
+pub fn handle_unix_client(unix: UnixStream, tx: Sender<ServerEvent>, settings: SessionSettings) {
+    if let Err(err) = unix.set_read_timeout(Some(settings.idle_timeout)) {
+        println!("Client Thread: Error setting idle timeout: {}", err);
+        return;
+    }
+    handle_session(Stream::Unix(unix), "the Unix socket", tx, settings);
+}
+
+/// to exchange messages with a connected client until the session is closed or idle
+/// * `stream` - the stream of the client, its transport set up
+/// * `peer` - the address of the client, for the logs
+/// * `tx` - the channel to the server thread
+/// * `settings` - the settings of the server for the sessions
+fn handle_session(
+    mut stream: Stream,
+    peer: &str,
+    tx: Sender<ServerEvent>,
+    settings: SessionSettings,
+) {
+    let idle_timeout = settings.idle_timeout;
+    if settings.websocket {
+        stream = match websocket::accept(stream) {
+            Ok(stream) => stream,
+            Err(err) => {
+                println!("Client Thread: WebSocket handshake failed: {}", err);
+                return;
+            }
+        };
+    }
+
+    let (tx_from_client, rx) = mpsc::channel::<(Fragment, Vec<u8>)>();
+    let session = NEXT_SESSION.fetch_add(1, Ordering::Relaxed);
+    let mut exchanges = 0;
+    let mut capabilities = Capabilities::legacy();
+    let mut first_message = true;
+    let mut authenticated = settings.secret.is_none();
+
+    loop {
+        // the client may not send frames larger than what was negotiated during the handshake
+        let limits = FrameLimits {
+            max_frame_size: capabilities
+                .max_message_size
+                .min(settings.limits.max_frame_size),
+            ..settings.limits
+        };
+        let (fragment, data) =
+            match read_message_from_client(&mut stream, capabilities.header_encoding(), &limits) {
+                Ok((fragment, data)) => (fragment, data),
+                Err(e @ FraktError::UnexpectedFragment { .. }) => {
+                    println!(
+                        "Client Thread: Invalid message from {}, closing: {}",
+                        peer, e
+                    );
+                    reject(
+                        &mut stream,
+                        ErrorCode::UnexpectedFragment,
+                        e.to_string(),
+                        &capabilities,
+                    );
+                    break;
+                }
+                Err(e @ (FraktError::Frame(_) | FraktError::Deserialization(_))) => {
+                    println!(
+                        "Client Thread: Invalid message from {}, closing: {}",
+                        peer, e
+                    );
+                    break;
+                }
+                Err(e) if e.is_timeout() => {
+                    println!(
+                        "Client Thread: Session idle for {:?}, closing",
+                        idle_timeout
+                    );
+                    break;
+                }
+                Err(e) if e.is_disconnection() && exchanges > 0 => {
+                    println!("Client Thread: Client closed the session");
+                    break;
+                }
+                Err(e) => {
+                    println!("Error reading message from client: {}", e);
+                    break;
+                }
+            };
+
+        let fragment = match (fragment, first_message) {
+            (Fragment::FragmentHello(hello), true) => {
+                first_message = false;
+                match welcome(&mut stream, &hello, &settings, peer) {
+                    Some(negotiated) => {
+                        capabilities = negotiated;
+                        authenticated = true;
+                        exchanges += 1;
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            (Fragment::FragmentHello(_), false) => {
+                println!("Client Thread: Hello received in the middle of a session, closing");
+                break;
+            }
+            (fragment, _) => fragment,
+        };
+        first_message = false;
+
+        if !authenticated {
+            println!(
+                "Client Thread: Unauthenticated message from {} rejected, authentication is required",
+                peer
+            );
+            reject(
+                &mut stream,
+                ErrorCode::Unauthenticated,
+                String::from("Authentication is required"),
+                &capabilities,
+            );
+            break;
+        }
+
+        // a whole result is seen as its only band, and the pixels are decoded here rather than on
+        // the server thread
+        let (fragment, payload) = match fragment {
+            Fragment::FragmentResult(result) => {
+                let chunk = FragmentResultChunk::whole(result);
+                let payload = decode_result(&chunk, &data, capabilities.payload_encoding());
+                (Fragment::FragmentResultChunk(chunk), payload)
+            }
+            Fragment::FragmentResultChunk(chunk) => {
+                let payload = decode_result(&chunk, &data, capabilities.payload_encoding());
+                (Fragment::FragmentResultChunk(chunk), payload)
+            }
+            fragment => (fragment, Payload::Raw(data)),
+        };
+
+        // progress messages only keep the task alive, and only the last band of a result is
+        // answered: the server thread does not answer the others
+        let expects_answer = match &fragment {
+            Fragment::FragmentProgress(_) => false,
+            Fragment::FragmentResultChunk(chunk) => chunk.last,
+            _ => true,
+        };
+        let message = (
+            tx_from_client.clone(),
+            fragment,
+            payload,
+            capabilities.clone(),
+            session,
+        );
+        match tx.send(ServerEvent::Client(Box::new(message))) {
+            Ok(_) => println!("Client Thread: send fragment and data to server thread"),
+            Err(_) => {
+                println!("Client Thread: Error sending fragment and data to server thread");
+                break;
+            }
+        };
+        if !expects_answer {
+            // the server thread may have pushed cancels meanwhile
+            let mut forwarded = true;
+            while let Ok((pushed, id)) = rx.try_recv() {
+                forwarded = forward(&mut stream, pushed, &id, &capabilities);
+                if !forwarded {
+                    break;
+                }
+            }
+            if !forwarded {
+                break;
+            }
+            continue;
+        }
+
+        // cancels pushed by the server thread are forwarded until the answer arrives
+        let answered = loop {
+            match rx.recv_timeout(idle_timeout) {
+                Ok((fragment, id)) => {
+                    println!("Client Thread: received fragment and id to server thread");
+                    let pushed = matches!(fragment, Fragment::FragmentCancel(_));
+                    if !forward(&mut stream, fragment, &id, &capabilities) {
+                        break false;
+                    }
+                    if !pushed {
+                        break true;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    println!(
+                        "Client Thread: No answer from the server thread within {:?}, closing",
+                        idle_timeout
+                    );
+                    break false;
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    println!("Client Thread: No more task");
+                    break false;
+                }
+            }
+        };
+        if !answered {
+            break;
+        }
+        exchanges += 1;
+    }
+
+    println!("Client disconnected after {} exchange(s)", exchanges);
+}
+
+/// to check and decode the pixels of a band of a result
+/// * `chunk` - the band received
+/// * `data` - the data received with the band: the unique id of the task followed by the pixels
+/// * `encoding` - the payload encoding negotiated with the client
+/// * Return: `Payload` - the id of the task with its pixels, or with the code and the reason of the rejection
+fn decode_result(chunk: &FragmentResultChunk, data: &[u8], encoding: PayloadEncoding) -> Payload {
+    let id_size = (chunk.id.count as usize).min(data.len());
+    let (id, pixels) = data.split_at(id_size);
+
+    // corrupted data are not decoded, the task is computed again
+    if let Err(err) = verify_checksum(chunk.checksum, data) {
+        println!("Client Thread: Corrupted result received: {}", err);
+        return Payload::Pixels(
+            id.to_vec(),
+            Err((ErrorCode::ChecksumMismatch, err.to_string())),
+        );
+    }
+    match format_data_to_pixel_intensity_vector(pixels, encoding, chunk.pixels.count) {
+        Ok(pixel_intensities) => Payload::Pixels(id.to_vec(), Ok(pixel_intensities)),
+        Err(err) => {
+            println!("Client Thread: Invalid pixels received: {}", err);
+            Payload::Pixels(
+                id.to_vec(),
+                Err((ErrorCode::InvalidResult, err.to_string())),
+            )
+        }
+    }
+}
+
+/// to send to the client a message of the server thread
+/// * `stream` - the stream of the client
+/// * `fragment` - the answer or the message pushed by the server thread
+/// * `id` - the data of the message
+/// * `capabilities` - the capabilities of the session
+/// * Return: `bool` - false if the message could not be sent
+fn forward(
+    stream: &mut Stream,
+    fragment: Fragment,
+    id: &[u8],
+    capabilities: &Capabilities,
+) -> bool {
+    match send_message_with(stream, fragment, id, capabilities.header_encoding()) {
+        Ok(_) => {
+            println!("Client Thread: send task to client for calcul");
+            true
+        }
+        Err(_) => {
+            println!("Client Thread: Error sending task to client");
+            false
+        }
+    }
+}
+
+/// to tell a client why its session is closed, before closing it
+/// * `stream` - the stream of the client
+/// * `code` - why the client is rejected
+/// * `message` - details for the logs of the client
+/// * `capabilities` - the capabilities of the session
+fn reject(stream: &mut Stream, code: ErrorCode, message: String, capabilities: &Capabilities) {
+    let error = Fragment::FragmentError(FragmentError::new(code, message));
+    if let Err(err) = send_message_with(stream, error, &[], capabilities.header_encoding()) {
+        println!("Client Thread: Error sending rejection to client: {}", err);
+    }
+}
+
+/// to answer the `FragmentHello` opening a session
+/// * `stream` - the stream of the client
+/// * `hello` - the `FragmentHello` sent by the client
+/// * `settings` - the settings of the server, with the key the client must prove it knows
+/// * `peer` - the address of the client, for the logs
+/// * Return: `Option<Capabilities>` - the capabilities negotiated for the session, or None if the client is refused
+fn welcome(
+    stream: &mut Stream,
+    hello: &FragmentHello,
+    settings: &SessionSettings,
+    peer: &str,
+) -> Option<Capabilities> {
+    let server_capabilities = Capabilities {
+        max_message_size: settings.limits.max_frame_size,
+        ..Capabilities::supported()
+    };
+    let negotiated = check_protocol_version(hello.protocol_version)
+        .and_then(|_| server_capabilities.negotiate(&hello.capabilities));
+    let negotiated = match (negotiated, &settings.secret) {
+        (Ok(negotiated), Some(secret)) => {
+            match authenticate(stream, hello, secret, &settings.limits) {
+                Ok(_) => Ok(negotiated),
+                Err(reason) => {
+                    println!(
+                        "Client Thread: Authentication of {} from {} failed: {}",
+                        hello.worker_name, peer, reason
+                    );
+                    Err(String::from("Authentication failed"))
+                }
+            }
+        }
+        (negotiated, _) => negotiated,
+    };
+
+    let (welcome, negotiated) = match negotiated {
+        Ok(negotiated) => {
+            println!(
+                "Client Thread: {} accepted, capabilities: {:?}",
+                hello.worker_name, negotiated
+            );
+            (
+                FragmentWelcome::accept(PROTOCOL_VERSION, negotiated.clone()),
+                Some(negotiated),
+            )
+        }
+        Err(reason) => {
+            println!("Client Thread: {} refused: {}", hello.worker_name, reason);
+            (
+                FragmentWelcome::refuse(PROTOCOL_VERSION, reason, server_capabilities),
+                None,
+            )
+        }
+    };
+
+    match send_message_to_client(stream, Fragment::FragmentWelcome(welcome), Vec::new()) {
+        Ok(_) => negotiated,
+        Err(err) => {
+            println!("Client Thread: Error sending welcome to client: {}", err);
+            None
+        }
+    }
+}
+
+/// to challenge a client to prove it knows the shared key
+/// * `stream` - the stream of the client
+/// * `hello` - the `FragmentHello` sent by the client
+/// * `secret` - the shared key
+/// * `limits` - the limits of the frames accepted from the client
+/// * Return: `Result<(), String>` - an error explaining why the client is not authenticated
+fn authenticate(
+    stream: &mut Stream,
+    hello: &FragmentHello,
+    secret: &[u8],
+    limits: &FrameLimits,
+) -> Result<(), String> {
+    let nonce = generate_nonce();
+    let challenge = FragmentChallenge::new(U8Data::new(0, nonce.len() as u32));
+    send_message_to_client(
+        stream,
+        Fragment::FragmentChallenge(challenge),
+        nonce.clone(),
+    )
+    .map_err(|err| format!("Error sending challenge: {}", err))?;
+
+    match read_message_from_client(stream, HeaderEncoding::Json, limits) {
+        Ok((Fragment::FragmentAuthentication(authentication), data)) => {
+            let start = authentication.proof.offset as usize;
+            let end = start + authentication.proof.count as usize;
+            match data.get(start..end) {
+                Some(proof) if verify_proof(secret, &nonce, &hello.worker_name, proof) => Ok(()),
+                _ => Err(String::from("wrong proof")),
+            }
+        }
+        Ok(_) => Err(String::from("the challenge was not answered")),
+        Err(err) => Err(format!("no answer to the challenge: {}", err)),
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(test)]
+use shared_lib::{
+    complementary_types::{pixelintensity::PixelIntensity, resolution::Resolution},
+    fractal_implementation::{assembler::ImageAssembler, palette::Palette},
+    job::job_file::Job,
+    messages::message::FragmentTask,
+};
+
+#[cfg(test)]
+use super::painter::{spawn_painter, PainterMessage};
+#[cfg(test)]
+use super::server::{create_params, default_job};
+#[cfg(test)]
+use super::session::ServerEvent;
+
+/// a job of 200x200 pixels cut into 4 tiles, its image saved in the temporary directory
+#[cfg(test)]
+fn job(name: &str) -> Job {
+    let directory =
+        std::env::temp_dir().join(format!("frakt_test_{}_{}", name, std::process::id()));
+    Job {
+        resolution: Resolution::new(200, 200),
+        tile_size: Resolution::new(100, 100),
+        output: directory.join("job.png").to_string_lossy().into_owned(),
+        ..default_job("Julia")
+    }
+}
+
+/// a painter thread with the job started, and the tasks of the job
+#[cfg(test)]
+fn start_painter(
+    job: &Job,
+) -> (
+    Sender<PainterMessage>,
+    Receiver<ServerEvent>,
+    Vec<FragmentTask>,
+) {
+    let (server, events) = mpsc::channel();
+    let painter = spawn_painter(server);
+    let plan = job.plan();
+    painter
+        .send(PainterMessage::Start {
+            job: 1,
+            assembler: ImageAssembler::for_plan(&plan, Palette::Classic),
+            output: job.output.clone(),
+        })
+        .unwrap();
+    (painter, events, create_params(&plan))
+}
+
+#[cfg(test)]
+fn paint(painter: &Sender<PainterMessage>, task: &FragmentTask, first_pixel: u32, count: u32) {
+    painter
+        .send(PainterMessage::Paint {
+            job: 1,
+            task: *task,
+            first_pixel,
+            pixels: vec![PixelIntensity::new(0.5, 0.25); count as usize],
+        })
+        .unwrap();
+}
+
+/// the size of the image saved, once the saver thread wrote it whole
+#[cfg(test)]
+fn wait_for_image(path: &str) -> Option<(u32, u32)> {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(10) {
+        if let Ok(image) = image::open(path) {
+            return Some((image.width(), image.height()));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    None
+}
+
+#[cfg(test)]
+fn remove_output(job: &Job) {
+    let output = PathBuf::from(&job.output);
+    if let Some(directory) = output.parent() {
+        let _ = fs::remove_dir_all(directory);
+    }
+}
+
+#[test]
+fn test_complete_image_is_reported_once_and_saved() {
+    let job = job("painter_complete");
+    let (painter, events, tasks) = start_painter(&job);
+    assert_eq!(tasks.len(), 4);
+
+    // the first tile in two bands, the others whole
+    paint(&painter, &tasks[0], 0, 5000);
+    paint(&painter, &tasks[0], 5000, 5000);
+    for task in &tasks[1..3] {
+        paint(&painter, task, 0, 10000);
+    }
+    assert!(matches!(
+        events.recv_timeout(Duration::from_millis(200)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+
+    paint(&painter, &tasks[3], 0, 10000);
+    assert!(matches!(
+        events.recv_timeout(Duration::from_secs(5)),
+        Ok(ServerEvent::JobPainted(1))
+    ));
+
+    // the job left the painter: pixels painted again are not reported a second time
+    paint(&painter, &tasks[3], 0, 10000);
+    assert!(matches!(
+        events.recv_timeout(Duration::from_millis(200)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+
+    let saved = wait_for_image(&job.output);
+    remove_output(&job);
+    assert_eq!(saved, Some((200, 200)));
+}
+
+#[test]
+fn test_pixels_of_an_unknown_job_are_ignored() {
+    let job = job("painter_unknown");
+    let (painter, events, tasks) = start_painter(&job);
+
+    for task in &tasks {
+        painter
+            .send(PainterMessage::Paint {
+                job: 2,
+                task: *task,
+                first_pixel: 0,
+                pixels: vec![PixelIntensity::new(0.5, 0.25); 10000],
+            })
+            .unwrap();
+    }
+    assert!(matches!(
+        events.recv_timeout(Duration::from_millis(200)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+    assert!(!Path::new(&job.output).exists());
+}
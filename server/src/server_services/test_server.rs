@@ -0,0 +1,484 @@
+#[cfg(test)]
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+#[cfg(test)]
+use shared_lib::{
+    complementary_types::{pixelintensity::PixelIntensity, resolution::Resolution},
+    job::job_file::Job,
+    messages::{
+        handshake::Capabilities,
+        message::{ErrorCode, Fragment},
+    },
+};
+
+#[cfg(test)]
+use super::painter::PainterMessage;
+#[cfg(test)]
+use super::server::{
+    accept_result, add_due_job, add_job, assign_tasks, default_job, find_result_task, finish_job,
+    reclaim_expired_tasks, refill_queue, requeue_task, take_task_for, FractalCalculState, Lease,
+    MAX_BATCH_TASKS, NEW_JOB_DELAY,
+};
+#[cfg(test)]
+use super::session::ServerEvent;
+
+#[cfg(test)]
+fn empty_state() -> (FractalCalculState, Receiver<PainterMessage>) {
+    let (painter, painted) = mpsc::channel();
+    let state = FractalCalculState {
+        jobs: BTreeMap::new(),
+        next_job: 1,
+        task_jobs: HashMap::new(),
+        tasks_state: HashMap::new(),
+        calcul_state: HashMap::new(),
+        last_seen: HashMap::new(),
+        silent: HashSet::new(),
+        sessions: HashMap::new(),
+        owners: HashMap::new(),
+        received: HashMap::new(),
+        painter,
+        idle_since: None,
+    };
+    (state, painted)
+}
+
+/// a job cut into tiles of 100x100 pixels
+#[cfg(test)]
+fn job(size: u16, priority: u32) -> Job {
+    Job {
+        resolution: Resolution::new(size, size),
+        tile_size: Resolution::new(100, 100),
+        priority,
+        output: String::from("images/test/job.png"),
+        ..default_job("Julia")
+    }
+}
+
+/// the number of the session of the worker the tasks are handed to
+#[cfg(test)]
+const SESSION: u64 = 1;
+
+#[cfg(test)]
+fn assign_one(
+    state: &mut FractalCalculState,
+    capabilities: &Capabilities,
+    owner: &Sender<(Fragment, Vec<u8>)>,
+) -> Vec<u8> {
+    match assign_tasks(state, capabilities, 1, owner, SESSION) {
+        Some((Fragment::FragmentTask(_), id)) => id,
+        other => panic!("expected a single task, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_silent_task_is_held_to_the_silent_lease() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let reporting = assign_one(&mut state, &Capabilities::supported(), &owner);
+    let silent = assign_one(&mut state, &Capabilities::legacy(), &owner);
+    assert!(!state.silent.contains(&reporting));
+    assert!(state.silent.contains(&silent));
+
+    thread::sleep(Duration::from_millis(20));
+    let lease = Lease {
+        progress: Duration::from_millis(10),
+        silent: Duration::from_secs(60),
+    };
+    assert_eq!(reclaim_expired_tasks(&mut state, lease), 1);
+    assert!(!state.tasks_state.contains_key(&reporting));
+    assert!(state.tasks_state.contains_key(&silent));
+    assert_eq!(state.jobs[&job].params.len(), 3);
+
+    let lease = Lease {
+        progress: Duration::from_millis(10),
+        silent: Duration::from_millis(10),
+    };
+    assert_eq!(reclaim_expired_tasks(&mut state, lease), 1);
+    assert!(state.tasks_state.is_empty());
+    assert!(state.last_seen.is_empty());
+    assert!(state.silent.is_empty());
+    assert_eq!(state.jobs[&job].params.len(), 4);
+}
+
+#[test]
+fn test_renewed_lease_is_not_reclaimed() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    let lease = Lease {
+        progress: Duration::from_secs(60),
+        silent: Duration::from_secs(60),
+    };
+    assert_eq!(reclaim_expired_tasks(&mut state, lease), 0);
+    assert!(state.tasks_state.contains_key(&id));
+    assert!(state.last_seen.contains_key(&id));
+}
+
+#[test]
+fn test_requeued_task_is_forgotten_and_handed_again() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+    let task = state.tasks_state[&id];
+    state.received.insert(id.clone(), 100);
+    assert_eq!(state.jobs[&job].params.len(), 3);
+
+    assert!(requeue_task(&mut state, &id));
+    assert_eq!(state.jobs[&job].params.len(), 4);
+    assert!(state.jobs[&job]
+        .params
+        .iter()
+        .any(|param| param.range == task.range));
+    assert!(!state.tasks_state.contains_key(&id));
+    assert!(!state.task_jobs.contains_key(&id));
+    assert!(!state.last_seen.contains_key(&id));
+    assert!(!state.owners.contains_key(&id));
+    assert!(!state.received.contains_key(&id));
+
+    assert!(!requeue_task(&mut state, &id));
+    assert_eq!(state.jobs[&job].params.len(), 4);
+}
+
+#[test]
+fn test_result_finds_its_task() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    let (task, task_job) = find_result_task(&state, &id, SESSION).unwrap();
+    assert_eq!(task, state.tasks_state[&id]);
+    assert_eq!(task_job, job);
+}
+
+#[test]
+fn test_late_result_is_rejected() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    thread::sleep(Duration::from_millis(20));
+    let lease = Lease {
+        progress: Duration::from_millis(10),
+        silent: Duration::from_millis(10),
+    };
+    assert_eq!(reclaim_expired_tasks(&mut state, lease), 1);
+    let (code, reason) = find_result_task(&state, &id, SESSION).unwrap_err();
+    assert_eq!(code, ErrorCode::UnknownTask);
+    assert!(reason.contains("lease may have expired"));
+}
+
+#[test]
+fn test_duplicate_result_is_rejected() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+    state.calcul_state.insert(id.clone(), Vec::new());
+
+    let (code, reason) = find_result_task(&state, &id, SESSION).unwrap_err();
+    assert_eq!(code, ErrorCode::UnknownTask);
+    assert!(reason.contains("already received"));
+}
+
+#[test]
+fn test_result_of_another_session_is_rejected() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    let (code, reason) = find_result_task(&state, &id, SESSION + 1).unwrap_err();
+    assert_eq!(code, ErrorCode::UnknownTask);
+    assert!(reason.contains("another worker"));
+}
+
+#[test]
+fn test_corrupted_result_gives_back_its_task() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    let corrupted = Err((ErrorCode::ChecksumMismatch, String::from("corrupted")));
+    let (code, _) = accept_result(&mut state, &id, SESSION, corrupted).unwrap_err();
+    assert_eq!(code, ErrorCode::ChecksumMismatch);
+    assert!(!state.tasks_state.contains_key(&id));
+    assert!(!state.sessions.contains_key(&id));
+    assert_eq!(state.jobs[&job].params.len(), 4);
+}
+
+#[test]
+fn test_corrupted_duplicate_leaves_the_completed_task() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+    let pixels = vec![PixelIntensity::new(0.5, 0.25); 10000];
+    assert!(accept_result(&mut state, &id, SESSION, Ok(pixels)).is_ok());
+    state.calcul_state.insert(id.clone(), Vec::new());
+
+    let corrupted = Err((ErrorCode::ChecksumMismatch, String::from("corrupted")));
+    let (code, reason) = accept_result(&mut state, &id, SESSION, corrupted).unwrap_err();
+    assert_eq!(code, ErrorCode::UnknownTask);
+    assert!(reason.contains("already received"));
+    assert!(state.tasks_state.contains_key(&id));
+    assert_eq!(state.task_jobs.get(&id), Some(&job));
+    assert_eq!(state.jobs[&job].params.len(), 3);
+}
+
+#[test]
+fn test_corrupted_result_of_another_session_leaves_the_lease() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    let job = add_job(&mut state, job(200, 1));
+    let id = assign_one(&mut state, &Capabilities::supported(), &owner);
+
+    let corrupted = Err((ErrorCode::ChecksumMismatch, String::from("corrupted")));
+    let (code, reason) = accept_result(&mut state, &id, SESSION + 1, corrupted).unwrap_err();
+    assert_eq!(code, ErrorCode::UnknownTask);
+    assert!(reason.contains("another worker"));
+    assert!(state.tasks_state.contains_key(&id));
+    assert!(state.last_seen.contains_key(&id));
+    assert_eq!(state.sessions.get(&id), Some(&SESSION));
+    assert_eq!(state.jobs[&job].params.len(), 3);
+}
+
+#[test]
+fn test_workers_are_shared_by_priority() {
+    let (mut state, _painted) = empty_state();
+    let low = add_job(&mut state, job(400, 1));
+    let high = add_job(&mut state, job(400, 3));
+
+    let taken: Vec<u32> = (0..8)
+        .map(|_| {
+            take_task_for(&mut state, &Capabilities::supported())
+                .unwrap()
+                .0
+        })
+        .collect();
+    assert_eq!(taken.iter().filter(|number| **number == low).count(), 2);
+    assert_eq!(taken.iter().filter(|number| **number == high).count(), 6);
+    assert_eq!(state.jobs[&low].dispatched, 2);
+    assert_eq!(state.jobs[&high].dispatched, 6);
+    assert_eq!(state.jobs[&low].params.len(), 14);
+    assert_eq!(state.jobs[&high].params.len(), 10);
+}
+
+#[test]
+fn test_new_job_starts_with_the_share_of_the_others() {
+    let (mut state, painted) = empty_state();
+    let first = add_job(&mut state, job(400, 1));
+    assert_eq!(state.jobs[&first].dispatched, 0);
+    assert!(matches!(
+        painted.try_recv(),
+        Ok(PainterMessage::Start { job, .. }) if job == first
+    ));
+    for _ in 0..6 {
+        take_task_for(&mut state, &Capabilities::supported()).unwrap();
+    }
+
+    // starting from 0, the new jobs would take every worker until they caught up with the first one
+    let second = add_job(&mut state, job(400, 2));
+    assert_eq!(state.jobs[&second].dispatched, 12);
+    let third = add_job(&mut state, job(400, 1));
+    assert_eq!(state.jobs[&third].dispatched, 6);
+
+    let (number, _) = take_task_for(&mut state, &Capabilities::supported()).unwrap();
+    assert_eq!(number, first);
+}
+
+#[test]
+fn test_oldest_job_first_on_a_tie() {
+    let (mut state, _painted) = empty_state();
+    let first = add_job(&mut state, job(200, 1));
+    let second = add_job(&mut state, job(200, 1));
+
+    let taken: Vec<u32> = (0..4)
+        .map(|_| {
+            take_task_for(&mut state, &Capabilities::supported())
+                .unwrap()
+                .0
+        })
+        .collect();
+    assert_eq!(taken, vec![first, second, first, second]);
+}
+
+#[test]
+fn test_no_task_for_a_fractal_the_worker_cannot_compute() {
+    let (mut state, _painted) = empty_state();
+    add_job(&mut state, job(200, 1));
+    let capabilities = Capabilities {
+        fractals: vec![String::from("Mandelbrot")],
+        ..Capabilities::supported()
+    };
+    assert!(take_task_for(&mut state, &capabilities).is_none());
+}
+
+#[test]
+fn test_finish_job_cancels_the_outstanding_tasks_of_owners() {
+    let (mut state, _painted) = empty_state();
+    let finished = add_job(&mut state, job(400, 1));
+    let (cancellable, cancellable_answers) = mpsc::channel();
+    let (legacy, legacy_answers) = mpsc::channel();
+    let (done, done_answers) = mpsc::channel();
+    let other = add_job(
+        &mut state,
+        Job {
+            fractal: default_job("Mandelbrot").fractal,
+            ..job(200, 1)
+        },
+    );
+    // each worker only computes the fractal of one of the jobs
+    let julia = |capabilities: Capabilities| Capabilities {
+        fractals: vec![String::from("Julia")],
+        ..capabilities
+    };
+    let mandelbrot = Capabilities {
+        fractals: vec![String::from("Mandelbrot")],
+        ..Capabilities::supported()
+    };
+
+    let outstanding = assign_one(&mut state, &julia(Capabilities::supported()), &cancellable);
+    let not_cancellable = assign_one(&mut state, &julia(Capabilities::legacy()), &legacy);
+    let computed = assign_one(&mut state, &julia(Capabilities::supported()), &done);
+    state.calcul_state.insert(computed.clone(), Vec::new());
+    let (other_owner, other_answers) = mpsc::channel();
+    let other_task = assign_one(&mut state, &mandelbrot, &other_owner);
+    assert_eq!(state.task_jobs[&other_task], other);
+
+    let render_job = finish_job(&mut state, finished, "Image complete").unwrap();
+    assert_eq!(render_job.dispatched, 3);
+    assert!(!state.jobs.contains_key(&finished));
+
+    match cancellable_answers.try_recv() {
+        Ok((Fragment::FragmentCancel(cancel), id)) => {
+            assert_eq!(id, outstanding);
+            assert_eq!(cancel.ids.len(), 1);
+            assert_eq!(cancel.ids[0].count, 16);
+            assert_eq!(cancel.reason, "Image complete");
+        }
+        other => panic!("expected a FragmentCancel, got {:?}", other),
+    }
+    assert!(cancellable_answers.try_recv().is_err());
+    assert!(legacy_answers.try_recv().is_err());
+    assert!(done_answers.try_recv().is_err());
+    assert!(other_answers.try_recv().is_err());
+
+    for id in [&outstanding, &not_cancellable, &computed] {
+        assert!(!state.task_jobs.contains_key(id));
+        assert!(!state.tasks_state.contains_key(id));
+        assert!(!state.calcul_state.contains_key(id));
+        assert!(!state.last_seen.contains_key(id));
+        assert!(!state.owners.contains_key(id));
+    }
+    assert!(state.tasks_state.contains_key(&other_task));
+    assert!(state.owners.contains_key(&other_task));
+
+    assert!(finish_job(&mut state, finished, "Image complete").is_none());
+}
+
+#[test]
+fn test_empty_queue_is_refilled_by_the_timer() {
+    let (mut state, _painted) = empty_state();
+    let (events, timer) = mpsc::channel();
+    let finished = add_job(&mut state, job(200, 1));
+    assert!(refill_queue(&mut state, &events).is_none());
+    assert!(state.idle_since.is_none());
+
+    finish_job(&mut state, finished, "Image complete").unwrap();
+    assert!(refill_queue(&mut state, &events).is_none());
+    assert!(refill_queue(&mut state, &events).is_none());
+    assert!(state.idle_since.is_some());
+    assert!(state.jobs.is_empty());
+
+    // a single timer, even if the queue is checked again meanwhile
+    let idle_since = match timer.recv_timeout(NEW_JOB_DELAY + Duration::from_secs(5)) {
+        Ok(ServerEvent::NewJobDue(idle_since)) => idle_since,
+        other => panic!("expected the timer, got {:?}", other),
+    };
+    assert_eq!(state.idle_since, Some(idle_since));
+    let added = add_due_job(&mut state, idle_since).unwrap();
+    assert!(state.jobs.contains_key(&added));
+    assert!(state.idle_since.is_none());
+    assert!(matches!(
+        timer.recv_timeout(Duration::from_millis(200)),
+        Err(RecvTimeoutError::Timeout)
+    ));
+}
+
+#[test]
+fn test_due_job_is_added_as_soon_as_the_timer_fires() {
+    let (mut state, _painted) = empty_state();
+    let (events, _timer) = mpsc::channel();
+    assert!(refill_queue(&mut state, &events).is_none());
+    let idle_since = state.idle_since.unwrap();
+
+    // the event is handled before NEW_JOB_DELAY has elapsed for the server thread
+    let added = add_due_job(&mut state, idle_since).unwrap();
+    assert!(state.jobs.contains_key(&added));
+    assert!(state.idle_since.is_none());
+}
+
+#[test]
+fn test_stale_timer_adds_no_job() {
+    let (mut state, _painted) = empty_state();
+    let (events, _timer) = mpsc::channel();
+    assert!(refill_queue(&mut state, &events).is_none());
+    let stale = state.idle_since.unwrap();
+
+    // the queue was refilled, then emptied again before the first timer fired
+    let refilled = add_due_job(&mut state, stale).unwrap();
+    finish_job(&mut state, refilled, "Image complete").unwrap();
+    thread::sleep(Duration::from_millis(1));
+    assert!(refill_queue(&mut state, &events).is_none());
+    assert!(add_due_job(&mut state, stale).is_none());
+    assert!(state.jobs.is_empty());
+
+    let idle_since = state.idle_since.unwrap();
+    assert_ne!(idle_since, stale);
+    assert!(add_due_job(&mut state, idle_since).is_some());
+    assert!(add_due_job(&mut state, idle_since).is_none());
+    assert_eq!(state.jobs.len(), 1);
+}
+
+#[test]
+fn test_batch_is_capped() {
+    let (mut state, _painted) = empty_state();
+    let (owner, _answers) = mpsc::channel();
+    // 100 tasks in the queue
+    let job = add_job(&mut state, job(1000, 1));
+
+    let greedy = match assign_tasks(
+        &mut state,
+        &Capabilities::supported(),
+        u32::MAX,
+        &owner,
+        SESSION,
+    ) {
+        Some((Fragment::FragmentBatch(batch), ids)) => {
+            assert_eq!(ids.len(), batch.tasks.len() * 16);
+            batch.tasks.len()
+        }
+        other => panic!("expected a batch, got {:?}", other),
+    };
+    assert_eq!(greedy, MAX_BATCH_TASKS as usize);
+    assert_eq!(state.tasks_state.len(), MAX_BATCH_TASKS as usize);
+    assert_eq!(
+        state.jobs[&job].params.len(),
+        100 - MAX_BATCH_TASKS as usize
+    );
+
+    // the other workers still find work
+    assign_one(&mut state, &Capabilities::supported(), &owner);
+}
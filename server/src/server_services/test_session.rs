@@ -0,0 +1,331 @@
+#[cfg(test)]
+use std::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+#[cfg(test)]
+use shared_lib::{
+    complementary_types::{
+        pixelintensity::PixelIntensity, point::Point, range::Range, resolution::Resolution,
+        u8data::U8Data,
+    },
+    fractal_implementation::fractal::FractalDescriptor,
+    fractal_types::mandelbrot::Mandelbrot,
+    messages::{
+        authentication::compute_proof,
+        handshake::{Capabilities, PROTOCOL_VERSION},
+        message::{
+            ErrorCode, Fragment, FragmentAuthentication, FragmentHello, FragmentNoWork,
+            FragmentRequest, FragmentResult, FragmentTask,
+        },
+    },
+    messages_methods::{
+        frame::FrameLimits,
+        messages_methods::{read_message, send_message},
+        payload::encode_pixels,
+    },
+};
+
+#[cfg(test)]
+use super::session::{handle_client, Payload, ServerEvent, ServerMessage, SessionSettings};
+
+/// Time to wait for the session thread in a test
+#[cfg(test)]
+const TEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// the settings of a plain TCP server, with the key of the workers if any
+#[cfg(test)]
+fn settings(secret: Option<&[u8]>, idle_timeout: Duration) -> SessionSettings {
+    SessionSettings {
+        tls: None,
+        secret: secret.map(|secret| secret.to_vec().into()),
+        idle_timeout,
+        limits: FrameLimits::default(),
+        websocket: false,
+    }
+}
+
+/// a client connected to a session thread over the loopback
+/// * Return: the stream of the client, the messages sent to the server thread, and a channel closed when the session ends
+#[cfg(test)]
+fn start_session(settings: SessionSettings) -> (TcpStream, Receiver<ServerEvent>, Receiver<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let (tx, events) = mpsc::channel();
+    let (ended, session) = mpsc::channel::<()>();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        handle_client(stream, tx, settings);
+        drop(ended);
+    });
+
+    let client = TcpStream::connect(address).unwrap();
+    client.set_read_timeout(Some(TEST_TIMEOUT)).unwrap();
+    (client, events, session)
+}
+
+/// the next message the session sent to the server thread
+#[cfg(test)]
+fn next_message(events: &Receiver<ServerEvent>) -> ServerMessage {
+    match events.recv_timeout(TEST_TIMEOUT) {
+        Ok(ServerEvent::Client(message)) => *message,
+        other => panic!("expected a message of the client, got {:?}", other),
+    }
+}
+
+/// whether the session thread returned in time
+#[cfg(test)]
+fn session_ended(session: &Receiver<()>) -> bool {
+    matches!(
+        session.recv_timeout(TEST_TIMEOUT),
+        Err(RecvTimeoutError::Disconnected)
+    )
+}
+
+#[cfg(test)]
+fn task(index: u8) -> (FragmentTask, Vec<u8>) {
+    let task = FragmentTask::new(
+        U8Data::new(0, 16),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(4, 4),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+    );
+    (task, vec![index; 16])
+}
+
+/// the answer of the server thread to a message of the session
+#[cfg(test)]
+fn answer(sender: &Sender<(Fragment, Vec<u8>)>, fragment: Fragment, data: Vec<u8>) {
+    sender.send((fragment, data)).unwrap();
+}
+
+#[cfg(test)]
+fn send_result(client: &mut TcpStream, task: &FragmentTask, id: &[u8]) {
+    let pixels = vec![PixelIntensity::new(0.5, 0.25); 16];
+    let mut data = id.to_vec();
+    data.extend(encode_pixels(&pixels, Capabilities::legacy().payload_encoding()).unwrap());
+    send_message(
+        client,
+        Fragment::FragmentResult(FragmentResult::create(task)),
+        &data,
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+fn send_request(client: &mut TcpStream) {
+    let request = FragmentRequest::new(String::from("test_worker"), 1);
+    send_message(client, Fragment::FragmentRequest(request), &[]).unwrap();
+}
+
+/// to open a session with a handshake, keeping the legacy encodings, and read the challenge of the server
+/// * Return: the nonce to prove the key with
+#[cfg(test)]
+fn open_challenge(client: &mut TcpStream) -> Vec<u8> {
+    let hello = FragmentHello::new(
+        PROTOCOL_VERSION,
+        String::from("test_worker"),
+        Capabilities::legacy(),
+    );
+    send_message(client, Fragment::FragmentHello(hello), &[]).unwrap();
+    match read_message(client).unwrap() {
+        (Fragment::FragmentChallenge(challenge), data) => {
+            let start = challenge.nonce.offset as usize;
+            data[start..start + challenge.nonce.count as usize].to_vec()
+        }
+        other => panic!("expected a challenge, got {:?}", other),
+    }
+}
+
+#[cfg(test)]
+fn send_proof(client: &mut TcpStream, proof: &[u8]) {
+    let authentication = FragmentAuthentication::new(U8Data::new(0, proof.len() as u32));
+    send_message(
+        client,
+        Fragment::FragmentAuthentication(authentication),
+        proof,
+    )
+    .unwrap();
+}
+
+/// whether the worker was accepted by the `FragmentWelcome` of the server
+#[cfg(test)]
+fn welcomed(client: &mut TcpStream) -> bool {
+    match read_message(client).unwrap() {
+        (Fragment::FragmentWelcome(welcome), _) => welcome.accepted,
+        other => panic!("expected a welcome, got {:?}", other),
+    }
+}
+
+/// whether the session ended without sending anything to the server thread
+#[cfg(test)]
+fn ended_unheard(events: &Receiver<ServerEvent>, session: &Receiver<()>) -> bool {
+    session_ended(session) && matches!(events.try_recv(), Err(mpsc::TryRecvError::Disconnected))
+}
+
+#[test]
+fn test_session_stays_open_for_several_rounds() {
+    let (mut client, events, session) = start_session(settings(None, TEST_TIMEOUT));
+
+    send_request(&mut client);
+    let (sender, fragment, _, _, _) = next_message(&events);
+    assert!(matches!(fragment, Fragment::FragmentRequest(_)));
+    let (mut current, mut id) = task(0);
+    answer(&sender, Fragment::FragmentTask(current), id.clone());
+
+    for round in 1..4 {
+        match read_message(&mut client).unwrap() {
+            (Fragment::FragmentTask(received), data) => {
+                assert_eq!(received, current);
+                assert_eq!(data, id);
+            }
+            other => panic!("expected a task, got {:?}", other),
+        }
+        send_result(&mut client, &current, &id);
+
+        let (sender, fragment, payload, _, _) = next_message(&events);
+        assert!(matches!(fragment, Fragment::FragmentResultChunk(chunk) if chunk.last));
+        match payload {
+            Payload::Pixels(received, Ok(pixels)) => {
+                assert_eq!(received, id);
+                assert_eq!(pixels.len(), 16);
+            }
+            other => panic!("expected the pixels of the result, got {:?}", other),
+        }
+        (current, id) = task(round);
+        answer(&sender, Fragment::FragmentTask(current), id.clone());
+    }
+
+    // the last task is answered with no more work, the session is still open
+    assert!(matches!(
+        read_message(&mut client).unwrap().0,
+        Fragment::FragmentTask(_)
+    ));
+    send_result(&mut client, &current, &id);
+    let (sender, _, _, _, _) = next_message(&events);
+    answer(
+        &sender,
+        Fragment::FragmentNoWork(FragmentNoWork::new(1)),
+        Vec::new(),
+    );
+    assert!(matches!(
+        read_message(&mut client).unwrap().0,
+        Fragment::FragmentNoWork(_)
+    ));
+    assert!(matches!(session.try_recv(), Err(mpsc::TryRecvError::Empty)));
+
+    drop(client);
+    assert!(session_ended(&session));
+}
+
+#[test]
+fn test_idle_session_is_closed() {
+    let (mut client, events, session) = start_session(settings(None, Duration::from_millis(200)));
+
+    send_request(&mut client);
+    let (sender, _, _, _, _) = next_message(&events);
+    let (task, id) = task(0);
+    answer(&sender, Fragment::FragmentTask(task), id);
+    assert!(matches!(
+        read_message(&mut client).unwrap().0,
+        Fragment::FragmentTask(_)
+    ));
+
+    // the worker goes quiet: the session closes the connection
+    assert!(session_ended(&session));
+    assert!(matches!(
+        read_message(&mut client),
+        Err(err) if err.is_disconnection()
+    ));
+}
+
+#[test]
+fn test_session_ends_without_answer_from_the_server_thread() {
+    let (mut client, events, session) = start_session(settings(None, Duration::from_millis(200)));
+
+    send_request(&mut client);
+    // the server thread receives the request but never answers it
+    let _unanswered = next_message(&events);
+
+    assert!(session_ended(&session));
+    assert!(matches!(
+        read_message(&mut client),
+        Err(err) if err.is_disconnection()
+    ));
+}
+
+#[test]
+fn test_worker_proving_the_key_is_accepted() {
+    let secret = b"shared key";
+    let (mut client, events, _session) = start_session(settings(Some(secret), TEST_TIMEOUT));
+
+    let nonce = open_challenge(&mut client);
+    send_proof(&mut client, &compute_proof(secret, &nonce, "test_worker"));
+    assert!(welcomed(&mut client));
+
+    send_request(&mut client);
+    let (sender, fragment, _, _, _) = next_message(&events);
+    assert!(matches!(fragment, Fragment::FragmentRequest(_)));
+    let (task, id) = task(0);
+    answer(&sender, Fragment::FragmentTask(task), id);
+    assert!(matches!(
+        read_message(&mut client).unwrap().0,
+        Fragment::FragmentTask(_)
+    ));
+}
+
+#[test]
+fn test_wrong_proof_is_refused() {
+    let secret = b"shared key";
+    let (mut client, events, session) = start_session(settings(Some(secret), TEST_TIMEOUT));
+
+    let nonce = open_challenge(&mut client);
+    send_proof(
+        &mut client,
+        &compute_proof(b"another key", &nonce, "test_worker"),
+    );
+    assert!(!welcomed(&mut client));
+    assert!(ended_unheard(&events, &session));
+}
+
+#[test]
+fn test_proof_for_another_nonce_is_refused() {
+    let secret = b"shared key";
+    let (mut client, events, session) = start_session(settings(Some(secret), TEST_TIMEOUT));
+
+    let nonce = open_challenge(&mut client);
+    let replayed: Vec<u8> = nonce.iter().map(|byte| byte ^ 0xff).collect();
+    send_proof(
+        &mut client,
+        &compute_proof(secret, &replayed, "test_worker"),
+    );
+    assert!(!welcomed(&mut client));
+    assert!(ended_unheard(&events, &session));
+}
+
+#[test]
+fn test_legacy_worker_is_refused_when_a_key_is_set() {
+    let (mut client, events, session) = start_session(settings(Some(b"shared key"), TEST_TIMEOUT));
+
+    send_request(&mut client);
+    match read_message(&mut client).unwrap() {
+        (Fragment::FragmentError(error), _) => assert_eq!(error.code, ErrorCode::Unauthenticated),
+        other => panic!("expected an error, got {:?}", other),
+    }
+    assert!(ended_unheard(&events, &session));
+}
+
+#[test]
+fn test_no_task_is_handed_before_the_challenge_is_answered() {
+    let (mut client, events, session) = start_session(settings(Some(b"shared key"), TEST_TIMEOUT));
+
+    // the worker skips the challenge and asks for work right away
+    open_challenge(&mut client);
+    send_request(&mut client);
+    assert!(!welcomed(&mut client));
+    assert!(ended_unheard(&events, &session));
+}
@@ -0,0 +1,243 @@
+//! # Capture Files
+//!
+//! A capture file records the frames exchanged between workers and a server, so a session can be
+//! read after the fact or replayed to reproduce a bug. The file starts with `CAPTURE_MAGIC`, then
+//! every frame is stored as a record:
+//!
+//! | Field             | Size                              |
+//! |-------------------|-----------------------------------|
+//! | session           | `u32` big-endian                  |
+//! | direction         | `u8`, 0 to the server, 1 to the worker |
+//! | timestamp         | `u64` big-endian, µs since the start of the capture |
+//! | frame             | the frame as sent on the wire     |
+//!
+//! The frames are kept as they are, header included: a session switching to CBOR headers after its
+//! handshake is recorded faithfully, and `CapturedMessage::fragment` decodes either encoding.
+//!
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use shared_lib::capture::capture_file::{CaptureReader, CaptureWriter, Direction};
+//!
+//! let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+//! writer
+//!     .record(0, Direction::ToServer, br#"{"FragmentRequest":{"worker_name":"w","maximal_work_load":1}}"#, &[])
+//!     .unwrap();
+//!
+//! let mut reader = CaptureReader::new(Cursor::new(writer.into_inner())).unwrap();
+//! let message = reader.next_message().unwrap().unwrap();
+//! assert_eq!(message.direction, Direction::ToServer);
+//! assert!(reader.next_message().unwrap().is_none());
+//! ```
+
+use std::{
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    errors::frakt_error::FraktError,
+    messages::{handshake::HeaderEncoding, message::Fragment},
+    messages_methods::{
+        frame::FrameLimits,
+        messages_methods::{decode_fragment, read_frame_with_limits},
+    },
+};
+
+/// Bytes opening every capture file
+pub const CAPTURE_MAGIC: &[u8; 8] = b"FRAKTCAP";
+
+/// Which way a captured frame went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the worker to the server
+    ToServer,
+    /// Sent by the server to the worker
+    ToWorker,
+}
+
+/// A frame read from a capture file:
+/// * session: the number of the connection the frame was sent on, in the order of the connections
+/// * direction: which way the frame went
+/// * timestamp: when the frame was captured, since the start of the capture
+/// * header: the `Fragment` header, as encoded on the wire
+/// * data: the data following the header
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedMessage {
+    pub session: u32,
+    pub direction: Direction,
+    pub timestamp: Duration,
+    pub header: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+impl CapturedMessage {
+    /// to know how the header of the frame is encoded
+    /// * Return: `HeaderEncoding` - the encoding of the header
+    pub fn header_encoding(&self) -> HeaderEncoding {
+        header_encoding(&self.header)
+    }
+
+    /// to decode the header of the frame
+    /// * Return: the `Fragment`, or a `FraktError::Deserialization` if the header is not valid
+    pub fn fragment(&self) -> Result<Fragment, FraktError> {
+        decode_header(&self.header)
+    }
+
+    /// to build the frame again, as it was sent on the wire
+    /// * Return: `Vec<u8>` - the sizes, the header and the data
+    pub fn frame(&self) -> Vec<u8> {
+        encode_frame(&self.header, &self.data)
+    }
+}
+
+/// to know how a raw header is encoded: JSON headers are objects, anything else is CBOR
+/// * `header` - the `Fragment` header, as encoded on the wire
+/// * Return: `HeaderEncoding` - the encoding of the header
+pub fn header_encoding(header: &[u8]) -> HeaderEncoding {
+    match header.first() {
+        Some(b'{') => HeaderEncoding::Json,
+        _ => HeaderEncoding::Cbor,
+    }
+}
+
+/// to decode a raw header, whatever its encoding
+/// * `header` - the `Fragment` header, as encoded on the wire
+/// * Return: the `Fragment`, or a `FraktError::Deserialization` if the header is not valid
+pub fn decode_header(header: &[u8]) -> Result<Fragment, FraktError> {
+    decode_fragment(header, header_encoding(header))
+}
+
+/// to build a frame from a raw header and its data
+/// * `header` - the `Fragment` header, already encoded
+/// * `data` - the data following the header
+/// * Return: `Vec<u8>` - the sizes, the header and the data
+pub fn encode_frame(header: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(8 + header.len() + data.len());
+    frame.extend_from_slice(&((header.len() + data.len()) as u32).to_be_bytes());
+    frame.extend_from_slice(&(header.len() as u32).to_be_bytes());
+    frame.extend_from_slice(header);
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Writes the records of a capture file
+pub struct CaptureWriter<W: Write> {
+    writer: W,
+    start: Instant,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// to start a capture file, the timestamps of the records are counted from now
+    /// * `writer` - where the capture is written
+    /// * Return: the writer, or an error if the magic bytes cannot be written
+    pub fn new(mut writer: W) -> Result<CaptureWriter<W>, FraktError> {
+        writer.write_all(CAPTURE_MAGIC)?;
+        Ok(CaptureWriter {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    /// to record a frame, written at once and flushed so the capture stays readable if the
+    /// recorder is killed
+    /// * `session` - the number of the connection the frame was sent on
+    /// * `direction` - which way the frame went
+    /// * `header` - the `Fragment` header, as encoded on the wire
+    /// * `data` - the data following the header
+    pub fn record(
+        &mut self,
+        session: u32,
+        direction: Direction,
+        header: &[u8],
+        data: &[u8],
+    ) -> Result<(), FraktError> {
+        let direction = match direction {
+            Direction::ToServer => 0_u8,
+            Direction::ToWorker => 1_u8,
+        };
+        let timestamp = self.start.elapsed().as_micros() as u64;
+
+        let mut record = Vec::with_capacity(13);
+        record.extend_from_slice(&session.to_be_bytes());
+        record.push(direction);
+        record.extend_from_slice(&timestamp.to_be_bytes());
+        record.extend(encode_frame(header, data));
+        self.writer.write_all(&record)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// to get back the underlying writer
+    /// * Return: `W` - the writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records of a capture file
+pub struct CaptureReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// to open a capture file
+    /// * `reader` - where the capture is read from
+    /// * Return: the reader, or a `FraktError::Validation` if it is not a capture file
+    pub fn new(mut reader: R) -> Result<CaptureReader<R>, FraktError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != CAPTURE_MAGIC {
+            return Err(FraktError::Validation(String::from(
+                "Not a capture file, the magic bytes are missing",
+            )));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    /// to read the next record of the capture
+    /// * Return: the captured frame, None at the end of the capture, or an error if the record is truncated or invalid
+    pub fn next_message(&mut self) -> Result<Option<CapturedMessage>, FraktError> {
+        let mut session = [0; 4];
+        match self.reader.read_exact(&mut session) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(FraktError::Transport(err)),
+        }
+        let mut direction = [0; 1];
+        self.reader.read_exact(&mut direction)?;
+        let direction = match direction[0] {
+            0 => Direction::ToServer,
+            1 => Direction::ToWorker,
+            other => {
+                return Err(FraktError::Validation(format!(
+                    "Unknown direction {} in the capture",
+                    other
+                )))
+            }
+        };
+        let mut timestamp = [0; 8];
+        self.reader.read_exact(&mut timestamp)?;
+
+        // the capture was written by a trusted recorder: its frames are not limited in size
+        let (header, data) = read_frame_with_limits(&mut self.reader, &FrameLimits::unlimited())?;
+
+        Ok(Some(CapturedMessage {
+            session: u32::from_be_bytes(session),
+            direction,
+            timestamp: Duration::from_micros(u64::from_be_bytes(timestamp)),
+            header,
+            data,
+        }))
+    }
+
+    /// to read all the records left in the capture
+    /// * Return: the captured frames, in the order they were recorded
+    pub fn read_all(&mut self) -> Result<Vec<CapturedMessage>, FraktError> {
+        let mut messages = Vec::new();
+        while let Some(message) = self.next_message()? {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+}
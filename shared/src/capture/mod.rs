@@ -0,0 +1,2 @@
+pub mod capture_file;
+mod test_capture_file;
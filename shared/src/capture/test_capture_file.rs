@@ -0,0 +1,73 @@
+#[cfg(test)]
+use std::io::Cursor;
+
+#[cfg(test)]
+use crate::capture::capture_file::{CaptureReader, CaptureWriter, Direction, CAPTURE_MAGIC};
+#[cfg(test)]
+use crate::errors::frakt_error::FraktError;
+#[cfg(test)]
+use crate::messages::fragment_method_cbor::fragment_to_cbor;
+#[cfg(test)]
+use crate::messages::fragment_method_json::fragment_to_string;
+#[cfg(test)]
+use crate::messages::handshake::HeaderEncoding;
+#[cfg(test)]
+use crate::messages::message::{Fragment, FragmentNoWork, FragmentRequest};
+
+#[test]
+fn test_capture_round_trip_keeps_frames_and_order() {
+    let request = Fragment::FragmentRequest(FragmentRequest::new(String::from("worker"), 4));
+    let no_work = Fragment::FragmentNoWork(FragmentNoWork::new(5));
+    let json = fragment_to_string(&request).unwrap().into_bytes();
+    let cbor = fragment_to_cbor(&no_work).unwrap();
+
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    writer
+        .record(0, Direction::ToServer, &json, &[1, 2, 3])
+        .unwrap();
+    writer.record(1, Direction::ToWorker, &cbor, &[]).unwrap();
+    let capture = writer.into_inner();
+    assert_eq!(&capture[..8], CAPTURE_MAGIC);
+
+    let messages = CaptureReader::new(Cursor::new(capture))
+        .unwrap()
+        .read_all()
+        .unwrap();
+    assert_eq!(messages.len(), 2);
+
+    assert_eq!(messages[0].session, 0);
+    assert_eq!(messages[0].direction, Direction::ToServer);
+    assert_eq!(messages[0].header_encoding(), HeaderEncoding::Json);
+    assert_eq!(messages[0].fragment().unwrap(), request);
+    assert_eq!(messages[0].data, vec![1, 2, 3]);
+
+    assert_eq!(messages[1].session, 1);
+    assert_eq!(messages[1].direction, Direction::ToWorker);
+    assert_eq!(messages[1].header_encoding(), HeaderEncoding::Cbor);
+    assert_eq!(messages[1].fragment().unwrap(), no_work);
+    assert!(messages[1].timestamp >= messages[0].timestamp);
+
+    // the frame is rebuilt as it was sent on the wire
+    let frame = messages[0].frame();
+    assert_eq!(frame.len(), 8 + json.len() + 3);
+    assert_eq!(&frame[4..8], &(json.len() as u32).to_be_bytes());
+}
+
+#[test]
+fn test_capture_refuses_other_files() {
+    let err = CaptureReader::new(Cursor::new(b"not a capture".to_vec())).err();
+    assert!(matches!(err, Some(FraktError::Validation(_))));
+}
+
+#[test]
+fn test_truncated_capture_is_an_error() {
+    let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+    writer
+        .record(0, Direction::ToServer, b"{}", &[0; 16])
+        .unwrap();
+    let mut capture = writer.into_inner();
+    capture.truncate(capture.len() - 4);
+
+    let mut reader = CaptureReader::new(Cursor::new(capture)).unwrap();
+    assert!(reader.next_message().is_err());
+}
@@ -0,0 +1,165 @@
+//! # Frakt Error
+//!
+//! `FraktError` is the error returned by the protocol functions of the library (message codec,
+//! payload encoding) and by the server and worker built on them. Each variant is a kind of failure
+//! a caller may want to react to differently: a lost connection is worth a reconnection, a
+//! malformed message or an unexpected `Fragment` means the peer is broken or hostile, a refusal
+//! means the configuration must change.
+//!
+//! `FraktError` converts from and into `io::Error`, so it mixes with the standard library.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use shared_lib::errors::frakt_error::FraktError;
+//! use shared_lib::messages_methods::messages_methods::read_message;
+//!
+//! match read_message(&mut Cursor::new(vec![0, 0, 0, 4, 0, 0, 0, 4, b'{', b'}', b'{', b'}'])) {
+//!     Ok((fragment, _)) => println!("Received {:?}", fragment),
+//!     Err(FraktError::Deserialization(reason)) => println!("Broken peer: {}", reason),
+//!     Err(err) if err.is_disconnection() => println!("Peer gone"),
+//!     Err(err) => println!("Error: {}", err),
+//! }
+//! ```
+
+use std::{error::Error, fmt, io};
+
+use crate::messages::message::{ErrorCode, FragmentError};
+use crate::messages_methods::frame::FrameError;
+
+/// Failures of the protocol
+#[derive(Debug)]
+pub enum FraktError {
+    /// The connection failed: closed, reset, timed out...
+    Transport(io::Error),
+    /// A frame was refused: truncated, too large or too slow
+    Frame(FrameError),
+    /// A `Fragment` or a payload cannot be serialized
+    Serialization(String),
+    /// A `Fragment` or a payload received cannot be deserialized
+    Deserialization(String),
+    /// The peer sent a `Fragment` which is not expected at this point of the exchange
+    UnexpectedFragment {
+        expected: &'static str,
+        received: String,
+    },
+    /// A message is well formed but its content is invalid
+    Validation(String),
+    /// The peer refused us, the reason is given by the peer
+    Refused(String),
+    /// The server rejected a message with a `FragmentError`
+    Rejected { code: ErrorCode, message: String },
+}
+
+impl FraktError {
+    /// to build the error of an unexpected `Fragment`
+    /// * `expected` - what was expected, for example "FragmentTask"
+    /// * `received` - the `Fragment` received
+    /// * Return: `FraktError::UnexpectedFragment`
+    pub fn unexpected<T: fmt::Debug>(expected: &'static str, received: &T) -> FraktError {
+        let received = format!("{:?}", received);
+        // keep the name of the variant only, the content may be large
+        let received = match received.find('(') {
+            Some(end) => received[..end].to_string(),
+            None => received,
+        };
+        FraktError::UnexpectedFragment { expected, received }
+    }
+
+    /// to know if the peer is gone: the connection was closed or reset
+    /// * Return: `bool` - true if a new connection is needed
+    pub fn is_disconnection(&self) -> bool {
+        match self {
+            FraktError::Transport(err) => matches!(
+                err.kind(),
+                io::ErrorKind::UnexpectedEof
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+            ),
+            _ => false,
+        }
+    }
+
+    /// to know if the connection timed out while waiting for the peer
+    /// * Return: `bool` - true if nothing was received in time
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            FraktError::Transport(err) => matches!(
+                err.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            ),
+            FraktError::Frame(FrameError::Timeout(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for FraktError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FraktError::Transport(err) => write!(f, "Transport error: {}", err),
+            FraktError::Frame(err) => write!(f, "{}", err),
+            FraktError::Serialization(reason) => write!(f, "Cannot serialize: {}", reason),
+            FraktError::Deserialization(reason) => write!(f, "Cannot deserialize: {}", reason),
+            FraktError::UnexpectedFragment { expected, received } => write!(
+                f,
+                "Unexpected fragment: {} expected, {} received",
+                expected, received
+            ),
+            FraktError::Validation(reason) => write!(f, "Invalid message: {}", reason),
+            FraktError::Refused(reason) => write!(f, "Refused by peer: {}", reason),
+            FraktError::Rejected { code, message } => {
+                write!(f, "Rejected by server ({:?}): {}", code, message)
+            }
+        }
+    }
+}
+
+impl Error for FraktError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FraktError::Transport(err) => Some(err),
+            FraktError::Frame(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FragmentError> for FraktError {
+    fn from(err: FragmentError) -> FraktError {
+        FraktError::Rejected {
+            code: err.code,
+            message: err.message,
+        }
+    }
+}
+
+impl From<io::Error> for FraktError {
+    fn from(err: io::Error) -> FraktError {
+        FraktError::Transport(err)
+    }
+}
+
+impl From<FrameError> for FraktError {
+    fn from(err: FrameError) -> FraktError {
+        FraktError::Frame(err)
+    }
+}
+
+impl From<FraktError> for io::Error {
+    fn from(err: FraktError) -> io::Error {
+        match err {
+            FraktError::Transport(err) => err,
+            FraktError::Frame(err) => err.into(),
+            FraktError::Refused(_)
+            | FraktError::Rejected {
+                code: ErrorCode::Unauthenticated,
+                ..
+            } => io::Error::new(io::ErrorKind::PermissionDenied, err),
+            _ => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
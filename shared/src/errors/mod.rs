@@ -0,0 +1,2 @@
+pub mod frakt_error;
+mod test_frakt_error;
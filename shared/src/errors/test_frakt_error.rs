@@ -0,0 +1,74 @@
+#[cfg(test)]
+use std::io;
+
+#[cfg(test)]
+use crate::errors::frakt_error::FraktError;
+#[cfg(test)]
+use crate::messages::handshake::HeaderEncoding;
+#[cfg(test)]
+use crate::messages::message::{ErrorCode, Fragment, FragmentError, FragmentRequest};
+#[cfg(test)]
+use crate::messages_methods::frame::FrameError;
+#[cfg(test)]
+use crate::messages_methods::messages_methods::{read_message_with, send_message_with};
+#[cfg(test)]
+use std::io::Cursor;
+
+#[test]
+fn test_unexpected_fragment_keeps_the_variant_name() {
+    let fragment = Fragment::FragmentRequest(FragmentRequest::new(String::from("worker"), 10));
+    match FraktError::unexpected("FragmentTask", &fragment) {
+        FraktError::UnexpectedFragment { expected, received } => {
+            assert_eq!(expected, "FragmentTask");
+            assert_eq!(received, "FragmentRequest");
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn test_io_conversions_keep_the_kind() {
+    let closed = FraktError::from(io::Error::from(io::ErrorKind::UnexpectedEof));
+    assert!(closed.is_disconnection());
+    assert!(!closed.is_timeout());
+
+    let timeout = FraktError::from(FrameError::Timeout(std::time::Duration::from_secs(1)));
+    assert!(timeout.is_timeout());
+    let err = io::Error::from(timeout);
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(matches!(
+        FrameError::from_io(&err),
+        Some(FrameError::Timeout(_))
+    ));
+
+    let refused = io::Error::from(FraktError::Refused(String::from("bad key")));
+    assert_eq!(refused.kind(), io::ErrorKind::PermissionDenied);
+}
+
+#[test]
+fn test_fragment_error_becomes_a_rejection() {
+    let error = FragmentError::new(
+        ErrorCode::Unauthenticated,
+        String::from("Authentication is required"),
+    );
+    for encoding in [HeaderEncoding::Json, HeaderEncoding::Cbor] {
+        let mut buffer = Vec::new();
+        let fragment = Fragment::FragmentError(error.clone());
+        send_message_with(&mut buffer, fragment, &[], encoding).unwrap();
+        let (fragment, _) = read_message_with(&mut Cursor::new(buffer), encoding).unwrap();
+        assert_eq!(fragment, Fragment::FragmentError(error.clone()));
+    }
+
+    let rejected = FraktError::from(error);
+    assert!(matches!(
+        rejected,
+        FraktError::Rejected {
+            code: ErrorCode::Unauthenticated,
+            ..
+        }
+    ));
+    assert_eq!(
+        io::Error::from(rejected).kind(),
+        io::ErrorKind::PermissionDenied
+    );
+}
@@ -0,0 +1,237 @@
+//! # Assembler
+//!
+//! Builds the image of a whole fractal from the results of its tiles.
+//!
+//! The image covers a viewport, the part of the complex plane rendered (`Range`), with a
+//! resolution that may be of any size and not square. The place of a tile in the image is found
+//! from its own `Range` and `Resolution` relative to the viewport: its pixels must have the size of
+//! the pixels of the image, and its corner must fall on a pixel of the image. A tile may be painted
+//! at once or in bands of rows, as the worker sends them.
+//!
+//! The pixels are colored with the `Palette` of the image.
+//!
+//! The assembler remembers the pixels painted, so the image is known to be complete when every
+//! pixel of the image was painted, whatever the number and the size of the tiles.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use shared_lib::complementary_types::{point::Point, range::Range, resolution::Resolution};
+//! use shared_lib::fractal_implementation::{
+//!     assembler::ImageAssembler, fractal::FractalDescriptor, palette::Palette, tiling::TilePlan,
+//! };
+//! use shared_lib::fractal_types::mandelbrot::Mandelbrot;
+//!
+//! let plan = TilePlan::new(
+//!     FractalDescriptor::Mandelbrot(Mandelbrot {}),
+//!     Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+//!     Resolution::new(30, 20),
+//!     Resolution::new(16, 16),
+//!     64,
+//! );
+//! let mut assembler = ImageAssembler::for_plan(&plan, Palette::Classic);
+//! for task in plan.tasks().unwrap() {
+//!     assert!(!assembler.is_complete());
+//!     let pixels = FractalDescriptor::get_datas(&task);
+//!     assembler.paint(&task, 0, &pixels).unwrap();
+//! }
+//! assert!(assembler.is_complete());
+//! assert_eq!(assembler.image().dimensions(), (30, 20));
+//! ```
+
+use image::{ImageBuffer, Rgb};
+
+use crate::{
+    complementary_types::{pixelintensity::PixelIntensity, range::Range, resolution::Resolution},
+    errors::frakt_error::FraktError,
+    messages::message::FragmentTask,
+};
+
+use super::{fractal::FractalDescriptor, palette::Palette, tiling::TilePlan};
+
+/// The relative error allowed between the size of the pixels of a tile and of the image
+const STEP_TOLERANCE: f64 = 1e-6;
+
+/// The distance allowed between the corner of a tile and a pixel of the image, in pixels
+const ORIGIN_TOLERANCE: f64 = 1e-3;
+
+/// The image of a fractal being assembled:
+/// * viewport: the part of the complex plane covered by the image
+/// * resolution: the size of the image in pixels
+/// * palette: the colors of the pixels
+/// * image: the pixels colored so far
+/// * painted: whether each pixel of the image was painted, row after row
+/// * remaining: the number of pixels not painted yet
+#[derive(Debug, Clone)]
+pub struct ImageAssembler {
+    viewport: Range,
+    resolution: Resolution,
+    palette: Palette,
+    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    painted: Vec<bool>,
+    remaining: usize,
+}
+
+impl ImageAssembler {
+    pub fn new(viewport: Range, resolution: Resolution, palette: Palette) -> ImageAssembler {
+        let pixels = resolution.nx as usize * resolution.ny as usize;
+        ImageAssembler {
+            viewport,
+            resolution,
+            palette,
+            image: ImageBuffer::new(resolution.nx as u32, resolution.ny as u32),
+            painted: vec![false; pixels],
+            remaining: pixels,
+        }
+    }
+
+    /// to create the assembler of the image cut into tiles by a plan
+    /// * `plan` - the plan of the image
+    /// * `palette` - the colors of the pixels
+    /// * Return: `ImageAssembler` - an assembler of the viewport and resolution of the plan, nothing painted yet
+    pub fn for_plan(plan: &TilePlan, palette: Palette) -> ImageAssembler {
+        ImageAssembler::new(plan.range, plan.resolution, palette)
+    }
+
+    /// to find the place of a tile in the image
+    /// * `task` - the task of the tile
+    /// * Return: `(u32, u32)` - the column and the row of the top left pixel of the tile, or a `FraktError::Validation` if the tile does not fit in the pixels of the image
+    pub fn tile_origin(&self, task: &FragmentTask) -> Result<(u32, u32), FraktError> {
+        let x = axis_origin(
+            self.viewport.min.x,
+            self.viewport.max.x,
+            self.resolution.nx,
+            task.range.min.x,
+            task.range.max.x,
+            task.resolution.nx,
+        );
+        let y = axis_origin(
+            self.viewport.min.y,
+            self.viewport.max.y,
+            self.resolution.ny,
+            task.range.min.y,
+            task.range.max.y,
+            task.resolution.ny,
+        );
+        match (x, y) {
+            (Some(x), Some(y)) => Ok((x, y)),
+            _ => Err(FraktError::Validation(format!(
+                "The tile {}x{} over ({}, {})..({}, {}) does not fit in the pixels of the image",
+                task.resolution.nx,
+                task.resolution.ny,
+                task.range.min.x,
+                task.range.min.y,
+                task.range.max.x,
+                task.range.max.y
+            ))),
+        }
+    }
+
+    /// to color the pixels of a tile in the image
+    /// * `task` - the task of the tile
+    /// * `first_pixel` - the index in the task of the first pixel of `pixels`, 0 for a whole result
+    /// * `pixels` - the pixels computed, row after row
+    /// * Return: `()` once painted, or a `FraktError::Validation` if the tile does not fit in the image or the pixels go past the end of the tile
+    pub fn paint(
+        &mut self,
+        task: &FragmentTask,
+        first_pixel: u32,
+        pixels: &[PixelIntensity],
+    ) -> Result<(), FraktError> {
+        let (x_start, y_start) = self.tile_origin(task)?;
+        let width = task.resolution.nx as u32;
+        let total = width * task.resolution.ny as u32;
+        if first_pixel as u64 + pixels.len() as u64 > total as u64 {
+            return Err(FraktError::Validation(format!(
+                "Pixels {}..{} received for a tile of {} pixels",
+                first_pixel,
+                first_pixel as u64 + pixels.len() as u64,
+                total
+            )));
+        }
+
+        for (index, pixel) in (first_pixel..).zip(pixels) {
+            let x = x_start + index % width;
+            let y = y_start + index / width;
+            self.image
+                .put_pixel(x, y, Rgb(pixel_color(&task.fractal, pixel, self.palette)));
+            let painted = &mut self.painted[y as usize * self.resolution.nx as usize + x as usize];
+            if !*painted {
+                *painted = true;
+                self.remaining -= 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// to get the number of pixels of the image not painted yet
+    /// * Return: `usize` - the number of pixels missing, 0 once the image is complete
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// to know whether every pixel of the image was painted
+    /// * Return: `bool` - true once the image is complete
+    pub fn is_complete(&self) -> bool {
+        self.remaining == 0
+    }
+
+    /// to get the image assembled so far
+    /// * Return: `&ImageBuffer` - the image, black where nothing was painted yet
+    pub fn image(&self) -> &ImageBuffer<Rgb<u8>, Vec<u8>> {
+        &self.image
+    }
+
+    /// to take the image out of the assembler, once it is no longer painted
+    /// * Return: `ImageBuffer` - the image, black where nothing was painted
+    pub fn into_image(self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.image
+    }
+}
+
+/// to get the color of a pixel: Julia and Mandelbrot are colored by their last value, the other fractals by their number of iterations
+/// * `fractal` - the fractal computed
+/// * `pixel` - the pixel computed
+/// * `palette` - the colors of the pixels
+/// * Return: `[u8; 3]` - the color of the pixel
+pub fn pixel_color(
+    fractal: &FractalDescriptor,
+    pixel: &PixelIntensity,
+    palette: Palette,
+) -> [u8; 3] {
+    match fractal {
+        FractalDescriptor::Julia(_) | FractalDescriptor::Mandelbrot(_) => {
+            palette.color(pixel.zn as f64)
+        }
+        _ => palette.color(pixel.count as f64),
+    }
+}
+
+/// the first pixel of a tile along one axis of the image, if the pixels of the tile are pixels of the image
+fn axis_origin(
+    view_min: f64,
+    view_max: f64,
+    view_count: u16,
+    tile_min: f64,
+    tile_max: f64,
+    tile_count: u16,
+) -> Option<u32> {
+    if view_count == 0 || tile_count == 0 {
+        return None;
+    }
+    let step = (view_max - view_min) / view_count as f64;
+    let tile_step = (tile_max - tile_min) / tile_count as f64;
+    if !tile_step.is_finite() || (tile_step - step).abs() > step.abs() * STEP_TOLERANCE {
+        return None;
+    }
+    let origin = (tile_min - view_min) / step;
+    let start = origin.round();
+    if !origin.is_finite()
+        || (origin - start).abs() > ORIGIN_TOLERANCE
+        || start < 0.0
+        || start + tile_count as f64 > view_count as f64
+    {
+        return None;
+    }
+    Some(start as u32)
+}
@@ -1,9 +1,13 @@
 use std::fmt::Error;
 use std::fmt::{Display, Formatter};
 
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
+use complex_math::Complex;
+
 use crate::complementary_types::pixelintensity::PixelIntensity;
+use crate::fractal_implementation::progress::Progress;
 use crate::fractal_types::iterated_sin_z::IteratedSinZ;
 use crate::fractal_types::julia_descriptor::JuliaDescriptor;
 use crate::fractal_types::mandelbrot::Mandelbrot;
@@ -13,8 +17,22 @@ use crate::fractal_types::nova_newton_raphson_z_3::NovaNewtonRaphsonZ3;
 use crate::fractal_types::nova_newton_raphson_z_4::NovaNewtonRaphsonZ4;
 use crate::messages::message::FragmentTask;
 
-pub trait GetDatas {
-    fn get_datas(&self, task: &FragmentTask) -> Vec<PixelIntensity>;
+pub trait GetDatas: Sync {
+    /// Get the datas of a single pixel of the fractal
+    /// * `pixel_complexe` - The point of the complex plane at the pixel
+    /// * `max_iteration` - The maximal number of iterations
+    /// * Return: the zn and the count of the pixel
+    fn get_pixel(&self, pixel_complexe: Complex, max_iteration: u16) -> (f32, f32);
+
+    /// Get the datas of every pixel of a task
+    /// * `task` - The fragment task
+    /// * `progress` - The progress of the computation
+    /// * Return: a vector of PixelIntensity
+    fn get_datas(&self, task: &FragmentTask, progress: &Progress) -> Vec<PixelIntensity> {
+        FractalDescriptor::compute_pixels(task, progress, |pixel_complexe| {
+            self.get_pixel(pixel_complexe, task.max_iteration)
+        })
+    }
 }
 
 /// The fractal descriptor
@@ -30,28 +48,93 @@ pub enum FractalDescriptor {
 }
 
 impl FractalDescriptor {
+    /// Get the names of every fractal this library can compute, as displayed by `Display`
+    /// * Return: a vector of fractal names
+    pub fn supported_kinds() -> Vec<String> {
+        vec![
+            String::from("Julia"),
+            String::from("Mandelbrot"),
+            String::from("IteratedSinZ"),
+            String::from("NewtonRaphsonZ3"),
+            String::from("NewtonRaphsonZ4"),
+            String::from("NovaNewtonRaphsonZ3"),
+            String::from("NovaNewtonRaphsonZ4"),
+        ]
+    }
+
     /// Get the datas computed depending the fractal descriptor
     /// * `task` - The fragment task
     /// * Return: a vector of PixelIntensity
     pub fn get_datas(task: &FragmentTask) -> Vec<PixelIntensity> {
+        FractalDescriptor::get_datas_with_progress(task, &Progress::for_task(task))
+    }
+
+    /// Get the datas computed depending the fractal descriptor, counting the pixels computed
+    /// * `task` - The fragment task
+    /// * `progress` - The progress of the computation, updated from every thread computing pixels and checked to skip the pixels of a cancelled task
+    /// * Return: a vector of PixelIntensity
+    pub fn get_datas_with_progress(
+        task: &FragmentTask,
+        progress: &Progress,
+    ) -> Vec<PixelIntensity> {
         return match &task.fractal {
-            FractalDescriptor::Julia(julia) => julia.get_datas(&task),
-            FractalDescriptor::Mandelbrot(mandelbrot) => mandelbrot.get_datas(&task),
-            FractalDescriptor::IteratedSinZ(iterated_sin_z) => iterated_sin_z.get_datas(&task),
+            FractalDescriptor::Julia(julia) => julia.get_datas(task, progress),
+            FractalDescriptor::Mandelbrot(mandelbrot) => mandelbrot.get_datas(task, progress),
+            FractalDescriptor::IteratedSinZ(iterated_sin_z) => {
+                iterated_sin_z.get_datas(task, progress)
+            }
             FractalDescriptor::NewtonRaphsonZ3(newton_raphson_z_3) => {
-                newton_raphson_z_3.get_datas(&task)
+                newton_raphson_z_3.get_datas(task, progress)
             }
             FractalDescriptor::NewtonRaphsonZ4(newton_raphson_z_4) => {
-                newton_raphson_z_4.get_datas(&task)
+                newton_raphson_z_4.get_datas(task, progress)
             }
             FractalDescriptor::NovaNewtonRaphsonZ3(nova_newton_raphson_z_3) => {
-                nova_newton_raphson_z_3.get_datas(&task)
+                nova_newton_raphson_z_3.get_datas(task, progress)
             }
             FractalDescriptor::NovaNewtonRaphsonZ4(nova_newton_raphson_z_4) => {
-                nova_newton_raphson_z_4.get_datas(&task)
+                nova_newton_raphson_z_4.get_datas(task, progress)
             }
         };
     }
+
+    /// Compute every pixel of a task in parallel, skipping the remaining ones once the task is cancelled
+    /// * `task` - The fragment task
+    /// * `progress` - The progress of the computation, counting the pixels computed
+    /// * `get_pixel` - The function computing the zn and the count of a point of the complex plane
+    /// * Return: a vector of PixelIntensity, the pixels skipped left at zero
+    pub fn compute_pixels<F>(
+        task: &FragmentTask,
+        progress: &Progress,
+        get_pixel: F,
+    ) -> Vec<PixelIntensity>
+    where
+        F: Fn(Complex) -> (f32, f32) + Sync,
+    {
+        let x_start = task.range.min.x;
+        let x_end = task.range.max.x;
+        let y_start = task.range.min.y;
+        let y_end = task.range.max.y;
+
+        let number_of_pixels = task.resolution.nx as u32 * task.resolution.ny as u32;
+
+        let x_step = (x_end - x_start) / task.resolution.nx as f64;
+        let y_step = (y_end - y_start) / task.resolution.ny as f64;
+
+        (0..number_of_pixels)
+            .into_par_iter() // Utilisation de rayon pour le traitement parallèle
+            .map(|i| {
+                if progress.is_cancelled() {
+                    return PixelIntensity::new(0.0, 0.0);
+                }
+                let x = x_start + (i % task.resolution.nx as u32) as f64 * x_step;
+                let y = y_start + (i / task.resolution.nx as u32) as f64 * y_step;
+                let fractal_result = get_pixel(Complex::new(x, y));
+                progress.pixel_done();
+                PixelIntensity::new(fractal_result.0, fractal_result.1)
+            })
+            .collect()
+    }
 }
 
 impl Display for FractalDescriptor {
@@ -1,2 +1,9 @@
+pub mod assembler;
 pub mod fractal;
-pub mod fractal_calcul;
\ No newline at end of file
+pub mod fractal_calcul;
+pub mod palette;
+pub mod progress;
+mod test_assembler;
+mod test_progress;
+mod test_tiling;
+pub mod tiling;
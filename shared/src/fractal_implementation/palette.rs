@@ -0,0 +1,37 @@
+//! # Palette
+//!
+//! The palettes turning the value computed for a pixel into a color. The value is the last value
+//! of the sequence for Julia and Mandelbrot, and the number of iterations for the other fractals,
+//! both between 0 and 1.
+
+use serde::{Deserialize, Serialize};
+
+use super::fractal_calcul::color;
+
+/// The palettes of the images:
+/// * Classic: a smooth gradient from blue to orange, the colors of the server images
+/// * Banded: the classic gradient repeated twice, the colors of the worker images
+/// * Grayscale: black for 0 to white for 1
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    #[default]
+    Classic,
+    Banded,
+    Grayscale,
+}
+
+impl Palette {
+    /// to get the color of a value
+    /// * `t` - the value computed for a pixel, between 0 and 1
+    /// * Return: `[u8; 3]` - the color of the value
+    pub fn color(&self, t: f64) -> [u8; 3] {
+        match self {
+            Palette::Classic => color(t),
+            Palette::Banded => color((2.0 * t + 0.5) % 1.0),
+            Palette::Grayscale => {
+                let level = (t.clamp(0.0, 1.0) * 255.0).round() as u8;
+                [level, level, level]
+            }
+        }
+    }
+}
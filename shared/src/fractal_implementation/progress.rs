@@ -0,0 +1,67 @@
+//! # Progress
+//!
+//! Counts the pixels of a task already computed. The pixels are computed in parallel by rayon, so
+//! the counter is shared between the threads: each of them reports its pixels, and the worker
+//! reads the percentage from another thread to tell the server it is still alive.
+//!
+//! The computation can also be cancelled from another thread: the threads computing pixels check
+//! the progress before each pixel and skip the remaining ones, so a cancelled task stops within
+//! milliseconds.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use crate::messages::message::FragmentTask;
+
+/// Progress of the computation of a task:
+/// * done: number of pixels computed
+/// * total: number of pixels of the task
+/// * cancelled: whether the remaining pixels must be skipped
+#[derive(Debug)]
+pub struct Progress {
+    done: AtomicU32,
+    total: u32,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn new(total: u32) -> Progress {
+        Progress {
+            done: AtomicU32::new(0),
+            total,
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// to create the progress of a task, nothing computed yet
+    /// * `task` - the task to compute
+    /// * Return: `Progress` - a progress counting every pixel of the task
+    pub fn for_task(task: &FragmentTask) -> Progress {
+        Progress::new(task.resolution.nx as u32 * task.resolution.ny as u32)
+    }
+
+    /// to report a pixel computed, from any thread
+    pub fn pixel_done(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// to get the share of the pixels already computed
+    /// * Return: `u8` - a percentage between 0 and 100, 100 for a task without pixels
+    pub fn percentage(&self) -> u8 {
+        if self.total == 0 {
+            return 100;
+        }
+        let done = self.done.load(Ordering::Relaxed).min(self.total);
+        (done as u64 * 100 / self.total as u64) as u8
+    }
+
+    /// to cancel the computation, from any thread: the pixels not computed yet are skipped
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// to know if the computation was cancelled, the pixels computed are then meaningless
+    /// * Return: `bool` - true once `cancel` was called
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,137 @@
+#[cfg(test)]
+use image::Rgb;
+
+#[cfg(test)]
+use crate::complementary_types::{
+    pixelintensity::PixelIntensity, point::Point, range::Range, resolution::Resolution,
+};
+#[cfg(test)]
+use crate::errors::frakt_error::FraktError;
+#[cfg(test)]
+use crate::fractal_implementation::{
+    assembler::{pixel_color, ImageAssembler},
+    fractal::FractalDescriptor,
+    palette::Palette,
+    tiling::TilePlan,
+};
+#[cfg(test)]
+use crate::fractal_types::mandelbrot::Mandelbrot;
+
+#[cfg(test)]
+fn plan(nx: u16, ny: u16, tile: u16) -> TilePlan {
+    TilePlan::new(
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+        Resolution::new(nx, ny),
+        Resolution::new(tile, tile),
+        64,
+    )
+}
+
+#[cfg(test)]
+fn canned(count: usize, zn: f32) -> Vec<PixelIntensity> {
+    vec![PixelIntensity::new(zn, 0.0); count]
+}
+
+#[test]
+fn test_complete_when_every_pixel_is_painted() {
+    let plan = plan(50, 30, 16);
+    let tasks = plan.tasks().unwrap();
+    let mut assembler = ImageAssembler::for_plan(&plan, Palette::Classic);
+    assert_eq!(assembler.remaining(), 50 * 30);
+
+    for task in &tasks[1..] {
+        let pixels = canned(
+            task.resolution.nx as usize * task.resolution.ny as usize,
+            0.1,
+        );
+        assembler.paint(task, 0, &pixels).unwrap();
+        // a tile painted twice is counted once
+        assembler.paint(task, 0, &pixels).unwrap();
+    }
+    assert!(!assembler.is_complete());
+    assert_eq!(assembler.remaining(), 16 * 16);
+
+    assembler
+        .paint(&tasks[0], 0, &canned(16 * 16, 0.1))
+        .unwrap();
+    assert!(assembler.is_complete());
+    assert_eq!(assembler.image().dimensions(), (50, 30));
+    assert_eq!(assembler.into_image().dimensions(), (50, 30));
+}
+
+#[test]
+fn test_tiles_are_placed_from_their_range() {
+    let plan = plan(50, 30, 16);
+    let tasks = plan.tasks().unwrap();
+    let mut assembler = ImageAssembler::for_plan(&plan, Palette::Classic);
+
+    // the last tile of the second row, narrower and lower than the others
+    let task = &tasks[7];
+    assert_eq!(task.resolution, Resolution::new(2, 14));
+    assert_eq!(assembler.tile_origin(task).unwrap(), (48, 16));
+
+    assembler.paint(task, 0, &canned(2 * 14, 0.3)).unwrap();
+    let painted = Rgb(pixel_color(
+        &task.fractal,
+        &PixelIntensity::new(0.3, 0.0),
+        Palette::Classic,
+    ));
+    assert_eq!(*assembler.image().get_pixel(48, 16), painted);
+    assert_eq!(*assembler.image().get_pixel(49, 29), painted);
+    assert_ne!(*assembler.image().get_pixel(47, 16), painted);
+}
+
+#[test]
+fn test_bands_are_painted_in_place() {
+    let plan = plan(20, 10, 20);
+    let task = plan.tasks().unwrap()[0];
+    let mut assembler = ImageAssembler::for_plan(&plan, Palette::Classic);
+
+    assembler.paint(&task, 0, &canned(30, 0.2)).unwrap();
+    assembler.paint(&task, 30, &canned(170, 0.7)).unwrap();
+    assert!(assembler.is_complete());
+
+    let first = Rgb(pixel_color(
+        &task.fractal,
+        &PixelIntensity::new(0.2, 0.0),
+        Palette::Classic,
+    ));
+    let second = Rgb(pixel_color(
+        &task.fractal,
+        &PixelIntensity::new(0.7, 0.0),
+        Palette::Classic,
+    ));
+    assert_eq!(*assembler.image().get_pixel(9, 1), first);
+    assert_eq!(*assembler.image().get_pixel(10, 1), second);
+
+    // a band may not go past the end of its tile
+    assert!(matches!(
+        assembler.paint(&task, 190, &canned(20, 0.2)),
+        Err(FraktError::Validation(_))
+    ));
+}
+
+#[test]
+fn test_foreign_tiles_are_refused() {
+    let plan = plan(40, 40, 10);
+    let assembler = ImageAssembler::for_plan(&plan, Palette::Classic);
+
+    // pixels of another size
+    let mut task = plan.tasks().unwrap()[0];
+    task.resolution = Resolution::new(20, 20);
+    assert!(assembler.tile_origin(&task).is_err());
+
+    // a corner between two pixels
+    let mut task = plan.tasks().unwrap()[0];
+    let shift = 0.5 * 3.0 / 40.0;
+    task.range.min.x += shift;
+    task.range.max.x += shift;
+    assert!(assembler.tile_origin(&task).is_err());
+
+    // a tile out of the viewport
+    let mut task = plan.tasks().unwrap()[3];
+    task.range.min.x += 3.0 / 4.0;
+    task.range.max.x += 3.0 / 4.0;
+    assert!(assembler.tile_origin(&task).is_err());
+}
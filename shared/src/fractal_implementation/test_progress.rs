@@ -0,0 +1,57 @@
+#[cfg(test)]
+use crate::complementary_types::{
+    point::Point, range::Range, resolution::Resolution, u8data::U8Data,
+};
+#[cfg(test)]
+use crate::fractal_implementation::{fractal::FractalDescriptor, progress::Progress};
+#[cfg(test)]
+use crate::fractal_types::mandelbrot::Mandelbrot;
+#[cfg(test)]
+use crate::messages::message::FragmentTask;
+
+#[test]
+fn test_progress_counts_every_pixel() {
+    let task = FragmentTask::new(
+        U8Data::new(0, 16),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(20, 10),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+    );
+    let progress = Progress::for_task(&task);
+    assert_eq!(progress.percentage(), 0);
+
+    let datas = FractalDescriptor::get_datas_with_progress(&task, &progress);
+    assert_eq!(datas.len(), 200);
+    assert_eq!(progress.percentage(), 100);
+}
+
+#[test]
+fn test_progress_percentage() {
+    let progress = Progress::new(3);
+    progress.pixel_done();
+    assert_eq!(progress.percentage(), 33);
+    progress.pixel_done();
+    progress.pixel_done();
+    progress.pixel_done();
+    assert_eq!(progress.percentage(), 100);
+    assert_eq!(Progress::new(0).percentage(), 100);
+}
+
+#[test]
+fn test_cancelled_task_skips_its_pixels() {
+    let task = FragmentTask::new(
+        U8Data::new(0, 16),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        u16::MAX,
+        Resolution::new(300, 300),
+        Range::new(Point::new(-0.5, -0.5), Point::new(0.0, 0.0)),
+    );
+    let progress = Progress::for_task(&task);
+    progress.cancel();
+    assert!(progress.is_cancelled());
+
+    let datas = FractalDescriptor::get_datas_with_progress(&task, &progress);
+    assert_eq!(datas.len(), 90000);
+    assert_eq!(progress.percentage(), 0);
+}
@@ -0,0 +1,92 @@
+#[cfg(test)]
+use crate::complementary_types::{point::Point, range::Range, resolution::Resolution};
+#[cfg(test)]
+use crate::errors::frakt_error::FraktError;
+#[cfg(test)]
+use crate::fractal_implementation::{fractal::FractalDescriptor, tiling::TilePlan};
+#[cfg(test)]
+use crate::fractal_types::mandelbrot::Mandelbrot;
+
+#[cfg(test)]
+fn plan(nx: u16, ny: u16, tile_nx: u16, tile_ny: u16) -> TilePlan {
+    TilePlan::new(
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+        Resolution::new(nx, ny),
+        Resolution::new(tile_nx, tile_ny),
+        64,
+    )
+}
+
+#[test]
+fn test_tiles_cover_every_pixel() {
+    let plan = plan(1000, 700, 300, 250);
+    assert_eq!(plan.tile_count(), (4, 3));
+
+    let tasks = plan.tasks().unwrap();
+    assert_eq!(tasks.len(), 12);
+    let pixels: u32 = tasks
+        .iter()
+        .map(|task| task.resolution.nx as u32 * task.resolution.ny as u32)
+        .sum();
+    assert_eq!(pixels, 1000 * 700);
+    for task in &tasks {
+        assert_eq!(task.max_iteration, 64);
+        assert_eq!(task.fractal, plan.fractal);
+    }
+}
+
+#[test]
+fn test_edge_tiles_are_smaller() {
+    let tasks = plan(1000, 700, 300, 250).tasks().unwrap();
+
+    // the tiles are listed row after row
+    assert_eq!(tasks[0].resolution, Resolution::new(300, 250));
+    assert_eq!(tasks[3].resolution, Resolution::new(100, 250));
+    assert_eq!(tasks[8].resolution, Resolution::new(300, 200));
+    assert_eq!(tasks[11].resolution, Resolution::new(100, 200));
+}
+
+#[test]
+fn test_tile_ranges_follow_pixels() {
+    let tasks = plan(1000, 700, 300, 250).tasks().unwrap();
+
+    // neighbouring tiles share their border, the corners of the image are the corners of the range
+    assert_eq!(tasks[0].range.min, Point::new(-2.0, -1.0));
+    assert_eq!(tasks[11].range.max, Point::new(1.0, 1.0));
+    assert_eq!(tasks[0].range.max.x, tasks[1].range.min.x);
+    assert_eq!(tasks[0].range.max.y, tasks[4].range.min.y);
+    for task in &tasks {
+        // every pixel of every tile has the same size
+        let width = (task.range.max.x - task.range.min.x) / task.resolution.nx as f64;
+        let height = (task.range.max.y - task.range.min.y) / task.resolution.ny as f64;
+        assert!((width - 3.0 / 1000.0).abs() < 1e-12);
+        assert!((height - 2.0 / 700.0).abs() < 1e-12);
+    }
+}
+
+#[test]
+fn test_single_tile_for_small_images() {
+    let tasks = plan(120, 80, 300, 300).tasks().unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].resolution, Resolution::new(120, 80));
+    assert_eq!(tasks[0].range, plan(120, 80, 300, 300).range);
+}
+
+#[test]
+fn test_invalid_plans_are_refused() {
+    assert!(matches!(
+        plan(0, 100, 10, 10).tasks(),
+        Err(FraktError::Validation(_))
+    ));
+    assert!(matches!(
+        plan(100, 100, 10, 0).tasks(),
+        Err(FraktError::Validation(_))
+    ));
+
+    let mut empty = plan(100, 100, 10, 10);
+    empty.range.max.x = empty.range.min.x;
+    assert!(empty.validate().is_err());
+    empty.range.max.x = f64::NAN;
+    assert!(empty.validate().is_err());
+}
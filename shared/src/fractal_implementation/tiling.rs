@@ -0,0 +1,151 @@
+//! # Tiling
+//!
+//! Cuts the image of a fractal into tiles, each tile being a `FragmentTask` a worker can compute.
+//!
+//! A `TilePlan` describes the whole image: the fractal, the part of the complex plane to render
+//! (`Range`), the resolution of the image and the largest tile to hand to a worker. The tiles are
+//! listed row after row, from the top left corner. When the resolution of the image is not a
+//! multiple of the tile size, the tiles of the last column and of the last row are narrower.
+//!
+//! The range of a tile is computed from its pixel boundaries in the image, so the tiles cover the
+//! range of the image without gap nor overlap, and a worker computes exactly the pixels the image
+//! would have computed as a whole.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use shared_lib::complementary_types::{point::Point, range::Range, resolution::Resolution};
+//! use shared_lib::fractal_implementation::{fractal::FractalDescriptor, tiling::TilePlan};
+//! use shared_lib::fractal_types::mandelbrot::Mandelbrot;
+//!
+//! let plan = TilePlan::new(
+//!     FractalDescriptor::Mandelbrot(Mandelbrot {}),
+//!     Range::new(Point::new(-2.0, -1.2), Point::new(1.0, 1.2)),
+//!     Resolution::new(1000, 800),
+//!     Resolution::new(300, 300),
+//!     64,
+//! );
+//! let tasks = plan.tasks().unwrap();
+//! assert_eq!(tasks.len(), 4 * 3);
+//! assert_eq!(tasks[3].resolution, Resolution::new(100, 300));
+//! ```
+
+use crate::{
+    complementary_types::{point::Point, range::Range, resolution::Resolution, u8data::U8Data},
+    errors::frakt_error::FraktError,
+    messages::message::FragmentTask,
+};
+
+use super::fractal::FractalDescriptor;
+
+/// The image of a fractal to cut into tiles:
+/// * fractal: the fractal to compute
+/// * range: the part of the complex plane covered by the image
+/// * resolution: the size of the image in pixels
+/// * tile_size: the size of the largest tile in pixels, the tiles of the edges may be smaller
+/// * max_iteration: the maximum number of iterations of every pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilePlan {
+    pub fractal: FractalDescriptor,
+    pub range: Range,
+    pub resolution: Resolution,
+    pub tile_size: Resolution,
+    pub max_iteration: u16,
+}
+
+impl TilePlan {
+    pub fn new(
+        fractal: FractalDescriptor,
+        range: Range,
+        resolution: Resolution,
+        tile_size: Resolution,
+        max_iteration: u16,
+    ) -> TilePlan {
+        TilePlan {
+            fractal,
+            range,
+            resolution,
+            tile_size,
+            max_iteration,
+        }
+    }
+
+    /// to check the plan can be cut into tiles
+    /// * Return: `()` if it can, a `FraktError::Validation` with the reason otherwise
+    pub fn validate(&self) -> Result<(), FraktError> {
+        if self.resolution.nx == 0 || self.resolution.ny == 0 {
+            return Err(FraktError::Validation(format!(
+                "The resolution {}x{} of the image has no pixel",
+                self.resolution.nx, self.resolution.ny
+            )));
+        }
+        if self.tile_size.nx == 0 || self.tile_size.ny == 0 {
+            return Err(FraktError::Validation(format!(
+                "The tile size {}x{} has no pixel",
+                self.tile_size.nx, self.tile_size.ny
+            )));
+        }
+        let Range { min, max } = self.range;
+        let finite = [min.x, min.y, max.x, max.y].iter().all(|v| v.is_finite());
+        if !finite || min.x >= max.x || min.y >= max.y {
+            return Err(FraktError::Validation(format!(
+                "The range ({}, {})..({}, {}) is empty",
+                min.x, min.y, max.x, max.y
+            )));
+        }
+        Ok(())
+    }
+
+    /// to get the number of tiles of the image
+    /// * Return: `(u32, u32)` - the number of columns and of rows of tiles
+    pub fn tile_count(&self) -> (u32, u32) {
+        (
+            (self.resolution.nx as u32).div_ceil(self.tile_size.nx as u32),
+            (self.resolution.ny as u32).div_ceil(self.tile_size.ny as u32),
+        )
+    }
+
+    /// to cut the image into tasks, row after row from the top left corner
+    /// * Return: `Vec<FragmentTask>` - one task per tile, their id still to be given, or a `FraktError::Validation` if the plan is invalid
+    pub fn tasks(&self) -> Result<Vec<FragmentTask>, FraktError> {
+        self.validate()?;
+        let (columns, rows) = self.tile_count();
+        let mut tasks = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            let y_start = row * self.tile_size.ny as u32;
+            let y_end = (y_start + self.tile_size.ny as u32).min(self.resolution.ny as u32);
+            for column in 0..columns {
+                let x_start = column * self.tile_size.nx as u32;
+                let x_end = (x_start + self.tile_size.nx as u32).min(self.resolution.nx as u32);
+                tasks.push(FragmentTask::new(
+                    U8Data::new(0, 16),
+                    self.fractal,
+                    self.max_iteration,
+                    Resolution::new((x_end - x_start) as u16, (y_end - y_start) as u16),
+                    Range::new(self.point_at(x_start, y_start), self.point_at(x_end, y_end)),
+                ));
+            }
+        }
+        Ok(tasks)
+    }
+
+    /// to get the point of the complex plane at a pixel boundary of the image
+    /// * `x` - the column of the boundary, between 0 and `resolution.nx`
+    /// * `y` - the row of the boundary, between 0 and `resolution.ny`
+    /// * Return: `Point` - the point, the corners of the range at the corners of the image
+    fn point_at(&self, x: u32, y: u32) -> Point {
+        let Range { min, max } = self.range;
+        Point::new(
+            lerp(min.x, max.x, x, self.resolution.nx),
+            lerp(min.y, max.y, y, self.resolution.ny),
+        )
+    }
+}
+
+/// the value at `position` pixels of `count`, exactly `min` and `max` at both ends
+fn lerp(min: f64, max: f64, position: u32, count: u16) -> f64 {
+    if position == count as u32 {
+        return max;
+    }
+    min + (max - min) * position as f64 / count as f64
+}
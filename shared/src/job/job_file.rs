@@ -0,0 +1,262 @@
+//! # Job File
+//!
+//! A job describes an image for the server to render: the fractal with all its parameters, the
+//! viewport (the part of the complex plane rendered), the resolution of the image, the number of
+//! iterations, the palette and the path of the image file. It is written in TOML or in JSON, the
+//! format is chosen from the extension of the file.
+//!
+//! A job is validated as a whole before the server cuts it into tasks, so a mistake is reported
+//! with the field at fault instead of producing a broken image. Unknown fields are refused, a
+//! typo is not silently ignored.
+//!
+//! ```toml
+//! max_iteration = 256
+//! palette = "Banded"
+//! output = "images/server/julia.png"
+//!
+//! [fractal.Julia]
+//! c = { re = -0.8, im = 0.156 }
+//! divergence_threshold_square = 4.0
+//!
+//! [viewport]
+//! min = { x = -1.6, y = -0.9 }
+//! max = { x = 1.6, y = 0.9 }
+//!
+//! [resolution]
+//! nx = 1600
+//! ny = 900
+//! ```
+//!
+//! `tile_size` (300x300 pixels by default), `palette` (`Classic` by default) and `priority` (1 by
+//! default) may be omitted. When several jobs are rendered together, a job of priority 3 receives
+//! three times as many workers as a job of priority 1.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use shared_lib::job::job_file::Job;
+//!
+//! let job = Job::from_json(
+//!     r#"{
+//!         "fractal": { "Mandelbrot": {} },
+//!         "viewport": { "min": { "x": -2.0, "y": -1.2 }, "max": { "x": 1.0, "y": 1.2 } },
+//!         "resolution": { "nx": 1000, "ny": 800 },
+//!         "max_iteration": 128,
+//!         "output": "images/server/mandelbrot.png"
+//!     }"#,
+//! )
+//! .unwrap();
+//! assert!(job.validate().is_ok());
+//! assert_eq!(job.plan().tasks().unwrap().len(), 4 * 3);
+//! ```
+
+use std::{error::Error, fmt, fs, path::Path};
+
+use complex_math::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    complementary_types::{range::Range, resolution::Resolution},
+    fractal_implementation::{fractal::FractalDescriptor, palette::Palette, tiling::TilePlan},
+};
+
+/// Size of the tiles of a job not giving its own
+pub const DEFAULT_TILE_SIZE: Resolution = Resolution { nx: 300, ny: 300 };
+
+/// An image to render:
+/// * fractal: the fractal to compute, with all its parameters
+/// * viewport: the part of the complex plane covered by the image
+/// * resolution: the size of the image in pixels, it may not be square
+/// * tile_size: the size of the largest task handed to a worker
+/// * max_iteration: the maximum number of iterations of every pixel
+/// * palette: the colors of the image
+/// * priority: the share of the workers given to the job when several jobs are rendered together, at least 1
+/// * output: the path of the image file, its extension gives the image format
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub fractal: FractalDescriptor,
+    pub viewport: Range,
+    pub resolution: Resolution,
+    #[serde(default = "default_tile_size")]
+    pub tile_size: Resolution,
+    pub max_iteration: u16,
+    #[serde(default)]
+    pub palette: Palette,
+    #[serde(default = "default_priority")]
+    pub priority: u32,
+    pub output: String,
+}
+
+/// Reasons why a job cannot be rendered
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobError {
+    /// The job file cannot be read
+    Read { path: String, reason: String },
+    /// The extension of the job file is neither .toml nor .json
+    UnknownFormat(String),
+    /// The job file is not valid TOML or JSON, or does not describe a job
+    Parse(String),
+    /// A field of the job has an invalid value
+    Invalid { field: &'static str, reason: String },
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Read { path, reason } => {
+                write!(f, "Cannot read the job file {}: {}", path, reason)
+            }
+            JobError::UnknownFormat(path) => write!(
+                f,
+                "Unknown format of the job file {}: use a .toml or .json file",
+                path
+            ),
+            JobError::Parse(reason) => write!(f, "Invalid job file: {}", reason),
+            JobError::Invalid { field, reason } => {
+                write!(f, "Invalid job, field `{}`: {}", field, reason)
+            }
+        }
+    }
+}
+
+impl Error for JobError {}
+
+impl Job {
+    /// to read a job file, in TOML or JSON depending on its extension, and validate the job
+    /// * `path` - the path of the job file, ending with .toml or .json
+    /// * Return: `Job` - the job, ready to be rendered, or a `JobError` telling what is wrong
+    pub fn load(path: &str) -> Result<Job, JobError> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let parse = match extension.as_deref() {
+            Some("toml") => Job::from_toml,
+            Some("json") => Job::from_json,
+            _ => return Err(JobError::UnknownFormat(path.to_string())),
+        };
+        let content = fs::read_to_string(path).map_err(|err| JobError::Read {
+            path: path.to_string(),
+            reason: err.to_string(),
+        })?;
+        let job = parse(&content)?;
+        job.validate()?;
+        Ok(job)
+    }
+
+    /// to read a job written in TOML, the job is not validated
+    /// * `content` - the TOML document
+    /// * Return: `Job` - the job, or a `JobError::Parse` with the place of the mistake
+    pub fn from_toml(content: &str) -> Result<Job, JobError> {
+        toml::from_str(content).map_err(|err| JobError::Parse(err.to_string()))
+    }
+
+    /// to read a job written in JSON, the job is not validated
+    /// * `content` - the JSON document
+    /// * Return: `Job` - the job, or a `JobError::Parse` with the place of the mistake
+    pub fn from_json(content: &str) -> Result<Job, JobError> {
+        serde_json::from_str(content).map_err(|err| JobError::Parse(err.to_string()))
+    }
+
+    /// to check every field of the job before any task is dispatched
+    /// * Return: `()` if the job can be rendered, a `JobError::Invalid` with the first field at fault otherwise
+    pub fn validate(&self) -> Result<(), JobError> {
+        validate_fractal(&self.fractal)?;
+
+        let Range { min, max } = self.viewport;
+        if ![min.x, min.y, max.x, max.y].iter().all(|v| v.is_finite()) {
+            return Err(invalid("viewport", "the corners must be finite numbers"));
+        }
+        if min.x >= max.x || min.y >= max.y {
+            return Err(invalid(
+                "viewport",
+                format!(
+                    "min ({}, {}) must be below and left of max ({}, {})",
+                    min.x, min.y, max.x, max.y
+                ),
+            ));
+        }
+        if self.resolution.nx == 0 || self.resolution.ny == 0 {
+            return Err(invalid("resolution", "nx and ny must be at least 1"));
+        }
+        if self.tile_size.nx == 0 || self.tile_size.ny == 0 {
+            return Err(invalid("tile_size", "nx and ny must be at least 1"));
+        }
+        if self.max_iteration == 0 {
+            return Err(invalid("max_iteration", "must be at least 1"));
+        }
+        if self.priority == 0 {
+            return Err(invalid("priority", "must be at least 1"));
+        }
+
+        if self.output.trim().is_empty() {
+            return Err(invalid("output", "the path of the image is empty"));
+        }
+        match image::ImageFormat::from_path(&self.output) {
+            Ok(format) if format.writing_enabled() => Ok(()),
+            _ => Err(invalid(
+                "output",
+                format!(
+                    "{} is not an image format the server can write, use .png",
+                    self.output
+                ),
+            )),
+        }
+    }
+
+    /// to get the tiling plan of the image
+    /// * Return: `TilePlan` - the plan cutting the image into tasks
+    pub fn plan(&self) -> TilePlan {
+        TilePlan::new(
+            self.fractal,
+            self.viewport,
+            self.resolution,
+            self.tile_size,
+            self.max_iteration,
+        )
+    }
+}
+
+fn default_tile_size() -> Resolution {
+    DEFAULT_TILE_SIZE
+}
+
+fn default_priority() -> u32 {
+    1
+}
+
+fn invalid<R: Into<String>>(field: &'static str, reason: R) -> JobError {
+    JobError::Invalid {
+        field,
+        reason: reason.into(),
+    }
+}
+
+/// to check the parameters of the fractal
+fn validate_fractal(fractal: &FractalDescriptor) -> Result<(), JobError> {
+    let finite = |c: &Complex| c.re.is_finite() && c.im.is_finite();
+    match fractal {
+        FractalDescriptor::Julia(julia) => {
+            if !finite(&julia.c) {
+                return Err(invalid(
+                    "fractal.Julia.c",
+                    "must be a finite complex number",
+                ));
+            }
+            if !julia.divergence_threshold_square.is_finite()
+                || julia.divergence_threshold_square <= 0.0
+            {
+                return Err(invalid(
+                    "fractal.Julia.divergence_threshold_square",
+                    "must be a positive number",
+                ));
+            }
+            Ok(())
+        }
+        FractalDescriptor::IteratedSinZ(iterated_sin_z) if !finite(&iterated_sin_z.c) => Err(
+            invalid("fractal.IteratedSinZ.c", "must be a finite complex number"),
+        ),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,2 @@
+pub mod job_file;
+mod test_job_file;
@@ -0,0 +1,186 @@
+#[cfg(test)]
+use std::fs;
+
+#[cfg(test)]
+use complex_math::Complex;
+
+#[cfg(test)]
+use crate::complementary_types::{point::Point, resolution::Resolution};
+#[cfg(test)]
+use crate::fractal_implementation::{fractal::FractalDescriptor, palette::Palette};
+#[cfg(test)]
+use crate::fractal_types::julia_descriptor::JuliaDescriptor;
+#[cfg(test)]
+use crate::job::job_file::{Job, JobError, DEFAULT_TILE_SIZE};
+
+#[cfg(test)]
+const JULIA_TOML: &str = r#"
+max_iteration = 256
+palette = "Banded"
+priority = 3
+output = "images/server/julia.png"
+
+[fractal.Julia]
+c = { re = -0.8, im = 0.156 }
+divergence_threshold_square = 4.0
+
+[viewport]
+min = { x = -1.6, y = -0.9 }
+max = { x = 1.6, y = 0.9 }
+
+[resolution]
+nx = 1600
+ny = 900
+
+[tile_size]
+nx = 400
+ny = 300
+"#;
+
+#[cfg(test)]
+const MANDELBROT_JSON: &str = r#"{
+    "fractal": { "Mandelbrot": {} },
+    "viewport": { "min": { "x": -2.0, "y": -1.2 }, "max": { "x": 1.0, "y": 1.2 } },
+    "resolution": { "nx": 1000, "ny": 800 },
+    "max_iteration": 128,
+    "output": "images/server/mandelbrot.png"
+}"#;
+
+#[cfg(test)]
+fn invalid_field(job: &Job) -> &'static str {
+    match job.validate() {
+        Err(JobError::Invalid { field, .. }) => field,
+        result => panic!("an invalid field was expected, {:?} received", result),
+    }
+}
+
+#[test]
+fn test_toml_job_with_every_field() {
+    let job = Job::from_toml(JULIA_TOML).unwrap();
+    assert!(job.validate().is_ok());
+    assert_eq!(
+        job.fractal,
+        FractalDescriptor::Julia(JuliaDescriptor {
+            c: Complex::new(-0.8, 0.156),
+            divergence_threshold_square: 4.0,
+        })
+    );
+    assert_eq!(job.viewport.min, Point::new(-1.6, -0.9));
+    assert_eq!(job.palette, Palette::Banded);
+    assert_eq!(job.priority, 3);
+
+    // the image is not square, the tiles of the last column are narrower
+    let tasks = job.plan().tasks().unwrap();
+    assert_eq!(tasks.len(), 4 * 3);
+    assert!(tasks.iter().all(|task| task.max_iteration == 256));
+}
+
+#[test]
+fn test_json_job_with_defaults() {
+    let job = Job::from_json(MANDELBROT_JSON).unwrap();
+    assert!(job.validate().is_ok());
+    assert_eq!(job.tile_size, DEFAULT_TILE_SIZE);
+    assert_eq!(job.palette, Palette::Classic);
+    assert_eq!(job.priority, 1);
+    assert_eq!(job.resolution, Resolution::new(1000, 800));
+}
+
+#[test]
+fn test_malformed_jobs_are_refused() {
+    // a typo in a field name
+    let typo = MANDELBROT_JSON.replace("max_iteration", "max_iterations");
+    assert!(matches!(Job::from_json(&typo), Err(JobError::Parse(_))));
+
+    // an unknown fractal
+    let unknown = MANDELBROT_JSON.replace("Mandelbrot\"", "Burning\"");
+    assert!(matches!(Job::from_json(&unknown), Err(JobError::Parse(_))));
+
+    // the place of the mistake is given
+    let err = Job::from_toml("max_iteration = \"many\"").unwrap_err();
+    assert!(err.to_string().contains("max_iteration"));
+}
+
+#[test]
+fn test_invalid_fields_are_named() {
+    let job = Job::from_toml(JULIA_TOML).unwrap();
+
+    let mut invalid = job.clone();
+    invalid.fractal = FractalDescriptor::Julia(JuliaDescriptor {
+        c: Complex::new(f64::NAN, 0.0),
+        divergence_threshold_square: 4.0,
+    });
+    assert_eq!(invalid_field(&invalid), "fractal.Julia.c");
+
+    let mut invalid = job.clone();
+    invalid.fractal = FractalDescriptor::Julia(JuliaDescriptor {
+        c: Complex::new(0.0, 0.0),
+        divergence_threshold_square: -1.0,
+    });
+    assert_eq!(
+        invalid_field(&invalid),
+        "fractal.Julia.divergence_threshold_square"
+    );
+
+    let mut invalid = job.clone();
+    invalid.viewport.max.y = invalid.viewport.min.y;
+    assert_eq!(invalid_field(&invalid), "viewport");
+
+    let mut invalid = job.clone();
+    invalid.resolution.ny = 0;
+    assert_eq!(invalid_field(&invalid), "resolution");
+
+    let mut invalid = job.clone();
+    invalid.tile_size.nx = 0;
+    assert_eq!(invalid_field(&invalid), "tile_size");
+
+    let mut invalid = job.clone();
+    invalid.max_iteration = 0;
+    assert_eq!(invalid_field(&invalid), "max_iteration");
+
+    let mut invalid = job.clone();
+    invalid.priority = 0;
+    assert_eq!(invalid_field(&invalid), "priority");
+
+    let mut invalid = job.clone();
+    invalid.output = String::from("images/server/julia.txt");
+    assert_eq!(invalid_field(&invalid), "output");
+}
+
+#[test]
+fn test_load_by_extension() {
+    let directory = std::env::temp_dir().join(format!("frakt_test_job_{}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+
+    let toml_path = directory.join("julia.toml");
+    fs::write(&toml_path, JULIA_TOML).unwrap();
+    let job = Job::load(toml_path.to_str().unwrap()).unwrap();
+    assert_eq!(job.resolution, Resolution::new(1600, 900));
+
+    let json_path = directory.join("mandelbrot.JSON");
+    fs::write(&json_path, MANDELBROT_JSON).unwrap();
+    assert!(Job::load(json_path.to_str().unwrap()).is_ok());
+
+    // an invalid job is refused when loaded
+    let invalid_path = directory.join("invalid.json");
+    fs::write(&invalid_path, MANDELBROT_JSON.replace("128", "0")).unwrap();
+    assert!(matches!(
+        Job::load(invalid_path.to_str().unwrap()),
+        Err(JobError::Invalid {
+            field: "max_iteration",
+            ..
+        })
+    ));
+
+    let yaml_path = directory.join("julia.yaml");
+    assert!(matches!(
+        Job::load(yaml_path.to_str().unwrap()),
+        Err(JobError::UnknownFormat(_))
+    ));
+    let missing_path = directory.join("missing.toml");
+    assert!(matches!(
+        Job::load(missing_path.to_str().unwrap()),
+        Err(JobError::Read { .. })
+    ));
+
+    fs::remove_dir_all(&directory).unwrap();
+}
@@ -1,5 +1,9 @@
+pub mod capture;
 pub mod complementary_types;
+pub mod errors;
 pub mod fractal_implementation;
 pub mod fractal_types;
+pub mod job;
 pub mod messages;
 pub mod messages_methods;
+pub mod transport;
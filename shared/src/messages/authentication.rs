@@ -0,0 +1,78 @@
+//! # Authentication
+//!
+//! Workers can authenticate with a key shared with the server, so that only trusted workers may
+//! submit results. The key never travels on the network: it is proved with a challenge-response
+//! during the handshake.
+//!
+//! 1. the worker sends its `FragmentHello`
+//! 2. the server answers with a `FragmentChallenge` carrying a random nonce in its data
+//! 3. the worker answers with a `FragmentAuthentication` carrying the HMAC-SHA256 of the nonce and
+//!    of its name, computed with the shared key
+//! 4. the server checks the proof and sends its `FragmentWelcome`
+//!
+//! A fresh nonce is drawn for every session, so a captured proof cannot be replayed.
+
+use std::{fs, io};
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Size of the nonces sent in a `FragmentChallenge`
+pub const NONCE_SIZE: usize = 32;
+
+/// to draw the nonce of a new challenge
+/// * Return: `Vec<u8>` - `NONCE_SIZE` random bytes
+pub fn generate_nonce() -> Vec<u8> {
+    let mut nonce = vec![0_u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+/// to compute the answer of a worker to a challenge
+/// * `secret` - the key shared by the worker and the server
+/// * `nonce` - the nonce of the challenge
+/// * `worker_name` - the name announced by the worker in its `FragmentHello`
+/// * Return: `Vec<u8>` - the HMAC-SHA256 of the nonce and of the name
+pub fn compute_proof(secret: &[u8], nonce: &[u8], worker_name: &str) -> Vec<u8> {
+    proof_mac(secret, nonce, worker_name)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+/// to check the answer of a worker to a challenge, in constant time
+/// * `secret` - the key of the server
+/// * `nonce` - the nonce of the challenge
+/// * `worker_name` - the name announced by the worker in its `FragmentHello`
+/// * `proof` - the proof sent by the worker
+/// * Return: `bool` - true if the worker knows the key
+pub fn verify_proof(secret: &[u8], nonce: &[u8], worker_name: &str, proof: &[u8]) -> bool {
+    proof_mac(secret, nonce, worker_name)
+        .verify_slice(proof)
+        .is_ok()
+}
+
+/// to read a shared key from a file, surrounding whitespaces are ignored
+/// * `path` - the path of the file
+/// * Return: the key, or an `io::Error` if the file cannot be read or is empty
+pub fn load_secret(path: &str) -> Result<Vec<u8>, io::Error> {
+    let content = fs::read(path)?;
+    let secret = content.trim_ascii().to_vec();
+    if secret.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The secret file {} is empty", path),
+        ));
+    }
+    Ok(secret)
+}
+
+fn proof_mac(secret: &[u8], nonce: &[u8], worker_name: &str) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(nonce);
+    mac.update(worker_name.as_bytes());
+    mac
+}
@@ -0,0 +1,39 @@
+//! # Fragment Binary Serialization
+//!
+//! This module provides functions for serializing and deserializing fragments using CBOR, a compact
+//! binary format. It is the binary alternative to `fragment_method_json`, selected per session
+//! through the handshake: smaller headers for small tiles and faster parsing on the server thread.
+//!
+//! CBOR is self-describing, so optional fields and new fields are handled the same way as in JSON.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use shared_lib::messages::fragment_method_cbor::{cbor_to_fragment, fragment_to_cbor};
+//! use shared_lib::messages::message::{Fragment, FragmentRequest};
+//!
+//! let fragment = Fragment::FragmentRequest(FragmentRequest::new(String::from("worker"), 10));
+//!
+//! let bytes = fragment_to_cbor(&fragment).unwrap();
+//! assert_eq!(cbor_to_fragment(&bytes).unwrap(), fragment);
+//! ```
+
+use std::io;
+
+use super::message::Fragment;
+
+/// Convert a `Fragment` reference to CBOR bytes.
+/// * `message` - The `Fragment` reference to convert.
+/// * Return: a `Result` containing the CBOR bytes if successful, or a `ciborium` error if an error occurred.
+pub fn fragment_to_cbor(message: &Fragment) -> Result<Vec<u8>, ciborium::ser::Error<io::Error>> {
+    let mut bytes = Vec::new();
+    ciborium::into_writer(message, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Convert CBOR bytes to a `Fragment`.
+/// * `message` - The CBOR bytes to convert.
+/// * Return: a `Result` containing the `Fragment` if successful, or a `ciborium` error if an error occurred.
+pub fn cbor_to_fragment(message: &[u8]) -> Result<Fragment, ciborium::de::Error<io::Error>> {
+    ciborium::from_reader(message)
+}
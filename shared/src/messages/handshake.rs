@@ -0,0 +1,224 @@
+//! # Handshake
+//!
+//! This module describes what a peer supports and how two peers agree on a common set of
+//! features when a session starts.
+//!
+//! A worker opens its session with a `FragmentHello` announcing its protocol version and its
+//! `Capabilities`. The server answers with a `FragmentWelcome` which either refuses the worker with
+//! a reason, or carries the negotiated `Capabilities` both peers use for the rest of the session.
+//!
+//! Workers which do not send a `FragmentHello` are legacy workers: the server uses
+//! `Capabilities::legacy()` for them.
+
+use serde::{Deserialize, Serialize};
+
+use crate::fractal_implementation::fractal::FractalDescriptor;
+
+/// Version of the protocol implemented by this library.
+/// Legacy peers, which do not send any `FragmentHello`, are considered as version 0.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Default maximal size of a frame (total size announced at the beginning of a message).
+pub const DEFAULT_MAX_MESSAGE_SIZE: u32 = 16 * 1024 * 1024;
+
+/// Encoding of the `Fragment` header of every frame following the handshake.
+/// The handshake itself is always sent in JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderEncoding {
+    /// Human readable, used by legacy peers and handy for debugging
+    Json,
+    /// Compact binary encoding
+    Cbor,
+}
+
+/// Encoding of the pixels carried in the data section of a `FragmentResult`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// Pairs of big-endian `f32` (zn, count), 8 bytes per pixel.
+    F32BigEndian,
+    /// The same pairs, compressed with deflate.
+    Deflate,
+}
+
+/// What a peer is able to handle.
+/// * fractals: names of the fractals the peer can compute or dispatch
+/// * header_encodings: encodings of the `Fragment` header, by order of preference
+/// * payload_encodings: encodings of the data section, by order of preference
+/// * max_message_size: maximal size of a frame the peer accepts to read
+/// * progress: whether the worker sends `FragmentProgress` while computing, and the server accepts them
+/// * checksum: whether the worker announces the CRC32 of the data of its results, checked by the server
+/// * cancel: whether the server may push a `FragmentCancel` while the worker computes
+/// * batch: whether the server may answer a `FragmentRequest` with a `FragmentBatch` of up to `maximal_work_load` tasks
+/// * result_chunks: whether the worker may stream a result band by band with `FragmentResultChunk`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    pub fractals: Vec<String>,
+    #[serde(default = "json_header_only")]
+    pub header_encodings: Vec<HeaderEncoding>,
+    pub payload_encodings: Vec<PayloadEncoding>,
+    pub max_message_size: u32,
+    #[serde(default)]
+    pub progress: bool,
+    #[serde(default)]
+    pub batch: bool,
+    #[serde(default)]
+    pub cancel: bool,
+    #[serde(default)]
+    pub result_chunks: bool,
+    #[serde(default)]
+    pub checksum: bool,
+}
+
+/// Header encodings of a peer which does not announce any: JSON only
+fn json_header_only() -> Vec<HeaderEncoding> {
+    vec![HeaderEncoding::Json]
+}
+
+impl Capabilities {
+    pub fn new(
+        fractals: Vec<String>,
+        header_encodings: Vec<HeaderEncoding>,
+        payload_encodings: Vec<PayloadEncoding>,
+        max_message_size: u32,
+    ) -> Capabilities {
+        Capabilities {
+            fractals,
+            header_encodings,
+            payload_encodings,
+            max_message_size,
+            progress: false,
+            batch: false,
+            cancel: false,
+            result_chunks: false,
+            checksum: false,
+        }
+    }
+
+    /// Capabilities of this library: every fractal and every payload encoding it implements
+    pub fn supported() -> Capabilities {
+        Capabilities {
+            progress: true,
+            batch: true,
+            cancel: true,
+            result_chunks: true,
+            checksum: true,
+            ..Capabilities::new(
+                FractalDescriptor::supported_kinds(),
+                vec![HeaderEncoding::Cbor, HeaderEncoding::Json],
+                vec![PayloadEncoding::F32BigEndian, PayloadEncoding::Deflate],
+                DEFAULT_MAX_MESSAGE_SIZE,
+            )
+        }
+    }
+
+    /// Capabilities assumed for a legacy peer which did not send any `FragmentHello`
+    pub fn legacy() -> Capabilities {
+        Capabilities {
+            header_encodings: json_header_only(),
+            payload_encodings: vec![PayloadEncoding::F32BigEndian],
+            progress: false,
+            batch: false,
+            cancel: false,
+            result_chunks: false,
+            checksum: false,
+            ..Capabilities::supported()
+        }
+    }
+
+    /// Encoding of the headers once the handshake is done: the preferred one
+    /// * Return: `HeaderEncoding` - the first header encoding, JSON if there is none
+    pub fn header_encoding(&self) -> HeaderEncoding {
+        match self.header_encodings.first() {
+            Some(encoding) => *encoding,
+            None => HeaderEncoding::Json,
+        }
+    }
+
+    /// Encoding of the pixels once the handshake is done: the preferred one
+    /// * Return: `PayloadEncoding` - the first payload encoding, raw pixels if there is none
+    pub fn payload_encoding(&self) -> PayloadEncoding {
+        match self.payload_encodings.first() {
+            Some(encoding) => *encoding,
+            None => PayloadEncoding::F32BigEndian,
+        }
+    }
+
+    /// to know if a fractal can be handled
+    /// * `fractal` - the fractal to check
+    /// * Return: `bool` - true if the fractal is part of the capabilities
+    pub fn supports_fractal(&self, fractal: &FractalDescriptor) -> bool {
+        self.fractals.contains(&fractal.to_string())
+    }
+
+    /// to negotiate the capabilities used during a session
+    /// * `remote` - the capabilities announced by the peer
+    /// * Return: `Result<Capabilities, String>` - the capabilities common to both peers, or the reason why they are incompatible
+    pub fn negotiate(&self, remote: &Capabilities) -> Result<Capabilities, String> {
+        let fractals: Vec<String> = remote
+            .fractals
+            .iter()
+            .filter(|fractal| self.fractals.contains(fractal))
+            .cloned()
+            .collect();
+        if fractals.is_empty() {
+            return Err(format!(
+                "No fractal in common, supported fractals are: {}",
+                self.fractals.join(", ")
+            ));
+        }
+
+        // JSON is always understood: a peer without any header encoding in common falls back to it
+        let mut header_encodings: Vec<HeaderEncoding> = remote
+            .header_encodings
+            .iter()
+            .filter(|encoding| self.header_encodings.contains(encoding))
+            .copied()
+            .collect();
+        if header_encodings.is_empty() {
+            header_encodings = json_header_only();
+        }
+
+        let payload_encodings: Vec<PayloadEncoding> = remote
+            .payload_encodings
+            .iter()
+            .filter(|encoding| self.payload_encodings.contains(encoding))
+            .copied()
+            .collect();
+        if payload_encodings.is_empty() {
+            return Err(format!(
+                "No payload encoding in common, supported encodings are: {:?}",
+                self.payload_encodings
+            ));
+        }
+
+        let max_message_size = self.max_message_size.min(remote.max_message_size);
+
+        Ok(Capabilities {
+            progress: self.progress && remote.progress,
+            batch: self.batch && remote.batch,
+            cancel: self.cancel && remote.cancel,
+            result_chunks: self.result_chunks && remote.result_chunks,
+            checksum: self.checksum && remote.checksum,
+            ..Capabilities::new(
+                fractals,
+                header_encodings,
+                payload_encodings,
+                max_message_size,
+            )
+        })
+    }
+}
+
+/// to check the protocol version announced by a peer
+/// * `version` - the protocol version of the peer
+/// * Return: `Result<(), String>` - an error explaining the mismatch if the version is not supported
+pub fn check_protocol_version(version: u16) -> Result<(), String> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(format!(
+            "Protocol version {} is not supported, expected version {}",
+            version, PROTOCOL_VERSION
+        ))
+    }
+}
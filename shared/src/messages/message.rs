@@ -7,11 +7,23 @@ use crate::{
     fractal_implementation::fractal::FractalDescriptor,
 };
 
+use super::handshake::Capabilities;
+
 #[derive(Debug, Deserialize, Serialize, PartialEq)]
 pub enum Fragment {
     FragmentResult(FragmentResult),
     FragmentRequest(FragmentRequest),
     FragmentTask(FragmentTask),
+    FragmentHello(FragmentHello),
+    FragmentWelcome(FragmentWelcome),
+    FragmentChallenge(FragmentChallenge),
+    FragmentAuthentication(FragmentAuthentication),
+    FragmentError(FragmentError),
+    FragmentNoWork(FragmentNoWork),
+    FragmentProgress(FragmentProgress),
+    FragmentBatch(FragmentBatch),
+    FragmentCancel(FragmentCancel),
+    FragmentResultChunk(FragmentResultChunk),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
@@ -70,12 +82,20 @@ impl FragmentTask {
     // }
 }
 
+/// Sent by a worker with the pixels of a task:
+/// * id: the id of the task, in the data of the message
+/// * resolution: the resolution of the task
+/// * range: the range of the task
+/// * pixels: the pixels, in the data after the id
+/// * checksum: the CRC32 of the whole data, when negotiated with the server
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct FragmentResult {
     pub id: U8Data,
     pub resolution: Resolution,
     pub range: Range,
     pub pixels: PixelData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
 }
 
 impl FragmentResult {
@@ -90,6 +110,7 @@ impl FragmentResult {
             resolution,
             range,
             pixels,
+            checksum: None,
         }
     }
 
@@ -147,3 +168,230 @@ impl FragmentRequest {
         }
     }
 }
+
+/// First message of a session, sent by the worker to announce what it supports
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentHello {
+    pub protocol_version: u16,
+    pub worker_name: String,
+    pub capabilities: Capabilities,
+}
+
+impl FragmentHello {
+    pub fn new(
+        protocol_version: u16,
+        worker_name: String,
+        capabilities: Capabilities,
+    ) -> FragmentHello {
+        FragmentHello {
+            protocol_version,
+            worker_name,
+            capabilities,
+        }
+    }
+}
+
+/// Answer of the server to a `FragmentHello`
+/// * accepted: false if the worker is refused, `reason` then explains why
+/// * capabilities: the capabilities negotiated for the session (the server ones if refused)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentWelcome {
+    pub protocol_version: u16,
+    pub accepted: bool,
+    pub reason: Option<String>,
+    pub capabilities: Capabilities,
+}
+
+impl FragmentWelcome {
+    pub fn accept(protocol_version: u16, capabilities: Capabilities) -> FragmentWelcome {
+        FragmentWelcome {
+            protocol_version,
+            accepted: true,
+            reason: None,
+            capabilities,
+        }
+    }
+
+    pub fn refuse(
+        protocol_version: u16,
+        reason: String,
+        capabilities: Capabilities,
+    ) -> FragmentWelcome {
+        FragmentWelcome {
+            protocol_version,
+            accepted: false,
+            reason: Some(reason),
+            capabilities,
+        }
+    }
+}
+
+/// Sent by a server requiring authentication in answer to a `FragmentHello`
+/// * nonce: where the random nonce to sign is in the data of the message
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FragmentChallenge {
+    pub nonce: U8Data,
+}
+
+impl FragmentChallenge {
+    pub fn new(nonce: U8Data) -> FragmentChallenge {
+        FragmentChallenge { nonce }
+    }
+}
+
+/// Answer of the worker to a `FragmentChallenge`
+/// * proof: where the proof that the worker knows the shared key is in the data of the message
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FragmentAuthentication {
+    pub proof: U8Data,
+}
+
+impl FragmentAuthentication {
+    pub fn new(proof: U8Data) -> FragmentAuthentication {
+        FragmentAuthentication { proof }
+    }
+}
+
+/// Why the server rejected a message or aborted the work of a worker
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The pixels of a result cannot be decoded, the task is given to another worker
+    InvalidResult,
+    /// A result refers to a task the server does not know or no longer waits for
+    UnknownTask,
+    /// The worker sent a message the server does not expect
+    UnexpectedFragment,
+    /// The worker must authenticate before sending requests or results
+    Unauthenticated,
+    /// The data of a result does not match its checksum, the task is given to another worker
+    ChecksumMismatch,
+    /// The server failed to handle the message
+    Internal,
+}
+
+/// Sent by the server instead of a `FragmentTask` when it rejects a message of the worker
+/// * code: what went wrong, for the worker to react to
+/// * message: details for the logs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl FragmentError {
+    pub fn new(code: ErrorCode, message: String) -> FragmentError {
+        FragmentError { code, message }
+    }
+}
+
+/// Sent by the server instead of a `FragmentTask` when it has no task for the worker right now
+/// * retry_after: seconds to wait before sending a new `FragmentRequest`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FragmentNoWork {
+    pub retry_after: u32,
+}
+
+impl FragmentNoWork {
+    pub fn new(retry_after: u32) -> FragmentNoWork {
+        FragmentNoWork { retry_after }
+    }
+}
+
+/// Sent periodically by a worker while it computes a task, to show it is still alive
+/// * id: the id of the task, carried in the data of the message
+/// * percentage: share of the pixels of the task already computed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct FragmentProgress {
+    pub id: U8Data,
+    pub percentage: u8,
+}
+
+impl FragmentProgress {
+    pub fn new(id: U8Data, percentage: u8) -> FragmentProgress {
+        FragmentProgress { id, percentage }
+    }
+}
+
+/// Sent by the server instead of a `FragmentTask` when the worker can compute several tasks at once
+/// * tasks: the tasks to compute, the id of each of them is in the data of the message at its own offset
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentBatch {
+    pub tasks: Vec<FragmentTask>,
+}
+
+impl FragmentBatch {
+    pub fn new(tasks: Vec<FragmentTask>) -> FragmentBatch {
+        FragmentBatch { tasks }
+    }
+}
+
+/// Pushed by the server to a worker computing tasks which are no longer needed
+/// * ids: the ids of the tasks to stop, each of them in the data of the message at its own offset
+/// * reason: why the tasks are cancelled, for the logs
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentCancel {
+    pub ids: Vec<U8Data>,
+    pub reason: String,
+}
+
+impl FragmentCancel {
+    pub fn new(ids: Vec<U8Data>, reason: String) -> FragmentCancel {
+        FragmentCancel { ids, reason }
+    }
+}
+
+/// Sent by a worker instead of a `FragmentResult` to stream the pixels of a task band by band, so
+/// neither peer holds the whole payload
+/// * id: the id of the task, in the data of the message
+/// * resolution: the resolution of the whole task
+/// * range: the range of the whole task
+/// * first_pixel: the index in the task of the first pixel of the band
+/// * pixels: the pixels of the band, in the data after the id
+/// * last: true for the band closing the result
+/// * checksum: the CRC32 of the whole data of the band, when negotiated with the server
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FragmentResultChunk {
+    pub id: U8Data,
+    pub resolution: Resolution,
+    pub range: Range,
+    pub first_pixel: u32,
+    pub pixels: PixelData,
+    pub last: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+}
+
+impl FragmentResultChunk {
+    /// to create the band of a result
+    /// * `task` - the task computed
+    /// * `first_pixel` - the index in the task of the first pixel of the band
+    /// * `count` - the number of pixels of the band
+    /// * Return: `FragmentResultChunk` - the band, the last one if it reaches the end of the task
+    pub fn create(task: &FragmentTask, first_pixel: u32, count: u32) -> FragmentResultChunk {
+        let total = task.resolution.nx as u32 * task.resolution.ny as u32;
+        FragmentResultChunk {
+            id: task.id,
+            resolution: task.resolution,
+            range: task.range,
+            first_pixel,
+            pixels: PixelData::new(task.id.offset + task.id.count, count),
+            last: first_pixel + count >= total,
+            checksum: None,
+        }
+    }
+
+    /// to see a whole `FragmentResult` as its only band
+    /// * `result` - the result
+    /// * Return: `FragmentResultChunk` - the last band, starting at the first pixel
+    pub fn whole(result: FragmentResult) -> FragmentResultChunk {
+        FragmentResultChunk {
+            id: result.id,
+            resolution: result.resolution,
+            range: result.range,
+            first_pixel: 0,
+            pixels: result.pixels,
+            last: true,
+            checksum: result.checksum,
+        }
+    }
+}
@@ -1,2 +1,7 @@
+pub mod authentication;
+pub mod fragment_method_cbor;
 pub mod fragment_method_json;
+pub mod handshake;
 pub mod message;
+mod test_authentication;
+mod test_handshake;
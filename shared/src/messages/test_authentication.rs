@@ -0,0 +1,37 @@
+#[cfg(test)]
+use crate::messages::authentication::{compute_proof, generate_nonce, verify_proof, NONCE_SIZE};
+
+#[test]
+fn test_proof_is_accepted_with_the_same_secret() {
+    let nonce = generate_nonce();
+    assert_eq!(nonce.len(), NONCE_SIZE);
+
+    let proof = compute_proof(b"shared secret", &nonce, "worker");
+    assert!(verify_proof(b"shared secret", &nonce, "worker", &proof));
+}
+
+#[test]
+fn test_proof_is_refused_when_anything_differs() {
+    let nonce = generate_nonce();
+    let proof = compute_proof(b"shared secret", &nonce, "worker");
+
+    assert!(!verify_proof(b"other secret", &nonce, "worker", &proof));
+    assert!(!verify_proof(
+        b"shared secret",
+        &generate_nonce(),
+        "worker",
+        &proof
+    ));
+    assert!(!verify_proof(
+        b"shared secret",
+        &nonce,
+        "other worker",
+        &proof
+    ));
+    assert!(!verify_proof(
+        b"shared secret",
+        &nonce,
+        "worker",
+        &proof[1..]
+    ));
+}
@@ -0,0 +1,78 @@
+#[cfg(test)]
+use crate::messages::handshake::{
+    check_protocol_version, Capabilities, HeaderEncoding, PayloadEncoding, PROTOCOL_VERSION,
+};
+
+#[test]
+fn test_negotiate_keeps_common_capabilities() {
+    let server = Capabilities::supported();
+    let worker = Capabilities::new(
+        vec![String::from("Mandelbrot"), String::from("Unknown")],
+        vec![HeaderEncoding::Json],
+        vec![PayloadEncoding::F32BigEndian],
+        1024,
+    );
+
+    let negotiated = server.negotiate(&worker).unwrap();
+    assert_eq!(negotiated.fractals, vec![String::from("Mandelbrot")]);
+    assert_eq!(
+        negotiated.payload_encodings,
+        vec![PayloadEncoding::F32BigEndian]
+    );
+    assert_eq!(negotiated.header_encoding(), HeaderEncoding::Json);
+    assert_eq!(negotiated.max_message_size, 1024);
+    // a worker built with `new` does not announce progress messages
+    assert!(!negotiated.progress);
+    assert!(!negotiated.batch);
+    assert!(!negotiated.cancel);
+    assert!(!negotiated.result_chunks);
+    assert!(!negotiated.checksum);
+}
+
+#[test]
+fn test_negotiate_refuses_without_common_fractal() {
+    let server = Capabilities::supported();
+    let worker = Capabilities::new(
+        vec![String::from("Unknown")],
+        vec![HeaderEncoding::Json],
+        vec![PayloadEncoding::F32BigEndian],
+        1024,
+    );
+
+    assert!(server.negotiate(&worker).is_err());
+}
+
+#[test]
+fn test_check_protocol_version() {
+    assert!(check_protocol_version(PROTOCOL_VERSION).is_ok());
+    assert!(check_protocol_version(PROTOCOL_VERSION + 1).is_err());
+}
+
+#[test]
+fn test_negotiate_prefers_binary_headers() {
+    let negotiated = Capabilities::supported()
+        .negotiate(&Capabilities::supported())
+        .unwrap();
+    assert_eq!(negotiated.header_encoding(), HeaderEncoding::Cbor);
+    assert!(negotiated.progress && negotiated.batch && negotiated.cancel);
+    assert!(negotiated.result_chunks && negotiated.checksum);
+    assert!(!Capabilities::legacy().progress);
+    assert!(!Capabilities::legacy().batch);
+    assert!(!Capabilities::legacy().cancel);
+    assert!(!Capabilities::legacy().result_chunks);
+    assert!(!Capabilities::legacy().checksum);
+    assert_eq!(
+        Capabilities::legacy().header_encoding(),
+        HeaderEncoding::Json
+    );
+}
+
+#[test]
+fn test_hello_without_header_encodings_means_json() {
+    let capabilities: Capabilities = serde_json::from_str(
+        r#"{"fractals":["Julia"],"payload_encodings":["F32BigEndian"],"max_message_size":1024}"#,
+    )
+    .unwrap();
+    assert_eq!(capabilities.header_encodings, vec![HeaderEncoding::Json]);
+    assert!(!capabilities.progress);
+}
@@ -0,0 +1,162 @@
+//! # Async Message Handling
+//!
+//! Async counterpart of `messages_methods`, available with the `async` feature. It works over any
+//! tokio `AsyncRead`/`AsyncWrite` implementor and produces exactly the same frames as the blocking
+//! codec, so an async peer can talk to a blocking one.
+//!
+//! # Examples
+//!
+//! ```rust,ignore
+//! use tokio::net::TcpStream;
+//! use shared_lib::messages::message::{Fragment, FragmentRequest};
+//! use shared_lib::messages_methods::async_messages_methods::{read_message, send_message};
+//!
+//! async fn request_task() -> std::io::Result<()> {
+//!     let mut stream = TcpStream::connect("127.0.0.1:8787").await?;
+//!
+//!     let request = Fragment::FragmentRequest(FragmentRequest::new(String::from("worker"), 10));
+//!     send_message(&mut stream, request, &[]).await?;
+//!
+//!     let (task, id) = read_message(&mut stream).await?;
+//!     println!("Received Fragment: {:?} with id {:?}", task, id);
+//!
+//!     Ok(())
+//! }
+//! ```
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::messages::{handshake::HeaderEncoding, message::Fragment};
+
+use super::frame::{FrameError, FrameLimits};
+use super::messages_methods::{
+    check_frame_size, check_header_size, check_received, decode_fragment, encode_header,
+};
+use crate::errors::frakt_error::FraktError;
+
+/// Send a message over an async stream, with a JSON header.
+/// * `stream` - The stream to send the message over.
+/// * `fragment` - The `Fragment` to send.
+/// * `data` - The data to send after the JSON `Fragment`.
+/// * Return: `()` if successful, or a `FraktError` if an error occurred.
+pub async fn send_message<W: AsyncWrite + Unpin + ?Sized>(
+    stream: &mut W,
+    fragment: Fragment,
+    data: &[u8],
+) -> Result<(), FraktError> {
+    send_message_with(stream, fragment, data, HeaderEncoding::Json).await
+}
+
+/// Send a message over an async stream, with the header encoding negotiated for the session.
+/// * `stream` - The stream to send the message over.
+/// * `fragment` - The `Fragment` to send.
+/// * `data` - The data to send after the `Fragment`.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * Return: `()` if successful, or a `FraktError` if an error occurred.
+pub async fn send_message_with<W: AsyncWrite + Unpin + ?Sized>(
+    stream: &mut W,
+    fragment: Fragment,
+    data: &[u8],
+    encoding: HeaderEncoding,
+) -> Result<(), FraktError> {
+    let header = encode_header(&fragment, data.len(), encoding)?;
+
+    stream.write_all(&header).await?;
+    stream.write_all(data).await?;
+    stream.flush().await?;
+
+    Ok(())
+}
+
+/// Read a message with a JSON header from an async stream.
+/// * `stream` - The stream to read the message from.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub async fn read_message<R: AsyncRead + Unpin + ?Sized>(
+    stream: &mut R,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    read_message_with(stream, HeaderEncoding::Json).await
+}
+
+/// Read a message from an async stream, with the header encoding negotiated for the session and
+/// the default `FrameLimits`.
+/// * `stream` - The stream to read the message from.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub async fn read_message_with<R: AsyncRead + Unpin + ?Sized>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    read_message_with_limits(stream, encoding, &FrameLimits::default()).await
+}
+
+/// Read a message from an async stream, refusing frames over the given limits, like the blocking
+/// `read_message_with_limits`: the whole frame is read before its header is deserialized, so the
+/// stream stays synchronised when the header is invalid, and a frame not received within the
+/// `frame_timeout` of the limits once started is refused with a `FrameError::Timeout`.
+/// * `stream` - The stream to read the message from.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub async fn read_message_with_limits<R: AsyncRead + Unpin + ?Sized>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+    limits: &FrameLimits,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    // waiting for the next frame is not bounded here, only the rest of the frame once started
+    let mut total_len_buf = [0; 4];
+    let received = read_full(stream, &mut total_len_buf).await?;
+    let total_message_size = check_frame_size(total_len_buf, received, limits)?;
+
+    let (header, data) = match limits.frame_timeout {
+        Some(timeout) => {
+            tokio::time::timeout(timeout, read_frame_rest(stream, total_message_size, limits))
+                .await
+                .map_err(|_| FrameError::Timeout(timeout))??
+        }
+        None => read_frame_rest(stream, total_message_size, limits).await?,
+    };
+    Ok((decode_fragment(&header, encoding)?, data))
+}
+
+/// Read the rest of a frame once its total size is checked: header size, header and data.
+/// * `stream` - The stream to read the frame from.
+/// * `total_message_size` - The total size announced by the peer, already checked.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: a tuple containing the raw `Fragment` header and the data if successful, or a `FraktError::Frame` if the frame is refused.
+async fn read_frame_rest<R: AsyncRead + Unpin + ?Sized>(
+    stream: &mut R,
+    total_message_size: u32,
+    limits: &FrameLimits,
+) -> Result<(Vec<u8>, Vec<u8>), FraktError> {
+    let frame_len = 8 + total_message_size as usize;
+
+    let mut header_len_buf = [0; 4];
+    let received = read_full(stream, &mut header_len_buf).await?;
+    let (header_size, data_message_size) =
+        check_header_size(header_len_buf, received, total_message_size, limits)?;
+
+    let mut header = vec![0_u8; header_size];
+    let received = read_full(stream, &mut header).await?;
+    check_received(frame_len, 8, received, header.len())?;
+
+    let mut data = vec![0_u8; data_message_size];
+    let received = read_full(stream, &mut data).await?;
+    check_received(frame_len, 8 + header.len(), received, data.len())?;
+
+    Ok((header, data))
+}
+
+/// Fill a buffer from the async stream, stopping early only at the end of the stream.
+async fn read_full<R: AsyncRead + Unpin + ?Sized>(
+    stream: &mut R,
+    buf: &mut [u8],
+) -> Result<usize, FraktError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match stream.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
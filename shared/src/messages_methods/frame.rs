@@ -0,0 +1,201 @@
+//! # Frame limits and errors
+//!
+//! A frame announces its own sizes, and those sizes come from the peer: they cannot be trusted.
+//! `FrameLimits` bounds what a reader accepts before allocating anything, and `FrameError`
+//! describes why a frame was refused.
+//!
+//! The frame timeout is checked before every read of a frame. Over a stream implementing
+//! `ReadTimeout` (TCP, Unix socket, `Stream`), `read_message_with_deadline` also bounds each read by
+//! the time left, so a peer stalling in the middle of a frame is cut at the deadline instead of at
+//! the idle timeout of the stream.
+//!
+//! The codec reports a refused frame with `FraktError::Frame`. Once converted into an `io::Error`,
+//! the `FrameError` can still be recovered with `FrameError::from_io`.
+//!
+//! ```rust
+//! use std::io::Cursor;
+//!
+//! use shared_lib::errors::frakt_error::FraktError;
+//! use shared_lib::messages_methods::frame::{FrameError, FrameLimits};
+//! use shared_lib::messages_methods::messages_methods::read_message_with_limits;
+//! use shared_lib::messages::handshake::HeaderEncoding;
+//!
+//! // a peer announcing a 4 GiB frame
+//! let mut stream = Cursor::new(vec![0xff, 0xff, 0xff, 0xff, 0, 0, 0, 2]);
+//! let err = read_message_with_limits(&mut stream, HeaderEncoding::Json, &FrameLimits::default())
+//!     .unwrap_err();
+//! assert!(matches!(err, FraktError::Frame(FrameError::Oversize { .. })));
+//! ```
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::{
+    error::Error,
+    fmt, io,
+    net::TcpStream,
+    time::{Duration, Instant},
+};
+
+use crate::messages::handshake::DEFAULT_MAX_MESSAGE_SIZE;
+
+/// Default maximal size of the `Fragment` header of a frame.
+pub const DEFAULT_MAX_HEADER_SIZE: u32 = 64 * 1024;
+
+/// What a reader accepts from a peer:
+/// * max_frame_size: maximal total size of a frame (header and data)
+/// * max_header_size: maximal size of the `Fragment` header
+/// * frame_timeout: maximal time to receive a whole frame once its first bytes arrived, None to wait as long as the stream allows
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameLimits {
+    pub max_frame_size: u32,
+    pub max_header_size: u32,
+    pub frame_timeout: Option<Duration>,
+}
+
+impl FrameLimits {
+    pub fn new(max_frame_size: u32) -> FrameLimits {
+        FrameLimits {
+            max_frame_size,
+            ..FrameLimits::default()
+        }
+    }
+
+    /// to read frames from a trusted source, such as a capture file or a relay between two peers
+    /// enforcing their own limits
+    pub fn unlimited() -> FrameLimits {
+        FrameLimits {
+            max_frame_size: u32::MAX,
+            max_header_size: u32::MAX,
+            frame_timeout: None,
+        }
+    }
+}
+
+impl Default for FrameLimits {
+    fn default() -> FrameLimits {
+        FrameLimits {
+            max_frame_size: DEFAULT_MAX_MESSAGE_SIZE,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            frame_timeout: None,
+        }
+    }
+}
+
+/// Why a frame was refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrameError {
+    /// The stream ended in the middle of a frame
+    Truncated { expected: usize, received: usize },
+    /// A size announced by the peer is over the limit
+    Oversize { size: u64, max: u32 },
+    /// The header size announced is bigger than the total size
+    InconsistentSizes { total: u32, header: u32 },
+    /// The frame was not received in time
+    Timeout(Duration),
+}
+
+impl FrameError {
+    /// to recover the `FrameError` carried by an `io::Error`
+    /// * `err` - the error returned by the codec
+    /// * Return: `Option<&FrameError>` - the frame error, None for other io errors (connection closed, reset...)
+    pub fn from_io(err: &io::Error) -> Option<&FrameError> {
+        err.get_ref()
+            .and_then(|inner| inner.downcast_ref::<FrameError>())
+    }
+
+    fn kind(&self) -> io::ErrorKind {
+        match self {
+            FrameError::Truncated { .. } => io::ErrorKind::UnexpectedEof,
+            FrameError::Timeout(_) => io::ErrorKind::TimedOut,
+            _ => io::ErrorKind::InvalidData,
+        }
+    }
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::Truncated { expected, received } => write!(
+                f,
+                "Frame truncated: {} bytes expected, {} received",
+                expected, received
+            ),
+            FrameError::Oversize { size, max } => {
+                write!(f, "Frame too large: {} bytes, the limit is {}", size, max)
+            }
+            FrameError::InconsistentSizes { total, header } => write!(
+                f,
+                "Header size {} is bigger than total message size {}",
+                header, total
+            ),
+            FrameError::Timeout(timeout) => {
+                write!(f, "Frame not received within {:?}", timeout)
+            }
+        }
+    }
+}
+
+impl Error for FrameError {}
+
+impl From<FrameError> for io::Error {
+    fn from(err: FrameError) -> io::Error {
+        io::Error::new(err.kind(), err)
+    }
+}
+
+/// Deadline of the frame being read
+pub(crate) struct FrameDeadline {
+    deadline: Option<(Instant, Duration)>,
+}
+
+impl FrameDeadline {
+    /// to start the deadline of a frame, when its first bytes arrived
+    pub(crate) fn start(timeout: Option<Duration>) -> FrameDeadline {
+        FrameDeadline {
+            deadline: timeout.map(|timeout| (Instant::now() + timeout, timeout)),
+        }
+    }
+
+    /// to check the deadline before a new read
+    /// * Return: the time left to receive the frame, None without deadline, or a `FrameError::Timeout` once the deadline passed
+    pub(crate) fn remaining(&self) -> Result<Option<Duration>, FrameError> {
+        match self.deadline {
+            Some((deadline, timeout)) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Ok(Some(remaining)),
+                _ => Err(FrameError::Timeout(timeout)),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// A stream whose reads can be given a timeout, so the deadline of a frame also bounds a read
+/// waiting for bytes
+pub trait ReadTimeout {
+    /// Get the read timeout of the stream, None if reads block forever
+    fn read_timeout(&self) -> io::Result<Option<Duration>>;
+
+    /// Set the read timeout of the stream, None to block forever
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+impl ReadTimeout for TcpStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        TcpStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl ReadTimeout for UnixStream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        UnixStream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
@@ -1,41 +1,66 @@
 //! # Message Handling
 //!
-//! This module provides functions for sending and receiving messages over a TCP stream. Messages
-//! consist of a `Fragment` and associated data.
+//! This module provides functions for sending and receiving messages over any byte stream
+//! implementing `Read`/`Write` (TCP stream, Unix socket, in-memory buffer...). Messages consist of
+//! a `Fragment` and associated data.
+//!
+//! ## Frame Layout
+//!
+//! Every message is framed the same way, whatever the transport:
+//!
+//! | Field               | Size                  |
+//! |---------------------|-----------------------|
+//! | total size          | `u32` big-endian      |
+//! | header size         | `u32` big-endian      |
+//! | `Fragment` header   | header size bytes     |
+//! | data                | total - header size   |
+//!
+//! The `Fragment` header is encoded in JSON, or in CBOR once a session negotiated binary headers
+//! during the handshake (`send_message_with` / `read_message_with`), the layout staying the same.
+//!
+//! An async counterpart of the same codec is available in `async_messages_methods` when the
+//! `async` feature is enabled.
 //!
 //! ## Sending Messages
 //!
-//! The `send_message` function takes a mutable reference to a `TcpStream`, a `Fragment`, and data
+//! The `send_message` function takes a mutable reference to a `Write` implementor, a `Fragment`, and data
 //! as a vector of bytes. It serializes the `Fragment` to JSON, calculates the total message size,
 //! and sends both the JSON message size and content along with the data to the client.
 //!
 //! ## Receiving Messages
 //!
 //! The `read_message` function reads the total message size, JSON message size, and content from
-//! the `TcpStream`. It then deserializes the JSON message to a `Fragment` and returns it along
+//! the `Read` implementor. It then deserializes the JSON message to a `Fragment` and returns it along
 //! with the associated data as a vector of bytes.
 //!
+//! The sizes read come from the peer: `read_message_with_limits` checks them against `FrameLimits`
+//! before allocating anything and reports refused frames with a typed `FrameError` (see `frame`).
+//! `read_message_with_deadline` also bounds each read of a frame by the time left before its
+//! `frame_timeout`, for streams implementing `ReadTimeout`.
+//! Every function of this module fails with a `FraktError`.
+//! `read_message` and `read_message_with` use the default limits.
+//!
 //! # Examples
 //!
 //! ```rust
-//! use std::{io, net::TcpStream};
-//! use crate::messages::{fragment_method_json::{fragment_to_string, string_to_fragment}, message::Fragment};
+//! use std::io::{self, Cursor};
+//!
+//! use shared_lib::messages::message::{Fragment, FragmentRequest};
+//! use shared_lib::messages_methods::messages_methods::{read_message, send_message};
 //!
 //! fn main() -> io::Result<()> {
-//!     // Example usage of sending and receiving messages
-//!     let mut stream = TcpStream::connect("127.0.0.1:8080")?;
+//!     // Any `Write` implementor can carry a message, here an in-memory buffer
+//!     let mut buffer = Vec::new();
 //!
 //!     // Prepare a sample Fragment and data
-//!     let sample_fragment = Fragment {
-//!         // Initialize fields of the Fragment...
-//!     };
+//!     let sample_fragment = Fragment::FragmentRequest(FragmentRequest::new(String::from("worker"), 10));
 //!     let data = vec![1, 2, 3, 4, 5];
 //!
-//!     // Send message to the client
-//!     send_message(&mut stream, sample_fragment.clone(), &data)?;
+//!     // Send message into the buffer
+//!     send_message(&mut buffer, sample_fragment, &data)?;
 //!
-//!     // Read message from the client
-//!     let (received_fragment, received_data) = read_message(&mut stream)?;
+//!     // Read message back from the buffer
+//!     let (received_fragment, received_data) = read_message(&mut Cursor::new(buffer))?;
 //!
 //!     println!("Received Fragment: {:?}", received_fragment);
 //!     println!("Received Data: {:?}", received_data);
@@ -44,94 +69,366 @@
 //! }
 //! ```
 
-use std::{
-    io::{self, Read, Write},
-    net::TcpStream,
-};
+use std::io::{self, Read, Write};
 
+use std::time::Duration;
+
+use super::frame::{FrameDeadline, FrameError, FrameLimits, ReadTimeout};
+use crate::errors::frakt_error::FraktError;
 use crate::messages::{
+    fragment_method_cbor::{cbor_to_fragment, fragment_to_cbor},
     fragment_method_json::{fragment_to_string, string_to_fragment},
+    handshake::HeaderEncoding,
     message::Fragment,
 };
 
 /// Send a message to the client.
-/// * `stream` - The TCP stream to send the message over.
+/// * `stream` - The stream to send the message over.
 /// * `fragment` - The `Fragment` to send.
 /// * `data` - The data to send.
-/// * Return: an `io::Result` containing `()` if successful, or an `io::Error` if an error occurred.
-pub fn send_message_to_client(
-    stream: &mut TcpStream,
+/// * Return: `()` if successful, or a `FraktError` if an error occurred.
+pub fn send_message_to_client<W: Write + ?Sized>(
+    stream: &mut W,
     fragment: Fragment,
     data: Vec<u8>,
-) -> Result<(), io::Error> {
+) -> Result<(), FraktError> {
     send_message(stream, fragment, &data)
 }
 
-pub fn send_message(
-    stream: &mut TcpStream,
+/// Send a message over a stream, with a JSON header.
+/// * `stream` - The stream to send the message over.
+/// * `fragment` - The `Fragment` to send.
+/// * `data` - The data to send after the JSON `Fragment`.
+/// * Return: `()` if successful, or a `FraktError` if an error occurred.
+pub fn send_message<W: Write + ?Sized>(
+    stream: &mut W,
     fragment: Fragment,
-    data: &Vec<u8>,
-) -> Result<(), io::Error> {
-    let json_message = fragment_to_string(&fragment)?;
+    data: &[u8],
+) -> Result<(), FraktError> {
+    send_message_with(stream, fragment, data, HeaderEncoding::Json)
+}
 
-    let json_message_size = json_message.len() as u32;
-    let data_message_size = data.len() as u32;
-    let total_message_size: u32 = json_message_size + data_message_size;
+/// Send a message over a stream, with the header encoding negotiated for the session.
+/// * `stream` - The stream to send the message over.
+/// * `fragment` - The `Fragment` to send.
+/// * `data` - The data to send after the `Fragment`.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * Return: `()` if successful, or a `FraktError` if an error occurred.
+pub fn send_message_with<W: Write + ?Sized>(
+    stream: &mut W,
+    fragment: Fragment,
+    data: &[u8],
+    encoding: HeaderEncoding,
+) -> Result<(), FraktError> {
+    let header = encode_header(&fragment, data.len(), encoding)?;
 
-    stream.write_all(&total_message_size.to_be_bytes())?;
-    stream.write_all(&json_message_size.to_be_bytes())?;
-    stream.write_all(&json_message.as_bytes())?;
+    stream.write_all(&header)?;
     stream.write_all(data)?;
+    stream.flush()?;
 
     Ok(())
 }
 
-/// Read a message from the client.
-/// * `stream` - The TCP stream to read the message from.
-/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or an `io::Error` if an error occurred.
-pub fn read_message(stream: &mut TcpStream) -> Result<(Fragment, Vec<u8>), io::Error> {
-    let mut total_len_buf = [0; 4];
-    match stream.read_exact(&mut total_len_buf) {
-        Ok(_) => {}
-        Err(err) => {
-            println!("Error reading total message size");
-            return Err(err);
-        }
+/// Read a message with a JSON header from the client.
+/// * `stream` - The stream to read the message from.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub fn read_message<R: Read + ?Sized>(stream: &mut R) -> Result<(Fragment, Vec<u8>), FraktError> {
+    read_message_with(stream, HeaderEncoding::Json)
+}
+
+/// Read a message from the client, with the header encoding negotiated for the session and the
+/// default `FrameLimits`.
+/// * `stream` - The stream to read the message from.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub fn read_message_with<R: Read + ?Sized>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    read_message_with_limits(stream, encoding, &FrameLimits::default())
+}
+
+/// Read a message from the client, refusing frames over the given limits.
+/// Nothing is allocated before the sizes announced by the peer are checked. A frame which is
+/// truncated, too large or too slow is refused with a `FraktError::Frame`, a header which cannot be
+/// deserialized with a `FraktError::Deserialization`; the stream must then be closed as it is no
+/// longer synchronised.
+/// * `stream` - The stream to read the message from.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub fn read_message_with_limits<R: Read + ?Sized>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+    limits: &FrameLimits,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    let (header, data) = read_frame_with_limits(stream, limits)?;
+    Ok((decode_fragment(&header, encoding)?, data))
+}
+
+/// Read a message from the client like `read_message_with_limits`, each read of a frame being
+/// bounded by the time left before the `frame_timeout` of the limits: a peer stalling in the middle
+/// of a frame is cut at the deadline, not at the idle timeout of the stream. The read timeout of the
+/// stream is restored before returning.
+/// * `stream` - The stream to read the message from.
+/// * `encoding` - The encoding of the `Fragment` header.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: a tuple containing the `Fragment` and associated data as a vector of bytes if successful, or a `FraktError` if an error occurred.
+pub fn read_message_with_deadline<R: Read + ReadTimeout + ?Sized>(
+    stream: &mut R,
+    encoding: HeaderEncoding,
+    limits: &FrameLimits,
+) -> Result<(Fragment, Vec<u8>), FraktError> {
+    let idle_timeout = stream.read_timeout()?;
+    let bound = |stream: &mut R, remaining: Duration| {
+        let timeout = idle_timeout.map_or(remaining, |idle_timeout| idle_timeout.min(remaining));
+        stream.set_read_timeout(Some(timeout))
     };
+    let frame = read_frame(stream, limits, Some(&bound));
+    stream.set_read_timeout(idle_timeout)?;
+    let (header, data) = frame?;
+    Ok((decode_fragment(&header, encoding)?, data))
+}
+
+/// Read a frame without deserializing its header, refusing frames over the given limits, for
+/// tools relaying frames they do not need to understand.
+/// * `stream` - The stream to read the frame from.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: a tuple containing the raw `Fragment` header and the data if successful, or a `FraktError::Frame` if the frame is refused.
+pub fn read_frame_with_limits<R: Read + ?Sized>(
+    stream: &mut R,
+    limits: &FrameLimits,
+) -> Result<(Vec<u8>, Vec<u8>), FraktError> {
+    read_frame(stream, limits, None)
+}
+
+/// Bound given to the next read of a frame, from the time left before its deadline
+type ReadBound<'a, R> = &'a dyn Fn(&mut R, Duration) -> io::Result<()>;
+
+/// Read a frame, refusing frames over the given limits.
+/// * `stream` - The stream to read the frame from.
+/// * `limits` - The limits of the frames accepted.
+/// * `bound` - Called before each read of the frame with the time left, None to only check the deadline between reads.
+/// * Return: a tuple containing the raw `Fragment` header and the data if successful, or a `FraktError::Frame` if the frame is refused.
+fn read_frame<R: Read + ?Sized>(
+    stream: &mut R,
+    limits: &FrameLimits,
+    bound: Option<ReadBound<R>>,
+) -> Result<(Vec<u8>, Vec<u8>), FraktError> {
+    // waiting for the next frame is not bounded here: only the idle timeout of the stream applies
+    let mut total_len_buf = [0; 4];
+    let received = read_full(
+        stream,
+        &mut total_len_buf,
+        &FrameDeadline::start(None),
+        None,
+    )?;
+    let total_message_size = check_frame_size(total_len_buf, received, limits)?;
+
+    let deadline = FrameDeadline::start(limits.frame_timeout);
+    let frame_len = 8 + total_message_size as usize;
+
+    let mut header_len_buf = [0; 4];
+    let received = read_full(stream, &mut header_len_buf, &deadline, bound)?;
+    let (header_size, data_message_size) =
+        check_header_size(header_len_buf, received, total_message_size, limits)?;
+
+    let mut header = vec![0_u8; header_size];
+    let received = read_full(stream, &mut header, &deadline, bound)?;
+    check_received(frame_len, 8, received, header.len())?;
+
+    let mut data = vec![0_u8; data_message_size];
+    let received = read_full(stream, &mut data, &deadline, bound)?;
+    check_received(frame_len, 8 + header.len(), received, data.len())?;
+
+    Ok((header, data))
+}
+
+/// Check the total size read at the beginning of a frame, before anything is allocated.
+/// Shared by the blocking and async codecs so both refuse the same frames.
+/// * `total_len_buf` - The first 4 bytes of the frame.
+/// * `received` - The number of these bytes received, 0 if the stream ended between two frames.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: the total size announced, or a `FraktError` if the stream ended or the frame is refused.
+pub(crate) fn check_frame_size(
+    total_len_buf: [u8; 4],
+    received: usize,
+    limits: &FrameLimits,
+) -> Result<u32, FraktError> {
+    if received == 0 {
+        return Err(FraktError::Transport(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Connection closed by peer",
+        )));
+    }
+    check_received(8, 0, received, 4)?;
     let total_message_size = u32::from_be_bytes(total_len_buf);
+    if total_message_size > limits.max_frame_size {
+        return Err(FrameError::Oversize {
+            size: total_message_size as u64,
+            max: limits.max_frame_size,
+        }
+        .into());
+    }
+    Ok(total_message_size)
+}
 
-    let mut json_len_buf = [0; 4];
-    stream.read_exact(&mut json_len_buf)?;
-    let json_message_size = u32::from_be_bytes(json_len_buf);
+/// Check the header size read after the total size of a frame, before anything is allocated.
+/// Shared by the blocking and async codecs so both refuse the same frames.
+/// * `header_len_buf` - The 4 bytes following the total size.
+/// * `received` - The number of these bytes received.
+/// * `total_message_size` - The total size announced, already checked.
+/// * `limits` - The limits of the frames accepted.
+/// * Return: the sizes of the header and of the data, or a `FraktError::Frame` if the frame is refused.
+pub(crate) fn check_header_size(
+    header_len_buf: [u8; 4],
+    received: usize,
+    total_message_size: u32,
+    limits: &FrameLimits,
+) -> Result<(usize, usize), FraktError> {
+    check_received(8 + total_message_size as usize, 4, received, 4)?;
+    let header_size = u32::from_be_bytes(header_len_buf);
+    if header_size > limits.max_header_size {
+        return Err(FrameError::Oversize {
+            size: header_size as u64,
+            max: limits.max_header_size,
+        }
+        .into());
+    }
+    let data_message_size = data_size(total_message_size, header_size)?;
+    Ok((header_size as usize, data_message_size as usize))
+}
 
-    if total_message_size < json_message_size {
-        return Err(io::Error::new(
-            io::ErrorKind::Other,
-            "Json message size if bigger than total message size",
-        ));
+/// Fill a buffer from the stream, stopping early only at the end of the stream.
+/// * `stream` - The stream to read from.
+/// * `buf` - The buffer to fill.
+/// * `deadline` - The deadline of the frame, checked before every read.
+/// * `bound` - Called before every read with the time left before the deadline, if any.
+/// * Return: the number of bytes read, smaller than the buffer if the stream ended.
+fn read_full<R: Read + ?Sized>(
+    stream: &mut R,
+    buf: &mut [u8],
+    deadline: &FrameDeadline,
+    bound: Option<ReadBound<R>>,
+) -> Result<usize, FraktError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        if let (Some(remaining), Some(bound)) = (deadline.remaining()?, bound) {
+            bound(stream, remaining)?;
+        }
+        match stream.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => {
+                // a read cut by the time left is a frame received too slowly, not an idle stream
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) {
+                    deadline.remaining()?;
+                }
+                return Err(FraktError::Transport(err));
+            }
+        }
     }
+    Ok(filled)
+}
 
-    let data_message_size = total_message_size - json_message_size;
+/// Check a part of a frame was received entirely.
+/// * `frame_len` - The size of the whole frame, sizes included.
+/// * `offset` - The position of the part in the frame.
+/// * `received` - The number of bytes of the part received.
+/// * `expected` - The size of the part.
+/// * Return: a `FrameError::Truncated` if the part is incomplete.
+pub(crate) fn check_received(
+    frame_len: usize,
+    offset: usize,
+    received: usize,
+    expected: usize,
+) -> Result<(), FrameError> {
+    if received < expected {
+        return Err(FrameError::Truncated {
+            expected: frame_len,
+            received: offset + received,
+        });
+    }
+    Ok(())
+}
 
-    let mut sbuf = vec![0_u8; json_message_size as usize];
-    stream.read(&mut sbuf)?;
-    let s = String::from_utf8_lossy(&sbuf);
+/// Build the beginning of a frame: total size, header size and encoded `Fragment`.
+/// Shared by the blocking and async codecs so both produce the same bytes.
+/// * `fragment` - The `Fragment` to serialize.
+/// * `data_len` - The size of the data following the `Fragment`.
+/// * `encoding` - The encoding of the `Fragment`.
+/// * Return: the header bytes, or a `FraktError` if the `Fragment` cannot be serialized or the frame is too large.
+pub(crate) fn encode_header(
+    fragment: &Fragment,
+    data_len: usize,
+    encoding: HeaderEncoding,
+) -> Result<Vec<u8>, FraktError> {
+    let header = match encoding {
+        HeaderEncoding::Json => fragment_to_string(fragment)
+            .map_err(|err| FraktError::Serialization(format!("JSON header: {}", err)))?
+            .into_bytes(),
+        HeaderEncoding::Cbor => fragment_to_cbor(fragment)
+            .map_err(|err| FraktError::Serialization(format!("CBOR header: {}", err)))?,
+    };
 
-    let fragment_request = string_to_fragment(&s.to_string());
-    let fragment = match fragment_request {
-        Ok(r) => r,
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Message received by server cannot be deserialized",
-            ));
+    let total_len = header.len() as u64 + data_len as u64;
+    if total_len > u32::MAX as u64 {
+        return Err(FrameError::Oversize {
+            size: total_len,
+            max: u32::MAX,
         }
-    };
+        .into());
+    }
+    let header_size = header.len() as u32;
+    let total_message_size = total_len as u32;
 
-    let mut data = vec![0_u8; data_message_size as usize];
-    if let Err(e) = stream.read_exact(&mut data) {
-        return Err(e.into());
+    let mut frame_start = Vec::with_capacity(8 + header.len());
+    frame_start.extend_from_slice(&total_message_size.to_be_bytes());
+    frame_start.extend_from_slice(&header_size.to_be_bytes());
+    frame_start.extend_from_slice(&header);
+
+    Ok(frame_start)
+}
+
+/// Compute the size of the data section from the two sizes read at the beginning of a frame.
+/// * `total_message_size` - The total size announced by the peer.
+/// * `json_message_size` - The header size announced by the peer.
+/// * Return: the data size, or a `FrameError::InconsistentSizes` if the sizes are inconsistent.
+pub(crate) fn data_size(
+    total_message_size: u32,
+    json_message_size: u32,
+) -> Result<u32, FraktError> {
+    if total_message_size < json_message_size {
+        return Err(FrameError::InconsistentSizes {
+            total: total_message_size,
+            header: json_message_size,
+        }
+        .into());
     }
+    Ok(total_message_size - json_message_size)
+}
+
+/// Deserialize the header part of a frame into a `Fragment`.
+/// * `header` - The raw header bytes.
+/// * `encoding` - The encoding of the header.
+/// * Return: the `Fragment`, or a `FraktError::Deserialization` if it cannot be deserialized.
+pub(crate) fn decode_fragment(
+    header: &[u8],
+    encoding: HeaderEncoding,
+) -> Result<Fragment, FraktError> {
+    let fragment = match encoding {
+        HeaderEncoding::Json => match std::str::from_utf8(header) {
+            Ok(json) => string_to_fragment(json).map_err(|err| format!("invalid JSON: {}", err)),
+            Err(err) => Err(format!("JSON header is not UTF-8: {}", err)),
+        },
+        HeaderEncoding::Cbor => {
+            cbor_to_fragment(header).map_err(|err| format!("invalid CBOR: {}", err))
+        }
+    };
 
-    Ok((fragment, data))
+    fragment.map_err(FraktError::Deserialization)
 }
@@ -1 +1,6 @@
+#[cfg(feature = "async")]
+pub mod async_messages_methods;
+pub mod frame;
 pub mod messages_methods;
+pub mod payload;
+mod test_messages_methods;
@@ -0,0 +1,134 @@
+//! # Pixel Payload Encoding
+//!
+//! The data section of a `FragmentResult` carries the id of the task followed by the pixels, as
+//! pairs of big-endian `f32` (zn, count). This module encodes and decodes the pixel part according
+//! to the `PayloadEncoding` negotiated for the session, which may compress it with deflate: the
+//! pairs are highly redundant, so large renders over slow links get much smaller.
+//!
+//! When it is negotiated, a result also carries in its header the CRC32 of its whole data section,
+//! so a truncated or corrupted payload is detected instead of painting garbage pixels.
+
+use std::io::{Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::{
+    complementary_types::pixelintensity::PixelIntensity, errors::frakt_error::FraktError,
+    messages::handshake::PayloadEncoding,
+};
+
+/// Encode the raw pixels of a result for the wire.
+/// * `raw` - the pixels, as pairs of big-endian `f32`
+/// * `encoding` - the payload encoding negotiated for the session
+/// * Return: the bytes to put after the id in the data section, or a `FraktError::Serialization` if the compression failed
+pub fn encode_payload(raw: &[u8], encoding: PayloadEncoding) -> Result<Vec<u8>, FraktError> {
+    match encoding {
+        PayloadEncoding::F32BigEndian => Ok(raw.to_vec()),
+        PayloadEncoding::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+            encoder
+                .write_all(raw)
+                .and_then(|_| encoder.finish())
+                .map_err(|err| FraktError::Serialization(format!("deflate payload: {}", err)))
+        }
+    }
+}
+
+/// Compute the checksum announced in the header of a result.
+/// * `data` - the whole data section, id included, as sent on the wire
+/// * Return: the CRC32 of the data
+pub fn checksum(data: &[u8]) -> u32 {
+    crc32fast::hash(data)
+}
+
+/// Check a data section against the checksum announced in its header.
+/// * `expected` - the checksum of the header, None when the sender did not compute one
+/// * `data` - the whole data section received
+/// * Return: a `FraktError::Validation` if the data does not match the checksum
+pub fn verify_checksum(expected: Option<u32>, data: &[u8]) -> Result<(), FraktError> {
+    match expected {
+        Some(expected) if expected != checksum(data) => Err(FraktError::Validation(format!(
+            "Checksum mismatch: {:08x} announced, {:08x} computed on the {} bytes received",
+            expected,
+            checksum(data),
+            data.len()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Decode the pixel part of a data section back to raw pixels.
+/// * `payload` - the bytes found after the id in the data section
+/// * `encoding` - the payload encoding negotiated for the session
+/// * `max_len` - the maximal size of the raw pixels, protects against payloads inflating without end
+/// * Return: the pixels as pairs of big-endian `f32`, a `FraktError::Deserialization` if the payload is corrupted or a `FraktError::Validation` if it is too big
+pub fn decode_payload(
+    payload: &[u8],
+    encoding: PayloadEncoding,
+    max_len: usize,
+) -> Result<Vec<u8>, FraktError> {
+    let raw = match encoding {
+        PayloadEncoding::F32BigEndian => payload.to_vec(),
+        PayloadEncoding::Deflate => {
+            let mut raw = Vec::new();
+            DeflateDecoder::new(payload)
+                .take(max_len as u64 + 1)
+                .read_to_end(&mut raw)
+                .map_err(|err| FraktError::Deserialization(format!("deflate payload: {}", err)))?;
+            raw
+        }
+    };
+
+    if raw.len() > max_len {
+        return Err(FraktError::Validation(format!(
+            "Payload bigger than the {} bytes expected",
+            max_len
+        )));
+    }
+    Ok(raw)
+}
+
+/// Encode pixels for the wire: pairs of big-endian `f32` (zn, count), then the payload encoding.
+/// * `pixels` - the pixels of a result
+/// * `encoding` - the payload encoding negotiated for the session
+/// * Return: the bytes to put after the id in the data section, or a `FraktError::Serialization` if the compression failed
+pub fn encode_pixels(
+    pixels: &[PixelIntensity],
+    encoding: PayloadEncoding,
+) -> Result<Vec<u8>, FraktError> {
+    let mut raw = Vec::with_capacity(pixels.len() * 8);
+    for pixel in pixels {
+        raw.extend_from_slice(&pixel.zn.to_be_bytes());
+        raw.extend_from_slice(&pixel.count.to_be_bytes());
+    }
+    encode_payload(&raw, encoding)
+}
+
+/// Decode the pixel part of a data section, which must hold exactly the pixels announced.
+/// * `payload` - the bytes found after the id in the data section
+/// * `encoding` - the payload encoding negotiated for the session
+/// * `pixel_count` - the number of pixels announced in the header
+/// * Return: the pixels, or a `FraktError` if the payload is corrupted or does not hold `pixel_count` pixels
+pub fn decode_pixels(
+    payload: &[u8],
+    encoding: PayloadEncoding,
+    pixel_count: u32,
+) -> Result<Vec<PixelIntensity>, FraktError> {
+    let raw = decode_payload(payload, encoding, pixel_count as usize * 8)?;
+    if raw.len() != pixel_count as usize * 8 {
+        return Err(FraktError::Validation(format!(
+            "{} bytes of pixels received, {} pixels announced",
+            raw.len(),
+            pixel_count
+        )));
+    }
+    Ok(raw
+        .chunks_exact(8)
+        .map(|pixel| {
+            PixelIntensity::new(
+                f32::from_be_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]),
+                f32::from_be_bytes([pixel[4], pixel[5], pixel[6], pixel[7]]),
+            )
+        })
+        .collect())
+}
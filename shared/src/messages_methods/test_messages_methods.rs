@@ -0,0 +1,513 @@
+#[cfg(test)]
+use std::{
+    io::{self, Cursor, Read, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(test)]
+use crate::complementary_types::pixelintensity::PixelIntensity;
+#[cfg(test)]
+use crate::complementary_types::{
+    point::Point, range::Range, resolution::Resolution, u8data::U8Data,
+};
+#[cfg(test)]
+use crate::errors::frakt_error::FraktError;
+#[cfg(test)]
+use crate::fractal_implementation::fractal::FractalDescriptor;
+#[cfg(test)]
+use crate::fractal_types::mandelbrot::Mandelbrot;
+#[cfg(test)]
+use crate::messages::{
+    handshake::{HeaderEncoding, PayloadEncoding},
+    message::{
+        Fragment, FragmentBatch, FragmentRequest, FragmentResult, FragmentResultChunk, FragmentTask,
+    },
+};
+#[cfg(test)]
+use crate::messages_methods::frame::{FrameError, FrameLimits};
+#[cfg(test)]
+use crate::messages_methods::messages_methods::{
+    read_message, read_message_with, read_message_with_deadline, read_message_with_limits,
+    send_message, send_message_with,
+};
+#[cfg(test)]
+use crate::messages_methods::payload::{
+    checksum, decode_payload, decode_pixels, encode_payload, encode_pixels, verify_checksum,
+};
+
+#[cfg(test)]
+fn sample_request() -> Fragment {
+    Fragment::FragmentRequest(FragmentRequest::new(String::from("test_worker"), 10))
+}
+
+#[test]
+fn test_frame_layout() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[1, 2, 3]).unwrap();
+
+    let total_size = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+    let json_size = u32::from_be_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]);
+    assert_eq!(total_size as usize, buffer.len() - 8);
+    assert_eq!(total_size - json_size, 3);
+    assert_eq!(&buffer[buffer.len() - 3..], &[1, 2, 3]);
+}
+
+#[test]
+fn test_round_trip_over_buffer() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[4, 5, 6, 7]).unwrap();
+    send_message(&mut buffer, sample_request(), &[]).unwrap();
+
+    let mut cursor = Cursor::new(buffer);
+    let (fragment, data) = read_message(&mut cursor).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![4, 5, 6, 7]);
+
+    let (fragment, data) = read_message(&mut cursor).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert!(data.is_empty());
+}
+
+#[test]
+fn test_read_truncated_frame() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[1, 2, 3]).unwrap();
+    let frame_len = buffer.len();
+    buffer.truncate(frame_len - 1);
+
+    let err = read_message(&mut Cursor::new(buffer)).unwrap_err();
+    assert!(matches!(
+        err,
+        FraktError::Frame(FrameError::Truncated { expected, received })
+            if expected == frame_len && received == frame_len - 1
+    ));
+}
+
+#[test]
+fn test_closed_stream_is_not_a_frame_error() {
+    let err = read_message(&mut Cursor::new(Vec::new())).unwrap_err();
+    assert!(err.is_disconnection());
+}
+
+#[test]
+fn test_oversize_frame_is_refused() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[0; 64]).unwrap();
+
+    let err = read_message_with_limits(
+        &mut Cursor::new(buffer),
+        HeaderEncoding::Json,
+        &FrameLimits::new(32),
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        FraktError::Frame(FrameError::Oversize { max: 32, .. })
+    ));
+
+    // sizes announced by the peer are checked before anything is allocated
+    let mut hostile = Vec::new();
+    hostile.extend_from_slice(&u32::MAX.to_be_bytes());
+    hostile.extend_from_slice(&u32::MAX.to_be_bytes());
+    let err = read_message(&mut Cursor::new(hostile)).unwrap_err();
+    assert!(matches!(
+        err,
+        FraktError::Frame(FrameError::Oversize { .. })
+    ));
+}
+
+#[test]
+fn test_malformed_header_is_refused() {
+    let json = b"{\"FragmentRequest\":";
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(json);
+
+    let err = read_message(&mut Cursor::new(buffer)).unwrap_err();
+    assert!(matches!(err, FraktError::Deserialization(_)));
+
+    let mut inconsistent = Vec::new();
+    inconsistent.extend_from_slice(&1_u32.to_be_bytes());
+    inconsistent.extend_from_slice(&2_u32.to_be_bytes());
+    let err = read_message(&mut Cursor::new(inconsistent)).unwrap_err();
+    assert!(matches!(
+        err,
+        FraktError::Frame(FrameError::InconsistentSizes {
+            total: 1,
+            header: 2
+        })
+    ));
+}
+
+/// A peer sending its frame one byte at a time
+#[cfg(test)]
+struct SlowPeer(Cursor<Vec<u8>>);
+
+#[cfg(test)]
+impl Read for SlowPeer {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        thread::sleep(Duration::from_millis(5));
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn test_slow_frame_times_out() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[0; 16]).unwrap();
+
+    let limits = FrameLimits {
+        frame_timeout: Some(Duration::from_millis(50)),
+        ..FrameLimits::default()
+    };
+    let err = read_message_with_limits(
+        &mut SlowPeer(Cursor::new(buffer.clone())),
+        HeaderEncoding::Json,
+        &limits,
+    )
+    .unwrap_err();
+    assert!(matches!(err, FraktError::Frame(FrameError::Timeout(_))));
+
+    // without frame timeout, the same peer is only slow
+    let (fragment, _) = read_message_with_limits(
+        &mut SlowPeer(Cursor::new(buffer)),
+        HeaderEncoding::Json,
+        &FrameLimits::default(),
+    )
+    .unwrap();
+    assert_eq!(fragment, sample_request());
+}
+
+/// to connect a socket sending `frame` in two parts, waiting `stall` between them
+#[cfg(test)]
+fn dripping_peer(frame: Vec<u8>, stall: Duration) -> TcpStream {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut peer = TcpStream::connect(address).unwrap();
+        peer.write_all(&frame[..10]).unwrap();
+        thread::sleep(stall);
+        let _ = peer.write_all(&frame[10..]);
+    });
+    listener.accept().unwrap().0
+}
+
+#[test]
+fn test_stalled_frame_is_cut_at_the_deadline() {
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &[0; 16]).unwrap();
+    let limits = FrameLimits {
+        frame_timeout: Some(Duration::from_millis(100)),
+        ..FrameLimits::default()
+    };
+    let idle_timeout = Some(Duration::from_secs(5));
+
+    // the peer stalls in the middle of the frame, far longer than the frame timeout
+    let mut stream = dripping_peer(buffer.clone(), Duration::from_secs(2));
+    stream.set_read_timeout(idle_timeout).unwrap();
+    let start = Instant::now();
+    let err = read_message_with_deadline(&mut stream, HeaderEncoding::Json, &limits).unwrap_err();
+    assert!(matches!(err, FraktError::Frame(FrameError::Timeout(_))));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    // the idle timeout of the stream is given back
+    assert_eq!(stream.read_timeout().unwrap(), idle_timeout);
+
+    // a short stall stays within the deadline
+    let mut stream = dripping_peer(buffer, Duration::from_millis(10));
+    stream.set_read_timeout(idle_timeout).unwrap();
+    let (fragment, data) =
+        read_message_with_deadline(&mut stream, HeaderEncoding::Json, &limits).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![0; 16]);
+}
+
+/// A stream delivering at most 3 bytes per read, and interrupted every other read
+#[cfg(test)]
+struct PartialReads {
+    inner: Cursor<Vec<u8>>,
+    interrupted: bool,
+}
+
+#[cfg(test)]
+impl Read for PartialReads {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.interrupted = !self.interrupted;
+        if self.interrupted {
+            return Err(io::Error::from(io::ErrorKind::Interrupted));
+        }
+        let len = buf.len().min(3);
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn test_partial_reads_give_whole_frames() {
+    let data: Vec<u8> = (0..=255).collect();
+    let mut buffer = Vec::new();
+    send_message(&mut buffer, sample_request(), &data).unwrap();
+    send_message(&mut buffer, sample_request(), &[1, 2]).unwrap();
+
+    let mut stream = PartialReads {
+        inner: Cursor::new(buffer),
+        interrupted: false,
+    };
+    let (fragment, received) = read_message(&mut stream).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(received, data);
+
+    // the next frame starts exactly where the first one ended
+    let (fragment, received) = read_message(&mut stream).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(received, vec![1, 2]);
+}
+
+#[test]
+fn test_round_trip_with_binary_header() {
+    let mut json_buffer = Vec::new();
+    send_message(&mut json_buffer, sample_request(), &[1]).unwrap();
+
+    let mut cbor_buffer = Vec::new();
+    send_message_with(
+        &mut cbor_buffer,
+        sample_request(),
+        &[1],
+        HeaderEncoding::Cbor,
+    )
+    .unwrap();
+    assert!(cbor_buffer.len() < json_buffer.len());
+
+    let (fragment, data) =
+        read_message_with(&mut Cursor::new(cbor_buffer.clone()), HeaderEncoding::Cbor).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![1]);
+
+    // a binary header is not valid JSON
+    assert!(read_message(&mut Cursor::new(cbor_buffer)).is_err());
+}
+
+#[test]
+fn test_deflate_payload_round_trip() {
+    let raw: Vec<u8> = (0..1000_u32)
+        .flat_map(|i| ((i % 10) as f32).to_be_bytes())
+        .collect();
+
+    let compressed = encode_payload(&raw, PayloadEncoding::Deflate).unwrap();
+    assert!(compressed.len() < raw.len());
+    assert_eq!(
+        decode_payload(&compressed, PayloadEncoding::Deflate, raw.len()).unwrap(),
+        raw
+    );
+
+    // a payload inflating over the expected size is refused
+    assert!(decode_payload(&compressed, PayloadEncoding::Deflate, raw.len() - 1).is_err());
+}
+
+#[test]
+fn test_pixels_round_trip_with_exact_count() {
+    let pixels = vec![
+        PixelIntensity::new(0.5, 12.0),
+        PixelIntensity::new(1.25, 3.0),
+    ];
+    for encoding in [PayloadEncoding::F32BigEndian, PayloadEncoding::Deflate] {
+        let payload = encode_pixels(&pixels, encoding).unwrap();
+        let decoded = decode_pixels(&payload, encoding, 2).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert_eq!((decoded[1].zn, decoded[1].count), (1.25, 3.0));
+        assert!(decode_pixels(&payload, encoding, 3).is_err());
+        assert!(decode_pixels(&payload, encoding, 1).is_err());
+    }
+}
+
+#[test]
+fn test_batch_round_trip_keeps_id_offsets() {
+    let task = |offset| {
+        FragmentTask::new(
+            U8Data::new(offset, 16),
+            FractalDescriptor::Mandelbrot(Mandelbrot {}),
+            64,
+            Resolution::new(300, 300),
+            Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+        )
+    };
+    let ids: Vec<u8> = (0..32).collect();
+
+    let mut buffer = Vec::new();
+    let batch = FragmentBatch::new(vec![task(0), task(16)]);
+    send_message(&mut buffer, Fragment::FragmentBatch(batch), &ids).unwrap();
+
+    match read_message(&mut Cursor::new(buffer)).unwrap() {
+        (Fragment::FragmentBatch(batch), data) => {
+            assert_eq!(batch.tasks, vec![task(0), task(16)]);
+            let second = batch.tasks[1].id;
+            let start = second.offset as usize;
+            assert_eq!(data[start..start + second.count as usize], ids[16..]);
+        }
+        (fragment, _) => panic!("unexpected fragment {:?}", fragment),
+    }
+}
+
+#[test]
+fn test_result_chunks_cover_the_task() {
+    let task = FragmentTask::new(
+        U8Data::new(0, 16),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(10, 10),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+    );
+    let first = FragmentResultChunk::create(&task, 0, 60);
+    assert!(!first.last);
+    assert_eq!(first.pixels.offset, 16);
+    let second = FragmentResultChunk::create(&task, 60, 40);
+    assert!(second.last);
+
+    let ids = vec![7; 16];
+    let mut buffer = Vec::new();
+    send_message(
+        &mut buffer,
+        Fragment::FragmentResultChunk(second.clone()),
+        &ids,
+    )
+    .unwrap();
+    let (fragment, data) = read_message(&mut Cursor::new(buffer)).unwrap();
+    assert_eq!(fragment, Fragment::FragmentResultChunk(second));
+    assert_eq!(data, ids);
+}
+
+#[test]
+fn test_checksum_detects_corrupted_data() {
+    let task = FragmentTask::new(
+        U8Data::new(0, 4),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(2, 2),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+    );
+    let datas: Vec<u8> = (0..36).collect();
+    let mut result = FragmentResult::create(&task);
+    result.checksum = Some(checksum(&datas));
+
+    let mut buffer = Vec::new();
+    send_message(
+        &mut buffer,
+        Fragment::FragmentResult(result.clone()),
+        &datas,
+    )
+    .unwrap();
+    let (fragment, received) = read_message(&mut Cursor::new(buffer)).unwrap();
+    assert_eq!(fragment, Fragment::FragmentResult(result.clone()));
+    assert!(verify_checksum(result.checksum, &received).is_ok());
+
+    let mut corrupted = received.clone();
+    corrupted[20] ^= 1;
+    assert!(verify_checksum(result.checksum, &corrupted).is_err());
+    assert!(verify_checksum(result.checksum, &received[..35]).is_err());
+    // results of workers not computing a checksum are accepted as before
+    assert!(verify_checksum(None, &corrupted).is_ok());
+    let legacy = r#"{"FragmentResult":{"id":{"offset":0,"count":4},"resolution":{"nx":2,"ny":2},"range":{"min":{"x":-2.0,"y":-1.0},"max":{"x":1.0,"y":1.0}},"pixels":{"offset":4,"count":4}}}"#;
+    let legacy: Fragment = serde_json::from_str(legacy).unwrap();
+    assert_eq!(
+        legacy,
+        Fragment::FragmentResult(FragmentResult::create(&task))
+    );
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_codec_matches_blocking_codec() {
+    use crate::messages_methods::async_messages_methods;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let mut async_buffer = Vec::new();
+    runtime
+        .block_on(async_messages_methods::send_message(
+            &mut async_buffer,
+            sample_request(),
+            &[8, 9],
+        ))
+        .unwrap();
+
+    let mut blocking_buffer = Vec::new();
+    send_message(&mut blocking_buffer, sample_request(), &[8, 9]).unwrap();
+    assert_eq!(async_buffer, blocking_buffer);
+
+    let (fragment, data) = runtime
+        .block_on(async_messages_methods::read_message(
+            &mut async_buffer.as_slice(),
+        ))
+        .unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![8, 9]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_reader_skips_a_frame_with_an_invalid_header() {
+    use crate::messages_methods::async_messages_methods;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    // a frame whose header cannot be deserialized, with data, then a valid frame
+    let json = b"{\"FragmentRequest\":";
+    let mut buffer = Vec::new();
+    buffer.extend_from_slice(&(json.len() as u32 + 3).to_be_bytes());
+    buffer.extend_from_slice(&(json.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(json);
+    buffer.extend_from_slice(&[1, 2, 3]);
+    send_message(&mut buffer, sample_request(), &[8, 9]).unwrap();
+
+    let mut stream = buffer.as_slice();
+    let err = runtime
+        .block_on(async_messages_methods::read_message(&mut stream))
+        .unwrap_err();
+    assert!(matches!(err, FraktError::Deserialization(_)));
+    let (fragment, data) = runtime
+        .block_on(async_messages_methods::read_message(&mut stream))
+        .unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![8, 9]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn test_async_stalled_frame_times_out() {
+    use crate::messages_methods::async_messages_methods;
+    use tokio::io::AsyncWriteExt;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    let mut frame = Vec::new();
+    send_message(&mut frame, sample_request(), &[8, 9]).unwrap();
+    let limits = FrameLimits {
+        frame_timeout: Some(Duration::from_millis(50)),
+        ..FrameLimits::default()
+    };
+
+    let err = runtime.block_on(async {
+        let (mut peer, mut stream) = tokio::io::duplex(1024);
+        // the peer stops in the middle of its frame, and stays connected
+        peer.write_all(&frame[..frame.len() - 1]).await.unwrap();
+        let err = async_messages_methods::read_message_with_limits(
+            &mut stream,
+            HeaderEncoding::Json,
+            &limits,
+        )
+        .await
+        .unwrap_err();
+        drop(peer);
+        err
+    });
+    assert!(matches!(err, FraktError::Frame(FrameError::Timeout(_))));
+}
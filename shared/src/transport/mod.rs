@@ -0,0 +1,6 @@
+pub mod stream;
+mod test_stream;
+mod test_tls;
+mod test_websocket;
+pub mod tls;
+pub mod websocket;
@@ -0,0 +1,137 @@
+//! # Stream
+//!
+//! The `Stream` enum is the connection between a worker and the server, whatever the transport
+//! below it. It implements `Read` and `Write`, so the framing of `messages_methods` works the same
+//! over plain TCP, over TLS, inside WebSocket messages and, on Unix, over a Unix domain socket.
+//!
+//! A worker on the same machine as the server reaches its Unix domain socket with an address
+//! starting with `UNIX_PREFIX`, for example `unix:/tmp/frakt.sock`.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use rustls::{ClientConnection, ServerConnection, StreamOwned};
+
+use super::websocket::WebSocketStream;
+use crate::messages_methods::frame::ReadTimeout;
+
+/// Prefix of the addresses of Unix domain sockets, followed by the path of the socket
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// to get the path of a Unix domain socket from an address
+/// * `address` - the address of the server, for example `unix:/tmp/frakt.sock` or `localhost`
+/// * Return: `Option<&str>` - the path of the socket, None if the address is not a Unix domain socket
+pub fn unix_socket_path(address: &str) -> Option<&str> {
+    address
+        .strip_prefix(UNIX_PREFIX)
+        .filter(|path| !path.is_empty())
+}
+
+/// A connection between a worker and the server
+pub enum Stream {
+    /// Plain TCP
+    Tcp(TcpStream),
+    /// TLS, seen from the worker
+    TlsClient(Box<StreamOwned<ClientConnection, TcpStream>>),
+    /// TLS, seen from the server
+    TlsServer(Box<StreamOwned<ServerConnection, TcpStream>>),
+    /// WebSocket, over plain TCP or TLS
+    WebSocket(Box<WebSocketStream>),
+    /// Unix domain socket, for workers on the same machine as the server
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl Stream {
+    /// Set the read timeout of the underlying socket
+    /// * `timeout` - the timeout, None to block forever
+    /// * Return: an `io::Error` if the timeout cannot be set
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.set_read_timeout(timeout),
+            Stream::TlsClient(stream) => stream.sock.set_read_timeout(timeout),
+            Stream::TlsServer(stream) => stream.sock.set_read_timeout(timeout),
+            Stream::WebSocket(stream) => stream.get_ref().set_read_timeout(timeout),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.set_read_timeout(timeout),
+        }
+    }
+
+    /// Get the read timeout of the underlying socket
+    /// * Return: the timeout, None if reads block forever
+    pub fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        match self {
+            Stream::Tcp(stream) => stream.read_timeout(),
+            Stream::TlsClient(stream) => stream.sock.read_timeout(),
+            Stream::TlsServer(stream) => stream.sock.read_timeout(),
+            Stream::WebSocket(stream) => stream.get_ref().read_timeout(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read_timeout(),
+        }
+    }
+
+    /// to know if the connection is encrypted
+    /// * Return: `bool` - true for TLS connections, WebSocket ones included
+    pub fn is_tls(&self) -> bool {
+        match self {
+            Stream::Tcp(_) => false,
+            Stream::TlsClient(_) | Stream::TlsServer(_) => true,
+            Stream::WebSocket(stream) => stream.get_ref().is_tls(),
+            #[cfg(unix)]
+            Stream::Unix(_) => false,
+        }
+    }
+}
+
+impl ReadTimeout for Stream {
+    fn read_timeout(&self) -> io::Result<Option<Duration>> {
+        Stream::read_timeout(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        Stream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.read(buf),
+            Stream::TlsClient(stream) => stream.read(buf),
+            Stream::TlsServer(stream) => stream.read(buf),
+            Stream::WebSocket(stream) => stream.read(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Tcp(stream) => stream.write(buf),
+            Stream::TlsClient(stream) => stream.write(buf),
+            Stream::TlsServer(stream) => stream.write(buf),
+            Stream::WebSocket(stream) => stream.write(buf),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Tcp(stream) => stream.flush(),
+            Stream::TlsClient(stream) => stream.flush(),
+            Stream::TlsServer(stream) => stream.flush(),
+            Stream::WebSocket(stream) => stream.flush(),
+            #[cfg(unix)]
+            Stream::Unix(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+use std::{thread, time::Duration};
+
+#[cfg(all(test, unix))]
+use std::os::unix::net::{UnixListener, UnixStream};
+
+#[cfg(test)]
+use crate::messages::message::{Fragment, FragmentRequest};
+#[cfg(test)]
+use crate::messages_methods::messages_methods::{read_message, send_message};
+#[cfg(test)]
+use crate::transport::stream::{unix_socket_path, Stream};
+
+#[cfg(test)]
+fn sample_request() -> Fragment {
+    Fragment::FragmentRequest(FragmentRequest::new(String::from("local_worker"), 1))
+}
+
+#[test]
+fn test_unix_socket_path() {
+    assert_eq!(
+        unix_socket_path("unix:/tmp/frakt.sock"),
+        Some("/tmp/frakt.sock")
+    );
+    assert_eq!(unix_socket_path("unix:"), None);
+    assert_eq!(unix_socket_path("localhost"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_round_trip_over_unix_socket() {
+    let path = std::env::temp_dir().join(format!("frakt_test_{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    let server_thread = thread::spawn(move || {
+        let (unix, _) = listener.accept().unwrap();
+        let mut stream = Stream::Unix(unix);
+        let (fragment, data) = read_message(&mut stream).unwrap();
+        send_message(&mut stream, fragment, &data).unwrap();
+    });
+
+    let mut stream = Stream::Unix(UnixStream::connect(&path).unwrap());
+    assert!(!stream.is_tls());
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    assert_eq!(stream.read_timeout().unwrap(), Some(Duration::from_secs(5)));
+
+    send_message(&mut stream, sample_request(), &[7, 8]).unwrap();
+    let (fragment, data) = read_message(&mut stream).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![7, 8]);
+
+    server_thread.join().unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,114 @@
+#[cfg(test)]
+use std::{fs, net::TcpListener, net::TcpStream, path::PathBuf, thread};
+
+#[cfg(test)]
+use crate::messages::message::{Fragment, FragmentRequest};
+#[cfg(test)]
+use crate::messages_methods::messages_methods::{read_message, send_message};
+#[cfg(test)]
+use crate::transport::tls::{accept, client_config, connect, server_config};
+
+/// to write a self-signed certificate for localhost and its key in the temporary directory
+/// * `name` - the prefix of the files
+/// * Return: the paths of the certificate and of the key
+#[cfg(test)]
+fn self_signed(name: &str) -> (String, String) {
+    let certified = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+    let directory: PathBuf = std::env::temp_dir().join("frakt_test_tls");
+    fs::create_dir_all(&directory).unwrap();
+
+    let cert_path = directory.join(format!("{}_{}_cert.pem", name, std::process::id()));
+    let key_path = directory.join(format!("{}_{}_key.pem", name, std::process::id()));
+    fs::write(&cert_path, certified.cert.pem()).unwrap();
+    fs::write(&key_path, certified.key_pair.serialize_pem()).unwrap();
+
+    (
+        cert_path.to_string_lossy().into_owned(),
+        key_path.to_string_lossy().into_owned(),
+    )
+}
+
+#[cfg(test)]
+fn sample_request() -> Fragment {
+    Fragment::FragmentRequest(FragmentRequest::new(String::from("tls_worker"), 10))
+}
+
+#[test]
+fn test_round_trip_over_tls() {
+    let (cert, key) = self_signed("server");
+    let server = server_config(&cert, &key, None).unwrap();
+    let client = client_config(Some(&cert), None).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = accept(tcp, &server).unwrap();
+        let (fragment, data) = read_message(&mut stream).unwrap();
+        send_message(&mut stream, fragment, &data).unwrap();
+    });
+
+    let mut stream = connect(TcpStream::connect(address).unwrap(), &client, "localhost").unwrap();
+    assert!(stream.is_tls());
+    send_message(&mut stream, sample_request(), &[1, 2, 3]).unwrap();
+    let (fragment, data) = read_message(&mut stream).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![1, 2, 3]);
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_untrusted_server_is_refused() {
+    let (cert, key) = self_signed("untrusted_server");
+    let (other_cert, _) = self_signed("other_ca");
+    let server = server_config(&cert, &key, None).unwrap();
+    let client = client_config(Some(&other_cert), None).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        assert!(accept(tcp, &server).is_err());
+    });
+
+    assert!(connect(TcpStream::connect(address).unwrap(), &client, "localhost").is_err());
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_client_certificate_required() {
+    let (cert, key) = self_signed("mtls_server");
+    let (client_cert, client_key) = self_signed("mtls_client");
+    let server = server_config(&cert, &key, Some(&client_cert)).unwrap();
+    let anonymous = client_config(Some(&cert), None).unwrap();
+    let identified = client_config(Some(&cert), Some((&client_cert, &client_key))).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        assert!(accept(tcp, &server).is_err());
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = accept(tcp, &server).unwrap();
+        read_message(&mut stream).unwrap();
+    });
+
+    // with TLS 1.3 the client only learns it was refused when it reads
+    let refused = connect(
+        TcpStream::connect(address).unwrap(),
+        &anonymous,
+        "localhost",
+    )
+    .and_then(|mut stream| Ok(read_message(&mut stream)?));
+    assert!(refused.is_err());
+
+    let mut stream = connect(
+        TcpStream::connect(address).unwrap(),
+        &identified,
+        "localhost",
+    )
+    .unwrap();
+    send_message(&mut stream, sample_request(), &[]).unwrap();
+    server_thread.join().unwrap();
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+use std::{io::Cursor, net::TcpListener, net::TcpStream, thread};
+
+#[cfg(test)]
+use tungstenite::Message;
+
+#[cfg(test)]
+use crate::messages::message::{Fragment, FragmentRequest};
+#[cfg(test)]
+use crate::messages_methods::messages_methods::{read_message, send_message};
+#[cfg(test)]
+use crate::transport::stream::Stream;
+#[cfg(test)]
+use crate::transport::websocket::{accept, connect};
+
+#[cfg(test)]
+fn sample_request() -> Fragment {
+    Fragment::FragmentRequest(FragmentRequest::new(String::from("browser_worker"), 10))
+}
+
+#[test]
+fn test_round_trip_over_websocket() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = accept(Stream::Tcp(tcp)).unwrap();
+        let (fragment, data) = read_message(&mut stream).unwrap();
+        send_message(&mut stream, fragment, &data).unwrap();
+    });
+
+    let tcp = TcpStream::connect(address).unwrap();
+    let url = format!("ws://{}/", address);
+    let mut stream = connect(Stream::Tcp(tcp), &url).unwrap();
+    assert!(!stream.is_tls());
+    send_message(&mut stream, sample_request(), &[1, 2, 3]).unwrap();
+    let (fragment, data) = read_message(&mut stream).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![1, 2, 3]);
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_frames_in_binary_messages() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = accept(Stream::Tcp(tcp)).unwrap();
+        send_message(&mut stream, sample_request(), &[4, 5]).unwrap();
+
+        // a frame split over two messages is read whole
+        let (fragment, data) = read_message(&mut stream).unwrap();
+        assert_eq!(fragment, sample_request());
+        assert_eq!(data, vec![6]);
+
+        // text messages do not carry frames
+        assert!(read_message(&mut stream).is_err());
+    });
+
+    // a client knowing nothing of the protocol but WebSocket, as a browser
+    let tcp = TcpStream::connect(address).unwrap();
+    let (mut socket, _) = tungstenite::client(format!("ws://{}/", address), tcp).unwrap();
+
+    // every frame is sent in its own binary message
+    let frame = match socket.read().unwrap() {
+        Message::Binary(frame) => frame,
+        message => panic!("binary message expected, {:?} received", message),
+    };
+    let (fragment, data) = read_message(&mut Cursor::new(frame.clone())).unwrap();
+    assert_eq!(fragment, sample_request());
+    assert_eq!(data, vec![4, 5]);
+
+    let mut frame = Vec::new();
+    send_message(&mut frame, sample_request(), &[6]).unwrap();
+    let second_half = frame.split_off(frame.len() / 2);
+    socket.send(Message::Binary(frame)).unwrap();
+    socket.send(Message::Binary(second_half)).unwrap();
+    socket
+        .send(Message::Text(String::from("not a frame")))
+        .unwrap();
+
+    server_thread.join().unwrap();
+}
+
+#[test]
+fn test_closed_websocket_is_a_disconnection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server_thread = thread::spawn(move || {
+        let (tcp, _) = listener.accept().unwrap();
+        let mut stream = accept(Stream::Tcp(tcp)).unwrap();
+        let err = read_message(&mut stream).err().unwrap();
+        assert!(err.is_disconnection());
+    });
+
+    let tcp = TcpStream::connect(address).unwrap();
+    let (mut socket, _) = tungstenite::client(format!("ws://{}/", address), tcp).unwrap();
+    socket.close(None).unwrap();
+    server_thread.join().unwrap();
+}
@@ -0,0 +1,168 @@
+//! # TLS
+//!
+//! Helpers to secure the connection between a worker and the server with TLS (rustls).
+//!
+//! The worker always verifies the certificate of the server: against the CA given with
+//! `client_config`, or against the usual public roots when there is none. The server may require
+//! the workers to present a certificate signed by a given CA.
+//!
+//! Certificates and keys are read from PEM files, so self-signed certificates generated with
+//! `openssl` are enough to try it locally.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::net::TcpStream;
+//! use shared_lib::transport::tls::{client_config, connect};
+//!
+//! fn connect_securely() -> std::io::Result<()> {
+//!     let config = client_config(Some("certs/ca.pem"), None)?;
+//!     let tcp = TcpStream::connect("127.0.0.1:8787")?;
+//!     let _stream = connect(tcp, &config, "localhost")?;
+//!     Ok(())
+//! }
+//! ```
+
+use std::{io, net::TcpStream, sync::Arc};
+
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+    server::WebPkiClientVerifier,
+    ClientConnection, RootCertStore, ServerConnection, StreamOwned,
+};
+
+pub use rustls::{ClientConfig, ServerConfig};
+
+use super::stream::Stream;
+
+/// to load the certificates of a PEM file
+/// * `path` - the path of the PEM file
+/// * Return: the certificates of the file, or an `io::Error` if the file cannot be read or holds no certificate
+pub fn load_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, io::Error> {
+    let certificates = CertificateDer::pem_file_iter(path)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(|err| pem_error(path, err))?;
+    if certificates.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("No certificate found in {}", path),
+        ));
+    }
+    Ok(certificates)
+}
+
+/// to load the private key of a PEM file
+/// * `path` - the path of the PEM file
+/// * Return: the first private key of the file, or an `io::Error` if there is none
+pub fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>, io::Error> {
+    PrivateKeyDer::from_pem_file(path).map_err(|err| pem_error(path, err))
+}
+
+/// to build the TLS configuration of the server
+/// * `cert_path` - the PEM file of the certificate chain of the server
+/// * `key_path` - the PEM file of the private key of the server
+/// * `client_ca_path` - the PEM file of the CA signing the certificates of the workers, if the workers must present one
+/// * Return: the configuration, or an `io::Error` if a file is invalid
+pub fn server_config(
+    cert_path: &str,
+    key_path: &str,
+    client_ca_path: Option<&str>,
+) -> Result<Arc<ServerConfig>, io::Error> {
+    let certificates = load_certificates(cert_path)?;
+    let key = load_private_key(key_path)?;
+
+    let builder = match client_ca_path {
+        Some(client_ca_path) => {
+            let roots = root_store(&load_certificates(client_ca_path)?)?;
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+            ServerConfig::builder().with_client_cert_verifier(verifier)
+        }
+        None => ServerConfig::builder().with_no_client_auth(),
+    };
+
+    let config = builder
+        .with_single_cert(certificates, key)
+        .map_err(tls_error)?;
+    Ok(Arc::new(config))
+}
+
+/// to build the TLS configuration of a worker
+/// * `ca_path` - the PEM file of the CA signing the certificate of the server, the public roots are used if None
+/// * `client_identity` - the PEM files of the certificate chain and of the private key of the worker, if the server requires one
+/// * Return: the configuration, or an `io::Error` if a file is invalid
+pub fn client_config(
+    ca_path: Option<&str>,
+    client_identity: Option<(&str, &str)>,
+) -> Result<Arc<ClientConfig>, io::Error> {
+    let roots = match ca_path {
+        Some(ca_path) => root_store(&load_certificates(ca_path)?)?,
+        None => RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        },
+    };
+    let builder = ClientConfig::builder().with_root_certificates(roots);
+
+    let config = match client_identity {
+        Some((cert_path, key_path)) => builder
+            .with_client_auth_cert(load_certificates(cert_path)?, load_private_key(key_path)?)
+            .map_err(tls_error)?,
+        None => builder.with_no_client_auth(),
+    };
+    Ok(Arc::new(config))
+}
+
+/// to secure a connection accepted by the server, the TLS handshake is done before returning
+/// * `tcp` - the connection accepted
+/// * `config` - the TLS configuration of the server
+/// * Return: the secured `Stream`, or an `io::Error` if the handshake failed
+pub fn accept(mut tcp: TcpStream, config: &Arc<ServerConfig>) -> Result<Stream, io::Error> {
+    let mut connection = ServerConnection::new(config.clone()).map_err(tls_error)?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut tcp)?;
+    }
+    Ok(Stream::TlsServer(Box::new(StreamOwned::new(
+        connection, tcp,
+    ))))
+}
+
+/// to secure a connection opened by a worker, the TLS handshake is done before returning
+/// * `tcp` - the connection opened to the server
+/// * `config` - the TLS configuration of the worker
+/// * `server_name` - the name the certificate of the server must be valid for
+/// * Return: the secured `Stream`, or an `io::Error` if the handshake failed or the certificate of the server is not trusted
+pub fn connect(
+    mut tcp: TcpStream,
+    config: &Arc<ClientConfig>,
+    server_name: &str,
+) -> Result<Stream, io::Error> {
+    let server_name = ServerName::try_from(server_name.to_string())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err.to_string()))?;
+    let mut connection = ClientConnection::new(config.clone(), server_name).map_err(tls_error)?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut tcp)?;
+    }
+    Ok(Stream::TlsClient(Box::new(StreamOwned::new(
+        connection, tcp,
+    ))))
+}
+
+fn root_store(certificates: &[CertificateDer<'static>]) -> Result<RootCertStore, io::Error> {
+    let mut roots = RootCertStore::empty();
+    for certificate in certificates {
+        roots.add(certificate.clone()).map_err(tls_error)?;
+    }
+    Ok(roots)
+}
+
+fn pem_error(path: &str, err: rustls::pki_types::pem::Error) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Cannot read {}: {}", path, err),
+    )
+}
+
+fn tls_error(err: rustls::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
@@ -0,0 +1,153 @@
+//! # WebSocket
+//!
+//! Helpers to carry the protocol inside WebSocket messages (tungstenite), so workers running where
+//! only WebSocket is available, such as a browser, can join a server.
+//!
+//! The frames are the same as over TCP (see `messages_methods`): each frame is sent in one binary
+//! WebSocket message, holding the sizes, the `Fragment` header and the data. On reception the
+//! binary messages are read as a stream of bytes, so a peer splitting a frame over several messages
+//! is understood too. Text messages are refused, the WebSocket pings are answered transparently.
+//!
+//! The WebSocket handshake is done over a `Stream`, so it works over plain TCP (`ws://`) as over
+//! TLS (`wss://`).
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::net::TcpStream;
+//! use shared_lib::messages::message::{Fragment, FragmentRequest};
+//! use shared_lib::messages_methods::messages_methods::send_message;
+//! use shared_lib::transport::{stream::Stream, websocket::connect};
+//!
+//! fn request_over_websocket() -> Result<(), shared_lib::errors::frakt_error::FraktError> {
+//!     let tcp = TcpStream::connect("127.0.0.1:8789")?;
+//!     let mut stream = connect(Stream::Tcp(tcp), "ws://127.0.0.1:8789/")?;
+//!     let request = FragmentRequest::new(String::from("browser"), 1);
+//!     send_message(&mut stream, Fragment::FragmentRequest(request), &[])
+//! }
+//! ```
+
+use std::io::{self, Read, Write};
+
+use tungstenite::{handshake::HandshakeError, protocol::WebSocket, Message};
+
+use super::stream::Stream;
+
+/// A WebSocket connection seen as a stream of bytes: written bytes are sent in one binary message
+/// on each flush, received binary messages are read one after the other
+pub struct WebSocketStream {
+    socket: WebSocket<Stream>,
+    received: Vec<u8>,
+    position: usize,
+    pending: Vec<u8>,
+}
+
+impl WebSocketStream {
+    fn new(socket: WebSocket<Stream>) -> WebSocketStream {
+        WebSocketStream {
+            socket,
+            received: Vec::new(),
+            position: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// to get the connection the WebSocket is carried over
+    /// * Return: `&Stream` - the underlying stream
+    pub fn get_ref(&self) -> &Stream {
+        self.socket.get_ref()
+    }
+}
+
+impl Read for WebSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == self.received.len() {
+            match self.socket.read() {
+                Ok(Message::Binary(data)) => {
+                    self.received = data;
+                    self.position = 0;
+                }
+                Ok(Message::Text(_)) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Text WebSocket message received, frames are sent in binary messages",
+                    ))
+                }
+                // the pongs are queued by tungstenite and sent with the next write
+                Ok(Message::Ping(_)) | Ok(Message::Pong(_)) | Ok(Message::Frame(_)) => {}
+                Ok(Message::Close(_))
+                | Err(tungstenite::Error::ConnectionClosed)
+                | Err(tungstenite::Error::AlreadyClosed) => return Ok(0),
+                Err(err) => return Err(websocket_error(err)),
+            }
+        }
+        let count = buf.len().min(self.received.len() - self.position);
+        buf[..count].copy_from_slice(&self.received[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+impl Write for WebSocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            let message = Message::Binary(std::mem::take(&mut self.pending));
+            self.socket.write(message).map_err(websocket_error)?;
+        }
+        self.socket.flush().map_err(websocket_error)
+    }
+}
+
+/// to accept a WebSocket connection on the server, the WebSocket handshake is done before returning
+/// * `stream` - the connection accepted, already secured if the server uses TLS
+/// * Return: the WebSocket `Stream`, or an `io::Error` if the handshake failed
+pub fn accept(stream: Stream) -> Result<Stream, io::Error> {
+    match tungstenite::accept(stream) {
+        Ok(socket) => Ok(Stream::WebSocket(Box::new(WebSocketStream::new(socket)))),
+        Err(err) => Err(handshake_error(err)),
+    }
+}
+
+/// to open a WebSocket connection to the server, the WebSocket handshake is done before returning
+/// * `stream` - the connection opened to the server, already secured if the server uses TLS
+/// * `url` - the URL of the server, for example `ws://localhost:8789/`
+/// * Return: the WebSocket `Stream`, or an `io::Error` if the handshake failed
+pub fn connect(stream: Stream, url: &str) -> Result<Stream, io::Error> {
+    match tungstenite::client(url, stream) {
+        Ok((socket, _)) => Ok(Stream::WebSocket(Box::new(WebSocketStream::new(socket)))),
+        Err(err) => Err(handshake_error(err)),
+    }
+}
+
+/// to convert an error of tungstenite to the `io::Error` of a stream
+fn websocket_error(err: tungstenite::Error) -> io::Error {
+    match err {
+        tungstenite::Error::Io(err) => err,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            io::Error::new(io::ErrorKind::BrokenPipe, "WebSocket connection closed")
+        }
+        err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+    }
+}
+
+fn handshake_error<R>(err: HandshakeError<R>) -> io::Error
+where
+    R: tungstenite::handshake::HandshakeRole,
+{
+    match err {
+        HandshakeError::Failure(tungstenite::Error::Io(err)) => err,
+        HandshakeError::Failure(err) => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("WebSocket handshake failed: {}", err),
+        ),
+        HandshakeError::Interrupted(_) => io::Error::new(
+            io::ErrorKind::TimedOut,
+            "WebSocket handshake interrupted, the peer is too slow",
+        ),
+    }
+}
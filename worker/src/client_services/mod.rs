@@ -1 +1,2 @@
+mod test_worker;
 pub mod worker;
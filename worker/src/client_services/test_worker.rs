@@ -0,0 +1,109 @@
+#[cfg(test)]
+use shared_lib::{
+    complementary_types::{
+        pixelintensity::PixelIntensity, point::Point, range::Range, resolution::Resolution,
+        u8data::U8Data,
+    },
+    fractal_implementation::fractal::FractalDescriptor,
+    fractal_types::mandelbrot::Mandelbrot,
+    messages::{
+        handshake::{Capabilities, HeaderEncoding, PayloadEncoding},
+        message::{Fragment, FragmentResultChunk, FragmentTask},
+    },
+    messages_methods::{
+        messages_methods::send_message_with,
+        payload::{checksum, encode_pixels},
+    },
+};
+
+#[cfg(test)]
+use super::worker::band_rows;
+
+#[cfg(test)]
+fn task(nx: u16, ny: u16) -> FragmentTask {
+    FragmentTask::new(
+        U8Data::new(0, 16),
+        FractalDescriptor::Mandelbrot(Mandelbrot {}),
+        64,
+        Resolution::new(nx, ny),
+        Range::new(Point::new(-2.0, -1.0), Point::new(1.0, 1.0)),
+    )
+}
+
+#[cfg(test)]
+fn capabilities(
+    header: HeaderEncoding,
+    payload: PayloadEncoding,
+    max_message_size: u32,
+) -> Capabilities {
+    Capabilities {
+        header_encodings: vec![header],
+        payload_encodings: vec![payload],
+        max_message_size,
+        ..Capabilities::supported()
+    }
+}
+
+/// pixels which do not compress, the worst case for a deflated band
+#[cfg(test)]
+fn noisy_pixels(count: usize) -> Vec<PixelIntensity> {
+    let mut seed: u32 = 0x2545_f491;
+    (0..count)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let zn = f32::from_bits(seed >> 9 | 0x3f00_0000);
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            PixelIntensity::new(zn, (seed >> 16) as f32)
+        })
+        .collect()
+}
+
+#[test]
+fn test_wide_task_bands_fit_the_message_limit() {
+    let task = task(4000, 40);
+    let id = vec![7; 16];
+    let pixels = noisy_pixels(4000 * 40);
+
+    for (header, payload) in [
+        (HeaderEncoding::Json, PayloadEncoding::F32BigEndian),
+        (HeaderEncoding::Cbor, PayloadEncoding::F32BigEndian),
+        (HeaderEncoding::Json, PayloadEncoding::Deflate),
+        (HeaderEncoding::Cbor, PayloadEncoding::Deflate),
+    ] {
+        let capabilities = capabilities(header, payload, 100_000);
+        let rows = band_rows(&capabilities, &task, id.len());
+        assert!(rows > 1 && rows < 64, "{} rows", rows);
+
+        let band = (rows * 4000) as usize;
+        for (index, band_pixels) in pixels.chunks(band).enumerate() {
+            let mut chunk =
+                FragmentResultChunk::create(&task, (index * band) as u32, band_pixels.len() as u32);
+            let mut datas = id.clone();
+            datas.extend(encode_pixels(band_pixels, payload).unwrap());
+            chunk.checksum = Some(checksum(&datas));
+
+            let mut frame = Vec::new();
+            send_message_with(
+                &mut frame,
+                Fragment::FragmentResultChunk(chunk),
+                &datas,
+                header,
+            )
+            .unwrap();
+            let total = u32::from_be_bytes([frame[0], frame[1], frame[2], frame[3]]);
+            assert!(total <= 100_000, "band {} is {} bytes", index, total);
+        }
+    }
+}
+
+#[test]
+fn test_narrow_task_keeps_whole_bands() {
+    let capabilities = Capabilities::supported();
+    assert_eq!(band_rows(&capabilities, &task(100, 100), 16), 64);
+}
+
+#[test]
+fn test_band_holds_at_least_one_row() {
+    let capabilities = capabilities(HeaderEncoding::Json, PayloadEncoding::F32BigEndian, 1_000);
+    assert_eq!(band_rows(&capabilities, &task(4000, 40), 16), 1);
+}
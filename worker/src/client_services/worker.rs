@@ -39,20 +39,79 @@
 
 use std::env;
 use std::io;
-use std::io::Write;
+use std::io::Read;
 use std::net::TcpStream;
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
 use std::process::exit;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
+use shared_lib::complementary_types::pixelintensity::PixelIntensity;
+use shared_lib::complementary_types::u8data::U8Data;
+use shared_lib::errors::frakt_error::FraktError;
 use shared_lib::fractal_implementation::fractal::FractalDescriptor;
 use shared_lib::fractal_implementation::fractal_calcul::create_image;
+use shared_lib::fractal_implementation::progress::Progress;
+use shared_lib::messages::authentication::{compute_proof, load_secret};
+use shared_lib::messages::fragment_method_cbor::fragment_to_cbor;
+use shared_lib::messages::fragment_method_json::fragment_to_string;
+use shared_lib::messages::handshake::{
+    Capabilities, HeaderEncoding, PayloadEncoding, PROTOCOL_VERSION,
+};
 use shared_lib::messages::message::FragmentResult;
 use shared_lib::messages::message::FragmentTask;
-use shared_lib::messages::message::{Fragment, FragmentRequest};
-use shared_lib::messages_methods::messages_methods::read_message;
+use shared_lib::messages::message::{
+    Fragment, FragmentAuthentication, FragmentBatch, FragmentChallenge, FragmentHello,
+    FragmentProgress, FragmentRequest, FragmentResultChunk,
+};
+use shared_lib::messages_methods::frame::FrameLimits;
 use shared_lib::messages_methods::messages_methods::send_message;
+use shared_lib::messages_methods::messages_methods::send_message_with;
+use shared_lib::messages_methods::messages_methods::{read_message, read_message_with_limits};
+use shared_lib::messages_methods::payload::{checksum, encode_pixels};
+use shared_lib::transport::stream::{unix_socket_path, Stream};
+use shared_lib::transport::tls::{self, client_config, ClientConfig};
+use shared_lib::transport::websocket;
+
+const WORKER_NAME: &str = "Group4-4AL1-Fractanstique";
+
+/// Interval between two `FragmentProgress` sent while a task is computed
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Interval between two checks of the messages pushed by the server while a task is computed
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Largest number of rows of a task sent in each `FragmentResultChunk`, when the server accepts results streamed in bands
+const RESULT_BAND_ROWS: u32 = 64;
+
+/// Bytes kept for the framing of a deflated band, which is a little larger than its raw pixels when they do not compress
+const DEFLATE_MARGIN: usize = 64;
+
+/// TLS settings of the worker:
+/// * config: the TLS configuration, with the CA trusted for the server certificate
+/// * server_name: the name the server certificate must be valid for
+#[derive(Clone)]
+pub struct WorkerTls {
+    pub config: Arc<ClientConfig>,
+    pub server_name: String,
+}
+
+/// What the server answers to a request or a result:
+/// * Task: a new task to compute, with its data
+/// * Batch: several tasks to compute, each with its own data
+/// * NoWork: no task for now, the worker should ask again after the given delay
+pub enum ServerAnswer {
+    Task(FragmentTask, Vec<u8>),
+    Batch(Vec<(FragmentTask, Vec<u8>)>),
+    NoWork(Duration),
+}
 
 pub struct ClientServices {
-    stream: TcpStream,
+    stream: Stream,
+    capabilities: Capabilities,
 }
 
 impl ClientServices {
@@ -61,94 +120,476 @@ impl ClientServices {
         TcpStream::connect(server_addr)
     }
 
-    pub fn new(host: &str, port: u16) -> Result<ClientServices, io::Error> {
-        let stream = ClientServices::connect_to(host, &port)?;
+    /// Open a plain connection to the server: its Unix domain socket for a `unix:<path>` host, TCP otherwise
+    /// * `host` - the host of the server, or the path of its Unix domain socket after `unix:`
+    /// * `port` - the port of the server, unused for a Unix domain socket
+    /// * Return: the connection, or an error if the server cannot be reached
+    fn open(host: &str, port: u16) -> Result<Stream, FraktError> {
+        #[cfg(unix)]
+        if let Some(path) = unix_socket_path(host) {
+            return Ok(Stream::Unix(UnixStream::connect(path)?));
+        }
+        Ok(Stream::Tcp(ClientServices::connect_to(host, &port)?))
+    }
+
+    /// Connect to the server, over TLS if settings are given, inside a WebSocket if asked
+    /// * `host` - the host of the server, or the path of its Unix domain socket after `unix:`
+    /// * `port` - the port of the server
+    /// * `tls` - the TLS settings, None to talk plain TCP
+    /// * `websocket` - whether the frames are carried in WebSocket messages, for the WebSocket port of the server
+    /// * Return: the connected client, or an error if the connection, the TLS or the WebSocket handshake failed
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: Option<&WorkerTls>,
+        websocket: bool,
+    ) -> Result<ClientServices, FraktError> {
+        let unix_socket = unix_socket_path(host).is_some();
+        let mut stream = match tls {
+            Some(_) if unix_socket => {
+                return Err(FraktError::Validation(String::from(
+                    "TLS is not available over a Unix domain socket",
+                )))
+            }
+            Some(tls) => tls::connect(
+                ClientServices::connect_to(host, &port)?,
+                &tls.config,
+                &tls.server_name,
+            )?,
+            None => ClientServices::open(host, port)?,
+        };
+        if websocket {
+            let scheme = if tls.is_some() { "wss" } else { "ws" };
+            let url = match unix_socket {
+                true => format!("{}://localhost/", scheme),
+                false => format!("{}://{}:{}/", scheme, host, port),
+            };
+            stream = websocket::connect(stream, &url)?;
+        }
 
-        Ok(ClientServices { stream })
+        Ok(ClientServices {
+            stream,
+            capabilities: Capabilities::legacy(),
+        })
     }
 
-    pub fn get_task_from_server(&mut self) -> Result<(FragmentTask, Vec<u8>), io::Error> {
-        let (task, datas) = match read_message(&mut self.stream) {
-            Ok((Fragment::FragmentTask(task), datas)) => {
-                println!("Task received");
-                (task, datas)
+    /// Bound the time to wait for the server, so that a dead server is noticed
+    /// * `timeout` - the maximum time to wait for a message, None to wait forever
+    /// * Return: an error if the timeout cannot be set
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<(), FraktError> {
+        Ok(self.stream.set_read_timeout(timeout)?)
+    }
+
+    /// Capabilities announced by this worker
+    /// * `json_headers` - announce JSON headers only, to keep the exchange readable
+    /// * `compress` - prefer compressed pixels, for slow links
+    /// * Return: the capabilities to announce in the handshake
+    pub fn worker_capabilities(json_headers: bool, compress: bool) -> Capabilities {
+        let mut capabilities = Capabilities::supported();
+        if json_headers {
+            capabilities.header_encodings = vec![HeaderEncoding::Json];
+        }
+        if compress {
+            capabilities.payload_encodings =
+                vec![PayloadEncoding::Deflate, PayloadEncoding::F32BigEndian];
+        }
+        capabilities
+    }
+
+    /// Open the session with a handshake: announce our protocol version and capabilities,
+    /// answer the challenge of the server if it requires authentication, and keep the
+    /// capabilities negotiated by the server
+    /// * `capabilities` - the capabilities to announce
+    /// * `secret` - the key shared with the server, None if the server does not require authentication
+    /// * Return: the negotiated capabilities, or an error if the server refused us or does not support the handshake
+    pub fn handshake(
+        &mut self,
+        capabilities: Capabilities,
+        secret: Option<&[u8]>,
+    ) -> Result<Capabilities, FraktError> {
+        let hello = FragmentHello::new(PROTOCOL_VERSION, String::from(WORKER_NAME), capabilities);
+        send_message(&mut self.stream, Fragment::FragmentHello(hello), &[])?;
+
+        let mut answer = read_message(&mut self.stream);
+        if let Ok((Fragment::FragmentChallenge(challenge), nonce)) = answer {
+            let secret = secret.ok_or_else(|| {
+                FraktError::Refused(String::from(
+                    "The server requires authentication (use --secret-file)",
+                ))
+            })?;
+            self.answer_challenge(&challenge, &nonce, secret)?;
+            answer = read_message(&mut self.stream);
+        }
+
+        let welcome = match answer {
+            Ok((Fragment::FragmentWelcome(welcome), _)) => welcome,
+            Ok((fragment, _)) => {
+                return Err(FraktError::unexpected("FragmentWelcome", &fragment));
             }
-            Ok(_) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Should return a FragmentTask",
-                ));
+            Err(err) if err.is_disconnection() => {
+                return Err(FraktError::Refused(format!(
+                    "No answer to handshake, the server may be a legacy server (use --one-shot): {}",
+                    err
+                )));
             }
             Err(err) => return Err(err),
         };
 
-        Ok((task, datas))
+        if !welcome.accepted {
+            return Err(FraktError::Refused(format!(
+                "protocol version {}: {}",
+                welcome.protocol_version,
+                welcome.reason.unwrap_or_default()
+            )));
+        }
+
+        self.capabilities = welcome.capabilities;
+        Ok(self.capabilities.clone())
     }
 
-    pub fn send_request(&mut self) -> Result<(), io::Error> {
+    /// Prove to the server that we know the shared key
+    /// * `challenge` - the challenge sent by the server
+    /// * `datas` - the data of the challenge, holding the nonce
+    /// * `secret` - the key shared with the server
+    /// * Return: an error if the challenge is malformed or the proof cannot be sent
+    fn answer_challenge(
+        &mut self,
+        challenge: &FragmentChallenge,
+        datas: &[u8],
+        secret: &[u8],
+    ) -> Result<(), FraktError> {
+        let start = challenge.nonce.offset as usize;
+        let end = start + challenge.nonce.count as usize;
+        let nonce = datas.get(start..end).ok_or_else(|| {
+            FraktError::Validation(String::from(
+                "the nonce is outside of the FragmentChallenge data",
+            ))
+        })?;
+
+        let proof = compute_proof(secret, nonce, WORKER_NAME);
+        let authentication = FragmentAuthentication::new(U8Data::new(0, proof.len() as u32));
+        println!("Challenge received, sending authentication");
+        send_message(
+            &mut self.stream,
+            Fragment::FragmentAuthentication(authentication),
+            &proof,
+        )
+    }
+
+    /// to read the answer of the server to a request or a result
+    /// * Return: `Result<ServerAnswer, FraktError>` - the task to compute or the delay before asking again, `FraktError::Rejected` if the server sent a `FragmentError`
+    pub fn get_task_from_server(&mut self) -> Result<ServerAnswer, FraktError> {
+        let encoding = self.capabilities.header_encoding();
+        let limits = FrameLimits::new(self.capabilities.max_message_size);
+        loop {
+            match read_message_with_limits(&mut self.stream, encoding, &limits)? {
+                (Fragment::FragmentTask(task), datas) => {
+                    println!("Task received");
+                    return Ok(ServerAnswer::Task(task, datas));
+                }
+                (Fragment::FragmentBatch(batch), datas) => {
+                    println!("Batch of {} tasks received", batch.tasks.len());
+                    return Ok(ServerAnswer::Batch(split_batch(batch, &datas)?));
+                }
+                (Fragment::FragmentNoWork(no_work), _) => {
+                    return Ok(ServerAnswer::NoWork(Duration::from_secs(
+                        no_work.retry_after as u64,
+                    )))
+                }
+                (Fragment::FragmentError(error), _) => return Err(FraktError::from(error)),
+                // the tasks were already computed and their results sent: nothing to stop
+                (Fragment::FragmentCancel(cancel), _) => {
+                    println!("Cancel received for tasks already done: {}", cancel.reason);
+                }
+                (fragment, _) => return Err(FraktError::unexpected("FragmentTask", &fragment)),
+            }
+        }
+    }
+
+    /// to ask the server for work
+    /// * `maximal_work_load` - the number of tasks the worker accepts at once
+    pub fn send_request(&mut self, maximal_work_load: u32) -> Result<(), FraktError> {
         //Create a Fragment from FragmentRequest
         let fragment_request = FragmentRequest {
-            worker_name: String::from("Group4-4AL1-Fractanstique"),
-            maximal_work_load: 10,
+            worker_name: String::from(WORKER_NAME),
+            maximal_work_load,
         };
         let request = Fragment::FragmentRequest(fragment_request);
         let data = Vec::new();
         println!("Request created");
 
         //Send the FragmentRequest (data empty for a request)
-        send_message(&mut self.stream, request, &data)
+        send_message_with(
+            &mut self.stream,
+            request,
+            &data,
+            self.capabilities.header_encoding(),
+        )
     }
 
-    pub fn send_result(&mut self, task: &FragmentTask, datas: &Vec<u8>) -> Result<(), io::Error> {
-        //Create a Fragment from FragmentResult
-        let fragment_result = FragmentResult::create(&task);
-        let _result = Fragment::FragmentResult(fragment_result);
-        println!("Result created");
+    /// to send the pixels of a task to the server: in a single `FragmentResult`, or band by band
+    /// with `FragmentResultChunk` messages of at most `RESULT_BAND_ROWS` rows when the server accepts it,
+    /// so only one band is encoded at a time and each band fits in the largest message the server accepts
+    /// * `task` - the task computed
+    /// * `datas` - the data received with the task, holding its id
+    /// * `pixels` - the pixels computed for the task
+    pub fn send_result(
+        &mut self,
+        task: &FragmentTask,
+        datas: &[u8],
+        pixels: &[PixelIntensity],
+    ) -> Result<(), FraktError> {
+        let id_end = (task.id.offset + task.id.count) as usize;
+        let id = datas.get(..id_end).unwrap_or(datas);
+        let rows = band_rows(&self.capabilities, task, id.len());
+        let band = rows * task.resolution.nx as u32;
+
+        if !self.capabilities.result_chunks || pixels.len() as u32 <= band {
+            let mut datas = id.to_vec();
+            datas.extend(self.encode_pixels(pixels)?);
+
+            //Create a Fragment from FragmentResult
+            let mut fragment_result = FragmentResult::create(task);
+            fragment_result.checksum = self.checksum(&datas);
+            let result = Fragment::FragmentResult(fragment_result);
+            println!("Result created");
+
+            return send_message_with(
+                &mut self.stream,
+                result,
+                &datas,
+                self.capabilities.header_encoding(),
+            );
+        }
 
-        send_message(&mut self.stream, _result, &datas)?;
+        for (index, band_pixels) in pixels.chunks(band as usize).enumerate() {
+            let mut chunk =
+                FragmentResultChunk::create(task, index as u32 * band, band_pixels.len() as u32);
+            let mut datas = id.to_vec();
+            datas.extend(self.encode_pixels(band_pixels)?);
+            chunk.checksum = self.checksum(&datas);
+            send_message_with(
+                &mut self.stream,
+                Fragment::FragmentResultChunk(chunk),
+                &datas,
+                self.capabilities.header_encoding(),
+            )?;
+        }
+        println!("Result sent in bands of {} rows", rows);
         Ok(())
     }
 
-    pub fn do_work(
+    /// to compute the checksum of the data of a result, when the server checks it
+    /// * `datas` - the whole data of the result
+    /// * Return: the checksum to put in the header, None if it was not negotiated
+    fn checksum(&self, datas: &[u8]) -> Option<u32> {
+        self.capabilities.checksum.then(|| checksum(datas))
+    }
+
+    /// to tell the server how much of a task is computed, showing the worker is still alive
+    /// * `task` - the task being computed
+    /// * `datas` - the data received with the task, holding its id
+    /// * `percentage` - the share of the pixels already computed
+    pub fn send_progress(
         &mut self,
         task: &FragmentTask,
-        mut datas: Vec<u8>,
-    ) -> Result<Vec<u8>, io::Error> {
-        // generate the datas for the fractal calculation from the task
-        let pixels_calculated = FractalDescriptor::get_datas(&task);
-        println!("Pixels calculated");
+        datas: &[u8],
+        percentage: u8,
+    ) -> Result<(), FraktError> {
+        let id_end = (task.id.offset + task.id.count) as usize;
+        let progress = Fragment::FragmentProgress(FragmentProgress::new(task.id, percentage));
+        send_message_with(
+            &mut self.stream,
+            progress,
+            datas.get(..id_end).unwrap_or(datas),
+            self.capabilities.header_encoding(),
+        )
+    }
+
+    /// to read a message pushed by the server while the worker computes, without waiting
+    /// * Return: the message, None if the server sent nothing
+    fn poll_message(&mut self) -> Result<Option<(Fragment, Vec<u8>)>, FraktError> {
+        let timeout = self.stream.read_timeout()?;
+        self.stream
+            .set_read_timeout(Some(Duration::from_millis(1)))?;
+        let mut first_byte = [0; 1];
+        let polled = self.stream.read(&mut first_byte);
+        self.stream.set_read_timeout(timeout)?;
 
-        // create the image from client (path can be changed to the desired path)
-        match create_image(&task, &pixels_calculated, Some("./images/worker/")) {
+        match polled {
+            Ok(0) => Err(FraktError::Transport(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Connection closed by peer",
+            ))),
             Ok(_) => {
-                println!("Image created");
+                // the rest of the frame is read as usual, after the byte already received
+                let encoding = self.capabilities.header_encoding();
+                let limits = FrameLimits::new(self.capabilities.max_message_size);
+                let mut stream = io::Cursor::new(first_byte).chain(&mut self.stream);
+                read_message_with_limits(&mut stream, encoding, &limits).map(Some)
             }
-            Err(err) => {
-                eprintln!("Error while creating image : {}", err);
+            Err(err)
+                if err.kind() == io::ErrorKind::WouldBlock
+                    || err.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(None)
             }
+            Err(err) => Err(FraktError::Transport(err)),
         }
+    }
 
-        // add the datas calculated to the buffer
-        for pixel in pixels_calculated {
-            match datas.write_all(&pixel.zn.to_be_bytes()) {
-                Ok(_) => {}
-                Err(err) => {
-                    eprintln!("Error while writting zn : {}", err);
+    /// to check if the server cancelled tasks of the batch being computed, and stop them
+    /// * `batch` - the tasks being computed, with the data holding their id
+    /// * `progresses` - the progress of each task of the batch
+    fn check_cancel(
+        &mut self,
+        batch: &[(FragmentTask, Vec<u8>)],
+        progresses: &[Progress],
+    ) -> Result<(), FraktError> {
+        let (cancel, datas) = match self.poll_message()? {
+            None => return Ok(()),
+            Some((Fragment::FragmentCancel(cancel), datas)) => (cancel, datas),
+            Some((fragment, _)) => {
+                return Err(FraktError::unexpected("FragmentCancel", &fragment));
+            }
+        };
+        for id in &cancel.ids {
+            let start = id.offset as usize;
+            let cancelled_id = datas.get(start..start + id.count as usize);
+            for ((task, task_datas), progress) in batch.iter().zip(progresses) {
+                if cancelled_id.is_some() && cancelled_id == task_id(task, task_datas) {
+                    println!("Task cancelled by the server: {}", cancel.reason);
+                    progress.cancel();
                 }
             }
-            match datas.write_all(&pixel.count.to_be_bytes()) {
-                Ok(_) => {}
-                Err(err) => {
-                    eprintln!("Error while writting count : {}", err);
+        }
+        Ok(())
+    }
+
+    /// to compute the pixels of the tasks of a batch, one after the other, while listening to the
+    /// server when it accepts it: a `FragmentProgress` is sent for every task of the batch every
+    /// `PROGRESS_INTERVAL`, so the tasks waiting for their turn keep their lease too, and a
+    /// `FragmentCancel` stops the tasks it names
+    /// * `batch` - the tasks to compute, with the data holding their id
+    /// * Return: the pixels computed for each task, None for a cancelled task, or an error if the server cannot be reached
+    fn compute(
+        &mut self,
+        batch: &[(FragmentTask, Vec<u8>)],
+    ) -> Result<Vec<Option<Vec<PixelIntensity>>>, FraktError> {
+        let progresses: Vec<Progress> = batch
+            .iter()
+            .map(|(task, _)| Progress::for_task(task))
+            .collect();
+        let compute_all = |progresses: &[Progress]| -> Vec<Vec<PixelIntensity>> {
+            batch
+                .iter()
+                .zip(progresses)
+                .map(|((task, _), progress)| {
+                    FractalDescriptor::get_datas_with_progress(task, progress)
+                })
+                .collect()
+        };
+
+        let pixels = if !self.capabilities.progress && !self.capabilities.cancel {
+            compute_all(&progresses)
+        } else {
+            thread::scope(|scope| {
+                let (tx, rx) = mpsc::channel();
+                let progresses = &progresses;
+                scope.spawn(move || {
+                    let _ = tx.send(compute_all(progresses));
+                });
+                let mut last_progress = Instant::now();
+                loop {
+                    match rx.recv_timeout(POLL_INTERVAL) {
+                        Ok(pixels) => return Ok(pixels),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => {
+                            return Err(FraktError::Validation(String::from(
+                                "The computation of the task stopped",
+                            )));
+                        }
+                    }
+                    if let Err(err) = self.listen_to_server(batch, progresses, &mut last_progress) {
+                        // the server is lost: no need to finish the batch
+                        progresses.iter().for_each(Progress::cancel);
+                        return Err(err);
+                    }
+                }
+            })?
+        };
+
+        Ok(pixels
+            .into_iter()
+            .zip(&progresses)
+            .map(|(pixels, progress)| (!progress.is_cancelled()).then_some(pixels))
+            .collect())
+    }
+
+    /// to check the cancels of the server and send the progress of the batch when it is time
+    /// * `batch` - the tasks being computed, with the data holding their id
+    /// * `progresses` - the progress of each task of the batch
+    /// * `last_progress` - when the last progress was sent, updated when a new one is sent
+    fn listen_to_server(
+        &mut self,
+        batch: &[(FragmentTask, Vec<u8>)],
+        progresses: &[Progress],
+        last_progress: &mut Instant,
+    ) -> Result<(), FraktError> {
+        if self.capabilities.cancel {
+            self.check_cancel(batch, progresses)?;
+        }
+        if self.capabilities.progress && last_progress.elapsed() >= PROGRESS_INTERVAL {
+            for ((task, datas), progress) in batch.iter().zip(progresses) {
+                if !progress.is_cancelled() {
+                    println!("Task {}% done", progress.percentage());
+                    self.send_progress(task, datas, progress.percentage())?;
                 }
             }
+            *last_progress = Instant::now();
         }
-        println!("Datas is now completed and ready to be sent");
-        Ok(datas)
+        Ok(())
+    }
+
+    /// to compute the tasks of a batch and create their images
+    /// * `batch` - the tasks to compute, with the data holding their id
+    /// * Return: each task not cancelled by the server with its data and the pixels computed
+    pub fn do_work(
+        &mut self,
+        batch: Vec<(FragmentTask, Vec<u8>)>,
+    ) -> Result<Vec<(FragmentTask, Vec<u8>, Vec<PixelIntensity>)>, FraktError> {
+        // generate the datas for the fractal calculation from the tasks
+        let pixels_calculated = self.compute(&batch)?;
+        println!("Pixels calculated");
+
+        Ok(batch
+            .into_iter()
+            .zip(pixels_calculated)
+            .filter_map(|((task, datas), pixels)| Some((task, datas, pixels?)))
+            .inspect(|(task, _, pixels)| {
+                // create the image from client (path can be changed to the desired path)
+                match create_image(task, pixels, Some("./images/worker/")) {
+                    Ok(_) => {
+                        println!("Image created");
+                    }
+                    Err(err) => {
+                        eprintln!("Error while creating image : {}", err);
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// to encode pixels as the data of a result, as negotiated with the server
+    /// * `pixels_calculated` - the pixels to send
+    /// * Return: the encoded pixels, or an error if they cannot be encoded
+    fn encode_pixels(&self, pixels_calculated: &[PixelIntensity]) -> Result<Vec<u8>, FraktError> {
+        encode_pixels(pixels_calculated, self.capabilities.payload_encoding())
     }
 
     ///function to get the arguments passed to the program
-    pub fn parse_args() -> (String, u16) {
+    /// * Return: (`String`, `u16`, `bool`, `bool`, `bool`) - the host, the port, whether a new connection is opened for every result, whether headers stay in JSON and whether pixels are compressed
+    pub fn parse_args() -> (String, u16, bool, bool, bool) {
         let args: Vec<String> = env::args().collect();
 
         let host_argument = args
@@ -161,6 +602,10 @@ impl ClientServices {
             .find(|arg| arg.starts_with("--port="))
             .map(|arg| arg.trim_start_matches("--port="));
 
+        let one_shot = args.iter().any(|arg| arg == "--one-shot");
+        let json_headers = args.iter().any(|arg| arg == "--json-headers");
+        let compress = args.iter().any(|arg| arg == "--compress");
+
         let mut host = match host_argument {
             Some(host) => {
                 println!("Host argument: {}", host);
@@ -187,8 +632,23 @@ impl ClientServices {
             if args[1] == "--help" {
                 println!("Usage : ./worker 0.0.0.0");
                 println!("Usage : ./worker <flag>");
-                println!("Flag: --ip=<ip_adress>");
+                println!("Flag: --ip=<ip_adress> (or unix:<path> for the Unix domain socket of a server on this machine)");
                 println!("Flag: --port=<port>");
+                println!("Flag: --one-shot (no handshake and new connection for every result, for legacy servers)");
+                println!("Flag: --json-headers (do not negotiate binary headers, for debugging)");
+                println!(
+                    "Flag: --compress (compress the pixels sent to the server, for slow links)"
+                );
+                println!("Flag: --tls (connect with TLS, the server certificate is checked against the public roots)");
+                println!("Flag: --ca-cert=<ca.pem> (with TLS, trust the server certificates signed by this CA)");
+                println!("Flag: --server-name=<name> (with TLS, name of the server certificate, the host by default)");
+                println!("Flag: --client-cert=<cert.pem> --client-key=<key.pem> (with TLS, certificate presented to the server)");
+                println!("Flag: --secret-file=<path> (authenticate to the server with the key of this file)");
+                println!("Flag: --read-timeout=<seconds> (time to wait for the server before reconnecting, 60 by default)");
+                println!(
+                    "Flag: --work-load=<tasks> (number of tasks computed at once, 1 by default)"
+                );
+                println!("Flag: --websocket (carry the messages in WebSocket messages, for the WebSocket port of the server)");
                 // Terminer le programme
                 exit(0);
             }
@@ -210,6 +670,209 @@ impl ClientServices {
             }
         }
 
-        (host.to_string(), port)
+        (host.to_string(), port, one_shot, json_headers, compress)
+    }
+
+    ///function to know whether the worker connects to the WebSocket port of the server
+    /// * Return: `bool` - true if --websocket is given
+    pub fn parse_websocket_args() -> bool {
+        let websocket = env::args().any(|arg| arg == "--websocket");
+        if websocket {
+            println!("WebSocket argument given");
+        }
+        websocket
+    }
+
+    ///function to get the TLS arguments passed to the program
+    /// * `host` - the host of the server, used as the name of its certificate by default
+    /// * Return: `Option<WorkerTls>` - the TLS settings, None to talk plain TCP
+    pub fn parse_tls_args(host: &str) -> Option<WorkerTls> {
+        let args: Vec<String> = env::args().collect();
+
+        let find_argument = |flag: &str| {
+            args.iter()
+                .find(|arg| arg.starts_with(flag))
+                .map(|arg| arg.trim_start_matches(flag).to_string())
+        };
+        let ca_argument = find_argument("--ca-cert=");
+        let server_name_argument = find_argument("--server-name=");
+        let client_cert_argument = find_argument("--client-cert=");
+        let client_key_argument = find_argument("--client-key=");
+
+        // any TLS flag turns TLS on
+        let use_tls = args.iter().any(|arg| arg == "--tls")
+            || ca_argument.is_some()
+            || server_name_argument.is_some()
+            || client_cert_argument.is_some()
+            || client_key_argument.is_some();
+        if !use_tls {
+            return None;
+        }
+
+        let client_identity = match (&client_cert_argument, &client_key_argument) {
+            (Some(cert), Some(key)) => Some((cert.as_str(), key.as_str())),
+            (None, None) => None,
+            _ => {
+                eprintln!("Error: --client-cert and --client-key must be given together");
+                exit(1);
+            }
+        };
+
+        let config = match client_config(ca_argument.as_deref(), client_identity) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Error while loading TLS configuration: {}", err);
+                exit(1);
+            }
+        };
+
+        let server_name = server_name_argument.unwrap_or_else(|| host.to_string());
+        println!("TLS enabled, server name: {}", server_name);
+        Some(WorkerTls {
+            config,
+            server_name,
+        })
+    }
+
+    ///function to get the shared key passed to the program
+    /// * Return: `Option<Vec<u8>>` - the key used to authenticate to the server, None if not given
+    pub fn parse_secret_args() -> Option<Vec<u8>> {
+        let args: Vec<String> = env::args().collect();
+
+        let secret_file_argument = args
+            .iter()
+            .find(|arg| arg.starts_with("--secret-file="))
+            .map(|arg| arg.trim_start_matches("--secret-file="))?;
+
+        println!("Secret file argument: {}", secret_file_argument);
+        match load_secret(secret_file_argument) {
+            Ok(secret) => Some(secret),
+            Err(err) => {
+                eprintln!("Error while loading secret: {}", err);
+                exit(1);
+            }
+        }
+    }
+
+    ///function to get the read timeout passed to the program
+    /// * Return: `Duration` - the maximum time to wait for a message from the server
+    pub fn parse_read_timeout_args() -> Duration {
+        let args: Vec<String> = env::args().collect();
+
+        let read_timeout_argument = args
+            .iter()
+            .find(|arg| arg.starts_with("--read-timeout="))
+            .map(|arg| arg.trim_start_matches("--read-timeout="));
+
+        match read_timeout_argument {
+            Some(read_timeout) => {
+                println!("Read timeout argument: {}", read_timeout);
+                match read_timeout.parse::<u64>() {
+                    Ok(seconds) if seconds > 0 => Duration::from_secs(seconds),
+                    _ => {
+                        eprintln!("Error while parsing read timeout argument");
+                        exit(1);
+                    }
+                }
+            }
+            None => Duration::from_secs(60),
+        }
+    }
+
+    ///function to get the number of tasks the worker computes at once
+    /// * Return: `u32` - the work load given with --work-load, 1 by default
+    pub fn parse_work_load_args() -> u32 {
+        let args: Vec<String> = env::args().collect();
+
+        let work_load_argument = args
+            .iter()
+            .find(|arg| arg.starts_with("--work-load="))
+            .map(|arg| arg.trim_start_matches("--work-load="));
+
+        match work_load_argument {
+            Some(work_load) => {
+                println!("Work load argument: {}", work_load);
+                match work_load.parse::<u32>() {
+                    Ok(work_load) if work_load > 0 => work_load,
+                    _ => {
+                        eprintln!("Error while parsing work load argument");
+                        exit(1);
+                    }
+                }
+            }
+            None => 1,
+        }
+    }
+}
+
+/// to split a `FragmentBatch` into tasks each holding its own id at the start of its data, as a
+/// single `FragmentTask` does
+/// * `batch` - the batch received
+/// * `datas` - the data received with the batch, holding the ids of the tasks
+/// * Return: the tasks with their data, or an error if an id is outside of the data
+fn split_batch(
+    batch: FragmentBatch,
+    datas: &[u8],
+) -> Result<Vec<(FragmentTask, Vec<u8>)>, FraktError> {
+    batch
+        .tasks
+        .into_iter()
+        .map(|task| {
+            let start = task.id.offset as usize;
+            let end = start + task.id.count as usize;
+            let id = datas.get(start..end).ok_or_else(|| {
+                FraktError::Validation(format!(
+                    "Task id at {}..{} is outside of the {} bytes of the batch",
+                    start,
+                    end,
+                    datas.len()
+                ))
+            })?;
+            let task = FragmentTask {
+                id: U8Data::new(0, task.id.count),
+                ..task
+            };
+            Ok((task, id.to_vec()))
+        })
+        .collect()
+}
+
+/// to get the id of a task in the data received with it
+/// * `task` - the task
+/// * `datas` - the data received with the task
+/// * Return: the id, None if it is outside of the data
+fn task_id<'a>(task: &FragmentTask, datas: &'a [u8]) -> Option<&'a [u8]> {
+    let start = task.id.offset as usize;
+    datas.get(start..start + task.id.count as usize)
+}
+
+/// to compute the number of rows of a task sent in each `FragmentResultChunk`: `RESULT_BAND_ROWS`,
+/// fewer when such a band of a wide task would not fit in the largest message the server accepts,
+/// at least one
+/// * `capabilities` - the capabilities negotiated with the server
+/// * `task` - the task computed
+/// * `id_len` - the size of the id of the task, sent before the pixels of each band
+/// * Return: `u32` - the number of rows of each band
+pub fn band_rows(capabilities: &Capabilities, task: &FragmentTask, id_len: usize) -> u32 {
+    // the largest header of a band: its numbers at their maximum, with a checksum
+    let mut chunk = FragmentResultChunk::create(task, 0, 0);
+    chunk.first_pixel = u32::MAX;
+    chunk.pixels.count = u32::MAX;
+    chunk.last = false;
+    chunk.checksum = Some(u32::MAX);
+    let chunk = Fragment::FragmentResultChunk(chunk);
+    let header_size = match capabilities.header_encoding() {
+        HeaderEncoding::Json => fragment_to_string(&chunk).map(|header| header.len()).ok(),
+        HeaderEncoding::Cbor => fragment_to_cbor(&chunk).map(|header| header.len()).ok(),
+    };
+
+    let mut available = (capabilities.max_message_size as usize)
+        .saturating_sub(header_size.unwrap_or(usize::MAX))
+        .saturating_sub(id_len);
+    if capabilities.payload_encoding() == PayloadEncoding::Deflate {
+        // deflate stores the pixels it cannot compress in blocks of 65535 bytes, each with a 5 bytes header
+        available = available.saturating_sub(DEFLATE_MARGIN) * 65535 / 65540;
     }
+    let row_size = (task.resolution.nx as usize * 8).max(1);
+    (available / row_size).clamp(1, RESULT_BAND_ROWS as usize) as u32
 }
@@ -11,13 +11,31 @@
 //! - `./worker`: Run the client with default settings.
 //! - `./worker [--ip=<ip>] [--port=<port>]`: Specify the server's host, using the default port (8787). Default ip is localhost.
 //! - `./worker <host> <port>`: Specify both the server's host and port.
+//! - `./worker unix:<path>`: Connect to the Unix domain socket of a server running on the same machine.
+//! - `./worker --one-shot`: Skip the handshake and open a new connection for every result, for legacy servers.
+//! - `./worker --json-headers`: Keep JSON headers instead of negotiating binary (CBOR) headers, for debugging.
+//! - `./worker --compress`: Prefer sending the pixels compressed with deflate, for slow links.
+//! - `./worker --tls`: Connect with TLS, the server certificate is checked against the public roots.
+//! - `./worker --ca-cert=<ca.pem>`: Connect with TLS, trusting the server certificates signed by this CA (self-signed certificates for local tests).
+//! - `./worker --server-name=<name>`: With TLS, the name the server certificate must be valid for (the host by default).
+//! - `./worker --client-cert=<cert.pem> --client-key=<key.pem>`: With TLS, the certificate presented to a server requiring one.
+//! - `./worker --secret-file=<path>`: Authenticate to a server requiring it, with the key stored in this file.
+//! - `./worker --read-timeout=<seconds>`: Time to wait for the server before reconnecting (default is 60).
+//! - `./worker --work-load=<tasks>`: Number of tasks asked to the server and computed at once (default is 1).
+//! - `./worker --websocket`: Carry the messages in WebSocket messages, to connect to the WebSocket port of a server.
 //!
 //! ## Features
 //!
 //! - Dynamically handles command-line arguments to configure the connection.
-//! - Establishes a connection to the server and continuously communicates.
+//! - Establishes a connection to the server and keeps it open for the whole session, reconnecting when it is lost.
+//! - Opens every session with a handshake announcing its protocol version and capabilities.
 //! - Requests tasks, performs computations, and sends back results.
+//! - Waits and asks again when the server has no task, asks for a new task when a result is rejected.
 //! - Through Rayon, the computation are parallelized.
+//! - Tells the server how much of a task is computed every 2 seconds, when the server accepts it.
+//! - Stops computing the tasks the server cancels.
+//! - Streams large results in bands of rows, when the server accepts it.
+//! - Announces the checksum of the data of its results, when the server checks it.
 //!
 //! ## How to Run
 //!
@@ -42,53 +60,137 @@ use core::time;
 use std::process::exit;
 use std::thread;
 
+use shared_lib::errors::frakt_error::FraktError;
+use shared_lib::messages::message::ErrorCode;
+
 mod client_services;
-use client_services::worker::ClientServices;
+use client_services::worker::{ClientServices, ServerAnswer};
 fn main() {
-    let (host, port) = client_services::worker::ClientServices::parse_args();
+    let (host, port, one_shot, json_headers, compress) =
+        client_services::worker::ClientServices::parse_args();
+    let tls = ClientServices::parse_tls_args(&host);
+    let secret = ClientServices::parse_secret_args();
+    let read_timeout = ClientServices::parse_read_timeout_args();
+    let work_load = ClientServices::parse_work_load_args();
+    let websocket = ClientServices::parse_websocket_args();
 
-    //Connexion
-    let mut client = match ClientServices::new(&host, port) {
-        Ok(client) => {
-            println!("Client created and connected");
-            client
-        }
-        Err(_) => {
-            eprintln!("Error while creating and connecting client");
-            std::process::exit(1);
-        }
-    };
+    loop {
+        //Connexion
+        let mut client = match ClientServices::new(&host, port, tls.as_ref(), websocket) {
+            Ok(client) => {
+                println!("Client created and connected");
+                if let Err(err) = client.set_read_timeout(Some(read_timeout)) {
+                    eprintln!("Error while setting read timeout : {}", err);
+                }
+                client
+            }
+            Err(err) => {
+                eprintln!("Error while creating and connecting client : {}", err);
+                std::process::exit(1);
+            }
+        };
 
-    //send request to server
-    match client.send_request() {
-        Ok(_) => {
-            println!("Request sent");
+        //legacy servers do not know the handshake
+        if !one_shot {
+            let capabilities = ClientServices::worker_capabilities(json_headers, compress);
+            match client.handshake(capabilities, secret.as_deref()) {
+                Ok(capabilities) => {
+                    println!("Handshake done, capabilities: {:?}", capabilities);
+                }
+                Err(err) => {
+                    eprintln!("Error during handshake : {}", err);
+                    exit(1);
+                }
+            };
         }
-        Err(err) => {
-            eprintln!("Error while sending request : {}", err);
-            exit(1);
-        }
-    };
 
-    loop {
-        //get task from server
-        match client.get_task_from_server() {
-            Ok(response) => {
-                let (task, datas) = response;
-                println!("Task received, {:?}", task);
-                //do work (and create image from client)
-                let datas_updated = match client.do_work(&task, datas) {
-                    Ok(datas) => datas,
-                    Err(err) => {
-                        eprintln!("Error while computing datas : {}", err);
+        //send request to server
+        match client.send_request(work_load) {
+            Ok(_) => {
+                println!("Request sent");
+            }
+            Err(err) => {
+                eprintln!("Error while sending request : {}", err);
+                exit(1);
+            }
+        };
+
+        //the session stays open as long as the server answers our results with new tasks
+        let mut answers_expected = 1;
+        'session: loop {
+            //get the answers of the server: one to the request, or one to each result sent
+            let mut batch = Vec::new();
+            let mut retry_after = None;
+            for _ in 0..answers_expected {
+                match client.get_task_from_server() {
+                    Ok(ServerAnswer::Task(task, datas)) => batch.push((task, datas)),
+                    Ok(ServerAnswer::Batch(tasks)) => batch.extend(tasks),
+                    Ok(ServerAnswer::NoWork(delay)) => retry_after = Some(delay),
+                    //le serveur refuse le resultat mais garde la session ouverte
+                    Err(
+                        err @ FraktError::Rejected {
+                            code:
+                                ErrorCode::InvalidResult
+                                | ErrorCode::UnknownTask
+                                | ErrorCode::ChecksumMismatch,
+                            ..
+                        },
+                    ) => {
+                        eprintln!("{}", err);
+                    }
+                    Err(
+                        err @ FraktError::Rejected {
+                            code: ErrorCode::Unauthenticated,
+                            ..
+                        },
+                    ) => {
+                        eprintln!("{}", err);
                         exit(1);
                     }
+                    Err(err @ FraktError::Rejected { .. }) => {
+                        eprintln!("{}, reconnecting", err);
+                        break 'session;
+                    }
+                    Err(_) => {
+                        eprintln!("No data to read currently, waiting 5sec before new attempt");
+                        thread::sleep(time::Duration::from_secs(5));
+                        break 'session;
+                    }
                 };
+            }
+
+            //no task received: ask for new ones, after the delay given by the server
+            if batch.is_empty() {
+                if let Some(retry_after) = retry_after {
+                    println!("No task available, asking again in {:?}", retry_after);
+                    thread::sleep(retry_after);
+                }
+                if let Err(err) = client.send_request(work_load) {
+                    eprintln!("Error while sending request : {}", err);
+                    break;
+                }
+                answers_expected = 1;
+                continue;
+            }
+            println!("{} task(s) received", batch.len());
 
-                //send result to server (new connection needed) -> loop because result sent will make server send a new task
-                client = match ClientServices::new(&host, port) {
+            //do work (and create image from client)
+            let results = match client.do_work(batch) {
+                Ok(results) => results,
+                Err(err) => {
+                    eprintln!("Error while computing datas : {}", err);
+                    break;
+                }
+            };
+
+            //legacy servers close the connection after each answer: a new one is needed to send the result
+            if one_shot {
+                client = match ClientServices::new(&host, port, tls.as_ref(), websocket) {
                     Ok(client) => {
                         println!("Client created and connected");
+                        if let Err(err) = client.set_read_timeout(Some(read_timeout)) {
+                            eprintln!("Error while setting read timeout : {}", err);
+                        }
                         client
                     }
                     Err(_) => {
@@ -96,20 +198,21 @@ fn main() {
                         std::process::exit(1);
                     }
                 };
-                match client.send_result(&task, &datas_updated) {
+            }
+
+            //send results to server -> loop because each result sent will make server send a new task
+            for (task, datas, pixels) in &results {
+                match client.send_result(task, datas, pixels) {
                     Ok(_) => {
                         println!("Result sent");
                     }
                     Err(err) => {
                         eprintln!("Error while sending result : {}", err);
-                        exit(1);
+                        break 'session;
                     }
                 };
             }
-            Err(_) => {
-                eprintln!("No data to read currently, waiting 5sec before new attempt");
-                thread::sleep(time::Duration::from_secs(5));
-            }
-        };
+            answers_expected = results.len();
+        }
     }
 }